@@ -1,33 +1,841 @@
 use std::time::Duration;
 
-use crate::{AuthHttpClient, HttpClient};
-use anyhow::ensure;
+use crate::{
+    authentication::auth::store_user_session_on_disk, device_label, ActiveUserSession,
+    AuthHttpClient, HttpClient, SharedUserSession, COOKIE_SAVE_PATH,
+};
+use anyhow::{bail, ensure};
+use chrono::{TimeDelta, Utc};
 use dioxus::logger::tracing::{error, info};
 use futures_util::{SinkExt, StreamExt};
-use reqwest::Response;
+use reqwest::{header::RETRY_AFTER, Method, RequestBuilder, Response, StatusCode};
 use tokio::{
     select,
     sync::mpsc::{channel, Receiver, Sender},
 };
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 use whatssock_lib::{
-    client::{FetchMessages, LoginRequest, RegisterRequest}, domain_paths::{WS_ESTABLISH_CHATROOM_CONNECTION, GET_FETCH_MESSAGES, GET_FETCH_USER, POST_LOGIN, POST_LOGOUT, POST_NEW_CHATROOM, POST_REGISTER, POST_REQUEST_K_CHATROOM, POST_REQUEST_UK_CHATROOM, POST_SESSION_VERIFICATION}, server::WebSocketChatroomMessageServer, CreateChatroomRequest, FetchKnownChatrooms, FetchUnknownChatroom, MessageFetchType, UserSession
+    client::{ChangePasswordRequest, ConfirmTotpRequest, DeleteAccountRequest, DisableTotpRequest, FetchMessages, ForgotPasswordRequest, LoginRequest, RegisterRequest, ResetPasswordRequest, RevokeSessionRequest, SearchUsersRequest, TwoFactorLoginRequest, UpdatePrivacyRequest, UpdateProfileRequest, VerifyEmailRequest}, domain_paths::{WS_ESTABLISH_CHATROOM_CONNECTION, GET_FETCH_AVATAR, GET_FETCH_MESSAGES, GET_FETCH_USER, POST_CHANGE_PASSWORD, POST_DELETE_ACCOUNT, POST_EXPORT_ACCOUNT, POST_FORGOT_PASSWORD, POST_LEAVE_CHATROOM, POST_LIST_SESSIONS, POST_LOGIN, POST_LOGIN_TOTP, POST_LOGOUT, POST_RESEND_VERIFICATION_EMAIL, POST_REVOKE_OTHER_SESSIONS, POST_REVOKE_SESSION, POST_NEW_CHATROOM, POST_REFRESH_SESSION, POST_REGISTER, POST_REQUEST_K_CHATROOM, POST_REQUEST_UK_CHATROOM, POST_RESET_PASSWORD, POST_SEARCH_USERS, POST_SESSION_VERIFICATION, POST_START_DIRECT_MESSAGE, POST_TOTP_CONFIRM, POST_TOTP_DISABLE, POST_TOTP_ENROLL, POST_TRANSFER_OWNERSHIP, POST_KICK_MEMBER, POST_BAN_MEMBER, POST_UNBAN_MEMBER, POST_LIST_BANS, POST_MUTE_MEMBER, POST_CREATE_INVITE, POST_LIST_INVITES, POST_REVOKE_INVITE, POST_JOIN_BY_INVITE, POST_UPDATE_CHATROOM_SETTINGS, POST_UPDATE_CHATROOM_ICON, POST_DELETE_CHATROOM, GET_FETCH_CHATROOM_ICON, POST_EDIT_MESSAGE, POST_DELETE_MESSAGE, POST_FETCH_MESSAGE_EDITS, POST_ADD_REACTION, POST_REMOVE_REACTION, POST_UPDATE_AVATAR, POST_UPDATE_MEMBER_ROLE, POST_UPDATE_PRIVACY, POST_UPDATE_PROFILE, POST_VERIFY_EMAIL}, server::{RefreshSessionResponse, WebSocketChatroomMessageServer}, BanMemberRequest, ChatroomIconQuery, ChatroomRole, DeleteChatroomRequest, UpdateChatroomSettingsRequest, EditMessageRequest, DeleteMessageRequest, FetchMessageEditsRequest, MessageReactionRequest, WebSocketChatroomMessages, CreateChatroomInviteRequest, CreateChatroomRequest, JoinByInviteRequest, ListChatroomInvitesRequest, RevokeChatroomInviteRequest, KickMemberRequest, ListChatroomBansRequest, MuteMemberRequest, UnbanMemberRequest, FetchKnownChatrooms, FetchUnknownChatroom, LeaveChatroomRequest, MessageFetchType, StartDirectMessageRequest, TransferOwnershipRequest, UpdateMemberRoleRequest, UserSession, UserSessionSecure
 };
 
-impl HttpClient {
-    pub async fn fetch_login(
+/// The session token is refreshed if it expires in less than this amount of time.
+const SESSION_REFRESH_MARGIN: TimeDelta = TimeDelta::minutes(5);
+
+impl HttpClient {
+    pub async fn fetch_login(
+        &self,
+        username: String,
+        password: String,
+    ) -> anyhow::Result<Response> {
+        ensure!(!username.is_empty(), "Username must not be empty.");
+        ensure!(!password.is_empty(), "Password must not be empty.");
+
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, POST_LOGIN))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&LoginRequest {
+                username,
+                password,
+                device_label: device_label(),
+            })?)
+            .send()
+            .await?;
+
+        ensure_not_rate_limited(&response)?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    /// Checks the stored session with the server, the response contains the [`whatssock_lib::client::UserSessionInformation`] of the session's user.
+    pub async fn verify_user_session(&self, user_session: &UserSession) -> anyhow::Result<Response> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, POST_SESSION_VERIFICATION))
+            .bearer_auth(user_session.to_bearer_token())
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn send_register_request(
+        &self,
+        username: String,
+        password: String,
+        email: String,
+    ) -> anyhow::Result<Response> {
+        ensure!(!username.is_empty(), "Username must not be empty.");
+        ensure!(!password.is_empty(), "Password must not be empty.");
+        ensure!(!email.is_empty(), "Email must not be empty.");
+
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, POST_REGISTER))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&RegisterRequest {
+                username,
+                password,
+                email,
+                device_label: device_label(),
+            })?)
+            .send()
+            .await?;
+
+        ensure_not_rate_limited(&response)?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    /// Finishes a login which requires two-factor authentication, `code` can be either a TOTP code or a recovery code.
+    pub async fn complete_two_factor_login(
+        &self,
+        challenge_token: String,
+        code: String,
+    ) -> anyhow::Result<Response> {
+        ensure!(!code.is_empty(), "Code must not be empty.");
+
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, POST_LOGIN_TOTP))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&TwoFactorLoginRequest {
+                challenge_token,
+                code,
+            })?)
+            .send()
+            .await?;
+
+        ensure_not_rate_limited(&response)?;
+
+        match response.status() {
+            StatusCode::FORBIDDEN => bail!("The code is incorrect."),
+            StatusCode::NOT_FOUND => bail!("The login attempt has expired, please log in again."),
+            _ => (),
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    /// Requests a password reset code to be sent to the email address of the account.
+    pub async fn request_password_reset(&self, username: String) -> anyhow::Result<Response> {
+        ensure!(!username.is_empty(), "Username must not be empty.");
+
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, POST_FORGOT_PASSWORD))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&ForgotPasswordRequest { username })?)
+            .send()
+            .await?;
+
+        ensure_not_rate_limited(&response)?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn reset_password(
+        &self,
+        token: String,
+        new_password: String,
+    ) -> anyhow::Result<Response> {
+        ensure!(!new_password.is_empty(), "Password must not be empty.");
+
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, POST_RESET_PASSWORD))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&ResetPasswordRequest {
+                token,
+                new_password,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("The reset code is invalid or has expired.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+}
+
+impl AuthHttpClient {
+    /// Returns the current [`UserSession`], the session token is refreshed first if it is about to expire.
+    pub async fn current_user_session(&self) -> anyhow::Result<UserSession> {
+        if self.session_needs_refresh() {
+            let _refresh_guard = self.refresh_lock.lock().await;
+
+            // Another request might have already refreshed the session while we were waiting
+            if self.session_needs_refresh() {
+                self.refresh_session().await?;
+            }
+        }
+
+        Ok(self.user_session.lock().user_session.clone())
+    }
+
+    /// Creates a request to the server's `path` with the `Authorization` header set to the current session.
+    async fn authorized_request(&self, method: Method, path: &str) -> anyhow::Result<RequestBuilder> {
+        let user_session = self.current_user_session().await?;
+
+        Ok(self
+            .client
+            .request(method, format!("{}{}", self.client.base_url, path))
+            .bearer_auth(user_session.to_bearer_token()))
+    }
+
+    fn session_needs_refresh(&self) -> bool {
+        self.user_session.lock().expires_at - SESSION_REFRESH_MARGIN <= Utc::now().naive_utc()
+    }
+
+    /// Rotates the session token, the new token and expiry date are written into the shared session.
+    async fn refresh_session(&self) -> anyhow::Result<()> {
+        let user_session = self.user_session.lock().user_session.clone();
+
+        // This cannot go through `authorized_request`, as that would try refreshing the session again
+        let response = self
+            .client
+            .post(format!("{}{}", self.client.base_url, POST_REFRESH_SESSION))
+            .bearer_auth(user_session.to_bearer_token())
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        let refreshed_session = serde_json::from_str::<RefreshSessionResponse>(&response.text().await?)?;
+
+        let user_session_secure = UserSessionSecure {
+            user_id: refreshed_session.user_session.user_id,
+            session_token: refreshed_session.user_session.session_token,
+            encryption_key: self.encryption_key.to_bytes(),
+            expires_at: refreshed_session.expires_at,
+        };
+
+        // The server has revoked the previous token already, so the new one is put to use before anything else can fail
+        *self.user_session.lock() = ActiveUserSession {
+            user_session: refreshed_session.user_session,
+            expires_at: refreshed_session.expires_at,
+        };
+
+        // Keep the stored session up to date, otherwise the next startup would try to use the rotated token
+        // Failing to store it only affects the next startup, so the refresh itself still succeeds
+        if let Err(err) = store_user_session_on_disk(&user_session_secure, (*COOKIE_SAVE_PATH).clone()) {
+            error!("Error occured when storing the refreshed session on disk: {err}");
+        }
+
+        Ok(())
+    }
+
+    pub async fn request_logout(&self) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_LOGOUT)
+            .await?
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn verify_email(&self, token: String) -> anyhow::Result<Response> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.client.base_url, POST_VERIFY_EMAIL))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&VerifyEmailRequest { token })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("The verification code is invalid or has expired.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn resend_verification_email(&self) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_RESEND_VERIFICATION_EMAIL)
+            .await?
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            bail!("A verification code has just been sent, please wait a minute before requesting a new one.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn change_password(
+        &self,
+        current_password: String,
+        new_password: String,
+    ) -> anyhow::Result<Response> {
+        ensure!(!new_password.is_empty(), "Password must not be empty.");
+
+        let response = self
+            .authorized_request(Method::POST, POST_CHANGE_PASSWORD)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&ChangePasswordRequest {
+                current_password,
+                new_password,
+            })?)
+            .send()
+            .await?;
+
+        ensure_not_rate_limited(&response)?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("The current password is incorrect.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    /// Starts enabling two-factor authentication, the returned provisioning URI has to be added to an authenticator app.
+    pub async fn enroll_totp(&self) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_TOTP_ENROLL)
+            .await?
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    /// Enables two-factor authentication with the first code generated by the authenticator app, the response contains the recovery codes.
+    pub async fn confirm_totp(&self, code: String) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_TOTP_CONFIRM)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&ConfirmTotpRequest { code })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("The code is incorrect.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn disable_totp(&self, password: String, code: String) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_TOTP_DISABLE)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&DisableTotpRequest { password, code })?)
+            .send()
+            .await?;
+
+        ensure_not_rate_limited(&response)?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("The password or the code is incorrect.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn export_account_data(&self) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_EXPORT_ACCOUNT)
+            .await?
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn delete_account(
+        &self,
+        password: String,
+        totp_code: Option<String>,
+    ) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_DELETE_ACCOUNT)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&DeleteAccountRequest {
+                password,
+                totp_code,
+            })?)
+            .send()
+            .await?;
+
+        ensure_not_rate_limited(&response)?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("The password or the code is incorrect.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn update_profile(
+        &self,
+        display_name: Option<String>,
+        bio: Option<String>,
+        status: Option<String>,
+    ) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_UPDATE_PROFILE)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&UpdateProfileRequest {
+                display_name,
+                bio,
+                status,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::BAD_REQUEST {
+            bail!("One of the profile fields is too long.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    /// Uploads a new avatar image, an empty image removes the current avatar.
+    pub async fn update_avatar(&self, avatar_image: Vec<u8>) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_UPDATE_AVATAR)
+            .await?
+            .header("Content-Type", "application/octet-stream")
+            .body(avatar_image)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            bail!("The avatar image is too large.");
+        }
+
+        if response.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            bail!("The avatar has to be a PNG, JPEG, GIF or WebP image.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn update_privacy(&self, discoverable: bool) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_UPDATE_PRIVACY)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&UpdatePrivacyRequest { discoverable })?)
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn search_users(&self, query: String, page: i64) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_SEARCH_USERS)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&SearchUsersRequest { query, page })?)
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn fetch_active_sessions(&self) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_LIST_SESSIONS)
+            .await?
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn revoke_session(&self, session_id: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_REVOKE_SESSION)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&RevokeSessionRequest {
+                session_id,
+            })?)
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn revoke_other_sessions(&self) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_REVOKE_OTHER_SESSIONS)
+            .await?
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn fetch_unknown_chatroom(
+        &self,
+        chatroom_id: String,
+        chatroom_passw: Option<String>,
+    ) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_REQUEST_UK_CHATROOM)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&FetchUnknownChatroom {
+                chatroom_id,
+                password: chatroom_passw,
+            })?)
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn fetch_known_chatrooms(&self, chatroom_uids: Vec<i32>) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_REQUEST_K_CHATROOM)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&FetchKnownChatrooms {
+                chatroom_uids,
+            })?)
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn create_new_chatroom(
+        &self,
+        chatroom_name: String,
+        chatroom_passw: Option<String>,
+    ) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_NEW_CHATROOM)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&CreateChatroomRequest {
+                chatroom_name,
+                chatroom_passw,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("Verify your email address before creating a chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn start_direct_message(&self, user_id: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_START_DIRECT_MESSAGE)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&StartDirectMessageRequest { user_id })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("Verify your email address before starting a conversation.");
+        }
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("This user does not exist.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn leave_chatroom(&self, chatroom_uid: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_LEAVE_CHATROOM)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&LeaveChatroomRequest { chatroom_uid })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("You are not a participant of this chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn update_member_role(
+        &self,
+        chatroom_uid: i32,
+        user_id: i32,
+        role: ChatroomRole,
+    ) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_UPDATE_MEMBER_ROLE)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&UpdateMemberRoleRequest {
+                chatroom_uid,
+                user_id,
+                role,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You can only manage the members ranked below you.");
+        }
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("This user is not a member of the chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn transfer_chatroom_ownership(
+        &self,
+        chatroom_uid: i32,
+        user_id: i32,
+    ) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_TRANSFER_OWNERSHIP)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&TransferOwnershipRequest {
+                chatroom_uid,
+                user_id,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("Only the owner can transfer the chatroom.");
+        }
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("This user is not a member of the chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn kick_member(&self, chatroom_uid: i32, user_id: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_KICK_MEMBER)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&KickMemberRequest {
+                chatroom_uid,
+                user_id,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are not allowed to kick this member.");
+        }
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("This user is not a member of the chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn ban_member(
         &self,
-        username: String,
-        password: String,
+        chatroom_uid: i32,
+        user_id: i32,
+        duration_secs: Option<u32>,
+        reason: Option<String>,
     ) -> anyhow::Result<Response> {
-        ensure!(!username.is_empty(), "Username must not be empty.");
-        ensure!(!password.is_empty(), "Password must not be empty.");
+        let response = self
+            .authorized_request(Method::POST, POST_BAN_MEMBER)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&BanMemberRequest {
+                chatroom_uid,
+                user_id,
+                duration_secs,
+                reason,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are not allowed to ban this user.");
+        }
+
+        if response.status() == StatusCode::BAD_REQUEST {
+            bail!("The reason of the ban is too long.");
+        }
+
+        let response_code = response.status().as_u16();
 
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn unban_member(&self, chatroom_uid: i32, user_id: i32) -> anyhow::Result<Response> {
         let response = self
-            .client
-            .post(format!("{}{}", self.base_url, POST_LOGIN))
+            .authorized_request(Method::POST, POST_UNBAN_MEMBER)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&UnbanMemberRequest {
+                chatroom_uid,
+                user_id,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("This user is not banned from the chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn list_chatroom_bans(&self, chatroom_uid: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_LIST_BANS)
+            .await?
             .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&LoginRequest { username, password })?)
+            .body(serde_json::to_string(&ListChatroomBansRequest { chatroom_uid })?)
             .send()
             .await?;
 
@@ -38,43 +846,75 @@ impl HttpClient {
         Ok(response)
     }
 
-    pub async fn send_register_request(
+    /// Mutes the member for the given amount of seconds, or unmutes them if `duration_secs` is `None`.
+    pub async fn mute_member(
         &self,
-        username: String,
-        password: String,
-        email: String,
+        chatroom_uid: i32,
+        user_id: i32,
+        duration_secs: Option<u32>,
     ) -> anyhow::Result<Response> {
-        ensure!(!username.is_empty(), "Username must not be empty.");
-        ensure!(!password.is_empty(), "Password must not be empty.");
-        ensure!(!email.is_empty(), "Email must not be empty.");
+        let response = self
+            .authorized_request(Method::POST, POST_MUTE_MEMBER)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&MuteMemberRequest {
+                chatroom_uid,
+                user_id,
+                duration_secs,
+            })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are not allowed to mute this member.");
+        }
+
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("This user is not a member of the chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
 
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn create_chatroom_invite(
+        &self,
+        chatroom_uid: i32,
+        max_uses: Option<u32>,
+        duration_secs: Option<u32>,
+    ) -> anyhow::Result<Response> {
         let response = self
-            .client
-            .post(format!("{}{}", self.base_url, POST_REGISTER))
+            .authorized_request(Method::POST, POST_CREATE_INVITE)
+            .await?
             .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&RegisterRequest {
-                username,
-                password,
-                email,
+            .body(serde_json::to_string(&CreateChatroomInviteRequest {
+                chatroom_uid,
+                max_uses,
+                duration_secs,
             })?)
             .send()
             .await?;
 
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are not allowed to create invites to this chatroom.");
+        }
+
         let response_code = response.status().as_u16();
 
         ensure!(response_code == 200, "Response code: {response_code}");
 
         Ok(response)
     }
-}
 
-impl AuthHttpClient {
-    pub async fn request_logout(&self) -> anyhow::Result<Response> {
+    pub async fn list_chatroom_invites(&self, chatroom_uid: i32) -> anyhow::Result<Response> {
         let response = self
-            .client
-            .post(format!("{}{}", self.client.base_url, POST_LOGOUT))
+            .authorized_request(Method::POST, POST_LIST_INVITES)
+            .await?
             .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&self.user_session)?)
+            .body(serde_json::to_string(&ListChatroomInvitesRequest { chatroom_uid })?)
             .send()
             .await?;
 
@@ -85,26 +925,22 @@ impl AuthHttpClient {
         Ok(response)
     }
 
-    pub async fn fetch_unknown_chatroom(
-        &self,
-        chatroom_id: String,
-        chatroom_passw: Option<String>,
-    ) -> anyhow::Result<Response> {
+    pub async fn revoke_chatroom_invite(&self, chatroom_uid: i32, invite_id: i32) -> anyhow::Result<Response> {
         let response = self
-            .client
-            .post(format!(
-                "{}{}",
-                self.client.base_url, POST_REQUEST_UK_CHATROOM
-            ))
+            .authorized_request(Method::POST, POST_REVOKE_INVITE)
+            .await?
             .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&FetchUnknownChatroom {
-                user_session: self.user_session.clone(),
-                chatroom_id,
-                password: chatroom_passw,
+            .body(serde_json::to_string(&RevokeChatroomInviteRequest {
+                chatroom_uid,
+                invite_id,
             })?)
             .send()
             .await?;
 
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("This invite does not exist anymore.");
+        }
+
         let response_code = response.status().as_u16();
 
         ensure!(response_code == 200, "Response code: {response_code}");
@@ -112,21 +948,25 @@ impl AuthHttpClient {
         Ok(response)
     }
 
-    pub async fn fetch_known_chatrooms(&self, chatroom_uids: Vec<i32>) -> anyhow::Result<Response> {
+    pub async fn join_by_invite(&self, invite_code: String) -> anyhow::Result<Response> {
+        ensure!(!invite_code.trim().is_empty(), "Invite code must not be empty.");
+
         let response = self
-            .client
-            .post(format!(
-                "{}{}",
-                self.client.base_url, POST_REQUEST_K_CHATROOM
-            ))
+            .authorized_request(Method::POST, POST_JOIN_BY_INVITE)
+            .await?
             .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&FetchKnownChatrooms {
-                user_session: self.user_session.clone(),
-                chatroom_uids,
-            })?)
+            .body(serde_json::to_string(&JoinByInviteRequest { invite_code })?)
             .send()
             .await?;
 
+        if response.status() == StatusCode::NOT_FOUND {
+            bail!("This invite is invalid, expired or has been used up.");
+        }
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are banned from this chatroom.");
+        }
+
         let response_code = response.status().as_u16();
 
         ensure!(response_code == 200, "Response code: {response_code}");
@@ -134,23 +974,216 @@ impl AuthHttpClient {
         Ok(response)
     }
 
-    pub async fn create_new_chatroom(
+    /// Changes the settings of the chatroom, the settings which are `None` are left unchanged.
+    pub async fn update_chatroom_settings(
         &self,
-        chatroom_name: String,
-        chatroom_passw: Option<String>,
+        chatroom_uid: i32,
+        chatroom_name: Option<String>,
+        description: Option<String>,
+        password: Option<String>,
     ) -> anyhow::Result<Response> {
         let response = self
-            .client
-            .post(format!("{}{}", self.client.base_url, POST_NEW_CHATROOM))
+            .authorized_request(Method::POST, POST_UPDATE_CHATROOM_SETTINGS)
+            .await?
             .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&CreateChatroomRequest {
-                user_session: self.user_session.clone(),
+            .body(serde_json::to_string(&UpdateChatroomSettingsRequest {
+                chatroom_uid,
                 chatroom_name,
-                chatroom_passw,
+                description,
+                password,
             })?)
             .send()
             .await?;
 
+        if response.status() == StatusCode::BAD_REQUEST {
+            bail!("The name must not be empty, and the name or the description is too long.");
+        }
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are not allowed to change the settings of this chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    /// Uploads a new chatroom icon, an empty image removes the current icon.
+    pub async fn update_chatroom_icon(&self, chatroom_uid: i32, icon_image: Vec<u8>) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_UPDATE_CHATROOM_ICON)
+            .await?
+            .query(&ChatroomIconQuery { chatroom_uid })
+            .header("Content-Type", "application/octet-stream")
+            .body(icon_image)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            bail!("The icon image is too large.");
+        }
+
+        if response.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            bail!("The icon has to be a PNG, JPEG, GIF or WebP image.");
+        }
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are not allowed to change the icon of this chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn fetch_chatroom_icon(&self, chatroom_uid: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::GET, GET_FETCH_CHATROOM_ICON)
+            .await?
+            .query(&ChatroomIconQuery { chatroom_uid })
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn delete_chatroom(&self, chatroom_uid: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_DELETE_CHATROOM)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&DeleteChatroomRequest { chatroom_uid })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("Only the owner can delete the chatroom.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn edit_message(&self, message_id: i32, message: WebSocketChatroomMessages) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_EDIT_MESSAGE)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&EditMessageRequest { message_id, message })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::BAD_REQUEST {
+            bail!("The edited message must not be empty or the same as before.");
+        }
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("This message cannot be edited anymore.");
+        }
+
+        if response.status() == StatusCode::CONFLICT {
+            bail!("The message has been edited somewhere else in the meantime.");
+        }
+
+        if response.status() == StatusCode::GONE {
+            bail!("The message has been deleted.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn delete_message(&self, message_id: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_DELETE_MESSAGE)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&DeleteMessageRequest { message_id })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are not allowed to delete this message.");
+        }
+
+        if response.status() == StatusCode::GONE {
+            bail!("The message has already been deleted.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn add_reaction(&self, message_id: i32, emoji: String) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_ADD_REACTION)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&MessageReactionRequest { message_id, emoji })?)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::BAD_REQUEST {
+            bail!("This message cannot be reacted to with more emoji.");
+        }
+
+        if response.status() == StatusCode::FORBIDDEN {
+            bail!("You are not allowed to react in this chatroom.");
+        }
+
+        if response.status() == StatusCode::GONE {
+            bail!("The message has been deleted.");
+        }
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn remove_reaction(&self, message_id: i32, emoji: String) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_REMOVE_REACTION)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&MessageReactionRequest { message_id, emoji })?)
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
+    pub async fn fetch_message_edits(&self, message_id: i32) -> anyhow::Result<Response> {
+        let response = self
+            .authorized_request(Method::POST, POST_FETCH_MESSAGE_EDITS)
+            .await?
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&FetchMessageEditsRequest { message_id })?)
+            .send()
+            .await?;
+
         let response_code = response.status().as_u16();
 
         ensure!(response_code == 200, "Response code: {response_code}");
@@ -174,18 +1207,33 @@ impl AuthHttpClient {
         Ok(response)
     }
 
+    pub async fn fetch_avatar(&self, user_id: i32) -> anyhow::Result<Response> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.client.base_url, GET_FETCH_AVATAR))
+            .header("Content-Type", "text/plain")
+            .body(user_id.to_string())
+            .send()
+            .await?;
+
+        let response_code = response.status().as_u16();
+
+        ensure!(response_code == 200, "Response code: {response_code}");
+
+        Ok(response)
+    }
+
     pub async fn fetch_messages(
         &self,
         message_fetch: MessageFetchType,
     ) -> anyhow::Result<Response> {
         let response = self
-            .client
-            .get(format!("{}{}", self.client.base_url, GET_FETCH_MESSAGES))
+            .authorized_request(Method::GET, GET_FETCH_MESSAGES)
+            .await?
             .header("Content-Type", "application/json")
             .body(
                 serde_json::to_string(&FetchMessages {
                     message_request: message_fetch,
-                    user_session: self.user_session.clone(),
                 })
                 .unwrap(),
             )
@@ -200,8 +1248,23 @@ impl AuthHttpClient {
     }
 }
 
+/// Returns an error with a readable message if the server has rejected the request because of too many attempts.
+fn ensure_not_rate_limited(response: &Response) -> anyhow::Result<()> {
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|header_value| header_value.to_str().ok())
+            .unwrap_or("a few");
+
+        bail!("Too many attempts, please try again in {retry_after} seconds.");
+    }
+
+    Ok(())
+}
+
 pub fn init_websocket_connection(
-    user_session: UserSession,
+    user_session: SharedUserSession,
 ) -> (Sender<WebSocketChatroomMessageServer>, Receiver<Message>) {
     let (websocket_sender, mut websocket_receiver) = channel::<WebSocketChatroomMessageServer>(255);
     let (remote_sender, remote_receiver) = channel::<Message>(255);
@@ -234,10 +1297,13 @@ pub fn init_websocket_connection(
 
             let (mut write, mut read) = ws_socket.split();
 
+            // Always authenticate with the latest session token, as it might have been refreshed since the last connection
+            let current_user_session = user_session.lock().user_session.clone();
+
             // Send the first authentication message
             write
                 .send(Message::Binary(
-                    rmp_serde::to_vec(&user_session).unwrap().into(),
+                    rmp_serde::to_vec(&current_user_session).unwrap().into(),
                 ))
                 .await
                 .unwrap();
@@ -7,22 +7,26 @@ use chacha20poly1305::{
 };
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
-use whatssock_lib::{server::{LoginResponse, LoginResponseSecure}, UserSession};
-
-use crate::UserAccount;
+use whatssock_lib::{server::{LoginOutcome, LoginResponseSecure}, UserSession, UserSessionSecure};
 
 pub fn deserialize_into_login_response(json_input: String) -> Result<LoginResponseSecure> {
     Ok(serde_json::from_str(&json_input)?)
 }
 
+pub fn deserialize_into_login_outcome(json_input: String) -> Result<LoginOutcome> {
+    Ok(serde_json::from_str(&json_input)?)
+}
+
 pub fn deserialize_into_user_session(json_input: String) -> Result<UserSession> {
     Ok(serde_json::from_str(&json_input)?)
 }
 
-pub fn store_user_account_on_disk(user_account: &UserAccount, path: PathBuf) -> Result<()> {
+/// Stores the session encrypted with the machine's hwid key, so that the user can be logged in automaticly on the next startup.
+/// The user's password is never stored, the session can be revoked from the server at any time.
+pub fn store_user_session_on_disk(user_session_secure: &UserSessionSecure, path: PathBuf) -> Result<()> {
     let hwid_key = create_hwid_key()?;
 
-    let serialized_bytes = rmp_serde::to_vec(&user_account)?;
+    let serialized_bytes = rmp_serde::to_vec(&user_session_secure)?;
 
     let encrypted_user_session = encrypt_bytes(serialized_bytes, hwid_key)?;
 
@@ -31,6 +35,15 @@ pub fn store_user_account_on_disk(user_account: &UserAccount, path: PathBuf) ->
     Ok(())
 }
 
+/// Removes the stored session, this should be called when the session is no longer valid.
+pub fn remove_user_session_from_disk(path: PathBuf) -> Result<()> {
+    if fs::exists(&path)? {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
 pub fn create_hwid_key() -> Result<
     sha2::digest::generic_array::GenericArray<
         u8,
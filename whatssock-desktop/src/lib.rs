@@ -2,6 +2,7 @@ use std::{
     fmt::Debug, fs, ops::{Deref, DerefMut}, path::PathBuf, sync::{Arc, LazyLock}
 };
 
+use chrono::NaiveDateTime;
 use dioxus::prelude::Routable;
 use dioxus::prelude::*;
 use dirs::data_local_dir;
@@ -14,7 +15,7 @@ use whatssock_lib::{server::WebSocketChatroomMessageServer, UserSession};
 pub mod api_requests;
 pub mod authentication;
 pub mod ui;
-use crate::ui::{login::Login, main_page::MainPage, not_found::NotFound, register::Register};
+use crate::ui::{change_password::ChangePassword, edit_profile::EditProfile, forgot_password::ForgotPassword, login::Login, main_page::MainPage, not_found::NotFound, register::Register};
 
 #[derive(Debug, Clone)]
 pub struct HttpClient {
@@ -45,28 +46,47 @@ impl DerefMut for HttpClient {
 #[derive(Debug, Clone)]
 pub struct AuthHttpClient {
     client: HttpClient,
-    user_session: UserSession,
+    user_session: SharedUserSession,
     encryption_key: SessionEncryptionKey,
+    /// Makes sure only one refresh request is sent at a time, as the old token stops working once it has been rotated.
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl AuthHttpClient {
-    pub fn new(client: HttpClient, user_session: UserSession, encryption_key: SessionEncryptionKey) -> Self {
+    pub fn new(client: HttpClient, user_session: SharedUserSession, encryption_key: SessionEncryptionKey) -> Self {
         Self {
             client,
             user_session,
-            encryption_key
+            encryption_key,
+            refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
         }
     }
 }
 
+/// The session of the logged in user and the date its token expires at.
+#[derive(Debug, Clone)]
+pub struct ActiveUserSession {
+    pub user_session: UserSession,
+    pub expires_at: NaiveDateTime,
+}
+
+/// The [`ActiveUserSession`] shared between the [`AuthHttpClient`] and the WebSocket connection, so that both of them use the latest token after a refresh.
+pub type SharedUserSession = Arc<Mutex<ActiveUserSession>>;
+
 #[derive(Routable, PartialEq, Clone)]
 pub enum Route {
     #[route("/")]
     Login {},
     #[route("/register")]
     Register {},
+    #[route("/forgot_password")]
+    ForgotPassword {},
     #[route("/chats")]
     MainPage {},
+    #[route("/change_password")]
+    ChangePassword {},
+    #[route("/profile")]
+    EditProfile {},
     #[route("/:..segments")]
     NotFound { segments: Vec<String> },
 }
@@ -85,6 +105,14 @@ pub static COOKIE_SAVE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
     cookie_save_path
 });
 
+/// Returns a human readable name of this device, which is sent to the server when creating a new session.
+pub fn device_label() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .map(|host_name| format!("Whatssock Desktop ({host_name})"))
+        .unwrap_or_else(|_| String::from("Whatssock Desktop"))
+}
+
 pub type HttpWebClient = Arc<Mutex<HttpClient>>;
 
 #[derive(Clone)]
@@ -104,6 +132,17 @@ pub enum RequestQueueState {
 #[derive(Clone)]
 pub struct SessionEncryptionKey(pub Arc<SecureArray<u8, 32>>);
 
+impl SessionEncryptionKey {
+    /// Copies the key out of the secure memory region.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let mut key = [0_u8; 32];
+
+        self.0.unlocked_scope(|unlocked_key| key.copy_from_slice(unlocked_key));
+
+        key
+    }
+}
+
 impl Debug for SessionEncryptionKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(
@@ -113,9 +152,3 @@ impl Debug for SessionEncryptionKey {
         )
     }
 }
-
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-pub struct UserAccount {
-    pub username: String,
-    pub password: String,
-}
\ No newline at end of file
@@ -5,9 +5,9 @@ use reqwest::Client;
 use secure_types::SecureArray;
 use std::{format, fs, path::PathBuf, sync::Arc};
 use whatssock_desktop::{
-    api_requests::init_websocket_connection, authentication::auth::{create_hwid_key, decrypt_bytes}, HttpClient, Route, SessionEncryptionKey, UserAccount, COOKIE_SAVE_PATH
+    api_requests::init_websocket_connection, authentication::auth::{create_hwid_key, decrypt_bytes, remove_user_session_from_disk}, ActiveUserSession, HttpClient, Route, SessionEncryptionKey, SharedUserSession, COOKIE_SAVE_PATH
 };
-use whatssock_lib::{client::UserSessionInformation, server::LoginResponseSecure, UserSession};
+use whatssock_lib::{client::UserSessionInformation, server::{LoginResponse, LoginResponseSecure}, UserSession, UserSessionSecure};
 
 const MAIN_CSS: Asset = asset!("/assets/main.css");
 
@@ -64,37 +64,43 @@ fn init_application() -> Element {
         server_sender.clone();
 
     use_root_context(|| server_sender_clone);
-    let mut log_res: Signal<Option<(UserSession, UserSessionInformation)>> = use_signal(|| None);
+    let mut log_res: Signal<Option<LoginResponse>> = use_signal(|| None);
     use_root_context::<Signal<Option<(UserSession, UserSessionInformation)>>>(|| Signal::new(None));
 
     if let Ok(encrypted_bytes) = fs::read(&*COOKIE_SAVE_PATH) {
         // We should decrypt the bytes so that we can get the cookie
-        match decrypt_bytes::<UserAccount>(encrypted_bytes, create_hwid_key().unwrap_or_default()) {
-            Ok(user_account) => {
-                let user_account = user_account.clone();
-                let server_sender = server_sender.clone();
+        match decrypt_bytes::<UserSessionSecure>(encrypted_bytes, create_hwid_key().unwrap_or_default()) {
+            Ok(user_session_secure) => {
+                let client = server_sender.lock().clone();
 
                 spawn(async move {
+                    let (user_session, _) = user_session_secure.clone().pop_secure_key();
+
                     // Verify user session with server
-                    match server_sender
-                        .lock()
-                        .fetch_login(user_account.username, user_account.password)
-                        .await
-                    {
+                    match client.verify_user_session(&user_session).await {
                         Ok(response) => {
-                            let login_response_secure = serde_json::from_str::<LoginResponseSecure>(
+                            let user_information = serde_json::from_str::<UserSessionInformation>(
                                 &response.text().await.unwrap(),
                             )
                             .unwrap();
 
-                            let (login_response, encryption_key) = login_response_secure.pop_secure_key();
+                            let (login_response, encryption_key) = LoginResponseSecure {
+                                user_information,
+                                user_session_secure,
+                            }
+                            .pop_secure_key();
 
                             provide_root_context(SessionEncryptionKey(Arc::new(SecureArray::new(encryption_key).unwrap())));
-                            
-                            log_res.set(Some((login_response.user_session, login_response.user_information)));
+
+                            log_res.set(Some(login_response));
                         }
                         Err(err) => {
-                            error!("{err}");
+                            // The session has expired or has been revoked, the user has to log in again
+                            error!("Stored session has been rejected: {err}");
+
+                            if let Err(err) = remove_user_session_from_disk((*COOKIE_SAVE_PATH).clone()) {
+                                error!("Error occured while removing the stored session: {err}");
+                            }
                         }
                     };
                 });
@@ -106,11 +112,17 @@ fn init_application() -> Element {
     }
 
     use_effect(move || {
-        if let Some((usr_session, user_info)) = (*log_res.read()).clone() {
+        if let Some(login_response) = (*log_res.read()).clone() {
             let mut session = use_context::<Signal<Option<(UserSession, UserSessionInformation)>>>();
-            session.set(Some((usr_session.clone(), user_info)));
+            session.set(Some((login_response.user_session.clone(), login_response.user_information)));
+
+            let shared_user_session: SharedUserSession = provide_root_context(Arc::new(Mutex::new(ActiveUserSession {
+                user_session: login_response.user_session,
+                expires_at: login_response.session_expires_at,
+            })));
+
             provide_root_context({
-                let (sender, reciever) = init_websocket_connection(usr_session.clone());
+                let (sender, reciever) = init_websocket_connection(shared_user_session);
 
                 (sender, Arc::new(Mutex::new(reciever)))
             });
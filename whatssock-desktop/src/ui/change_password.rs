@@ -0,0 +1,151 @@
+use std::fmt::Display;
+
+use crate::{ApplicationContext, Route};
+use dioxus::{logger::tracing, prelude::*};
+use whatssock_lib::server::ChangePasswordResponse;
+
+enum AttemptResult {
+    Attempted(String),
+    Succeeded(String),
+    Failed(String),
+}
+
+impl Display for AttemptResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AttemptResult::Attempted(inner) => inner,
+            AttemptResult::Succeeded(inner) => inner,
+            AttemptResult::Failed(inner) => inner,
+        })
+    }
+}
+
+#[component]
+pub fn ChangePassword() -> Element {
+    let client = use_context::<ApplicationContext>().authed_http_client;
+    let navigator = use_navigator();
+    let mut log_res: Signal<Option<AttemptResult>> = use_signal(|| None);
+    let mut current_password = use_signal(String::new);
+    let mut new_password = use_signal(String::new);
+    let mut new_password_confirmation = use_signal(String::new);
+
+    rsx! {
+        div {
+            id: "login_page_container",
+            div {
+                id: "main_title",
+                class: "title",
+                "Change your password"
+            }
+
+            div {
+                id: "user_input_fields",
+
+                div {
+                    id: "password_field",
+                    input {
+                        oninput: move |event| current_password.set(event.value()),
+                        placeholder: "Current password",
+                        r#type: "password",
+                    }
+                }
+
+                div {
+                    id: "password_field",
+                    input {
+                        oninput: move |event| new_password.set(event.value()),
+                        placeholder: "New password",
+                        r#type: "password",
+                    }
+                }
+
+                div {
+                    id: "password_field",
+                    input {
+                        oninput: move |event| new_password_confirmation.set(event.value()),
+                        placeholder: "Confirm new password",
+                        r#type: "password",
+                    }
+                }
+
+                button { id: "ui_button", class: "button", onclick: move |_| {
+                    if new_password() != new_password_confirmation() {
+                        log_res.set(Some(AttemptResult::Failed("The passwords do not match.".to_string())));
+
+                        return;
+                    }
+
+                    // Update state
+                    log_res.set(Some(AttemptResult::Attempted("Changing password...".to_string())));
+
+                    let client = client.clone();
+
+                    spawn(async move {
+                        match client.change_password(current_password.to_string(), new_password.to_string()).await {
+                            Ok(response) => {
+                                let change_response = serde_json::from_str::<ChangePasswordResponse>(&response.text().await.unwrap()).unwrap();
+
+                                log_res.set(Some(AttemptResult::Succeeded(format!("Your password has been changed! {} other session(s) have been logged out.", change_response.revoked_count))));
+                            },
+                            Err(err) => {
+                                tracing::error!("Error occured when changing the password: {}", err.to_string());
+
+                                // Update state
+                                log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                            },
+                        }
+                    });
+                }, "Change password" }
+
+                button { id: "ui_button", class: "button", onclick: move |_| {
+                    navigator.push(Route::MainPage {});
+                }, "Back" }
+
+                // Check if there is an existing error message
+                div {
+                    id: "login_result",
+                    {
+                        if let Some(change_result) = &*log_res.read() {
+                            // Display the result
+                            match change_result {
+                                AttemptResult::Attempted(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "attempted",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                                AttemptResult::Succeeded(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "succeeded",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                                AttemptResult::Failed(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "failed",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                            }
+                        }
+                        else {
+                            rsx!()
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::fmt::Display;
+
+use crate::{ApplicationContext, Route};
+use dioxus::{logger::tracing, prelude::*};
+use whatssock_lib::{client::UserSessionInformation, UserLookup, UserSession};
+
+enum AttemptResult {
+    Attempted(String),
+    Succeeded(String),
+    Failed(String),
+}
+
+impl Display for AttemptResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AttemptResult::Attempted(inner) => inner,
+            AttemptResult::Succeeded(inner) => inner,
+            AttemptResult::Failed(inner) => inner,
+        })
+    }
+}
+
+#[component]
+pub fn EditProfile() -> Element {
+    let client = use_context::<ApplicationContext>().authed_http_client;
+    let (user_session, user_information) = use_context::<(UserSession, UserSessionInformation)>();
+    let navigator = use_navigator();
+    let mut log_res: Signal<Option<AttemptResult>> = use_signal(|| None);
+    let mut display_name = use_signal(String::new);
+    let mut bio = use_signal(String::new);
+    let mut status = use_signal(String::new);
+    let client_profile_requester = client.clone();
+    let client_avatar_uploader = client.clone();
+    let client_avatar_remover = client.clone();
+    let client_privacy_updater = client.clone();
+    let is_discoverable = user_information.discoverable;
+    let mut discoverable = use_signal(move || is_discoverable);
+
+    // Fill in the fields with the current profile
+    use_hook(|| {
+        spawn(async move {
+            let profile = match client_profile_requester.fetch_user_information(user_session.user_id).await {
+                Ok(response) => serde_json::from_str::<UserLookup>(&response.text().await.unwrap()).unwrap(),
+                Err(err) => {
+                    tracing::error!("Error occured when fetching the profile: {}", err.to_string());
+
+                    return;
+                },
+            };
+
+            display_name.set(profile.display_name.unwrap_or_default());
+            bio.set(profile.bio.unwrap_or_default());
+            status.set(profile.status.unwrap_or_default());
+        });
+    });
+
+    rsx! {
+        div {
+            id: "login_page_container",
+            div {
+                id: "main_title",
+                class: "title",
+                "Edit your profile"
+            }
+
+            div {
+                id: "user_input_fields",
+
+                div {
+                    id: "username_field",
+                    input {
+                        value: display_name,
+                        oninput: move |event| display_name.set(event.value()),
+                        placeholder: "Display name",
+                    }
+                }
+
+                div {
+                    id: "username_field",
+                    input {
+                        value: status,
+                        oninput: move |event| status.set(event.value()),
+                        placeholder: "Status",
+                    }
+                }
+
+                div {
+                    id: "username_field",
+                    textarea {
+                        value: bio,
+                        oninput: move |event| bio.set(event.value()),
+                        placeholder: "Bio",
+                    }
+                }
+
+                button { id: "ui_button", class: "button", onclick: move |_| {
+                    // Update state
+                    log_res.set(Some(AttemptResult::Attempted("Saving profile...".to_string())));
+
+                    let client = client.clone();
+
+                    spawn(async move {
+                        // Empty fields are cleared by the server
+                        match client.update_profile(Some(display_name.to_string()), Some(bio.to_string()), Some(status.to_string())).await {
+                            Ok(_) => {
+                                log_res.set(Some(AttemptResult::Succeeded("Your profile has been saved!".to_string())));
+                            },
+                            Err(err) => {
+                                tracing::error!("Error occured when updating the profile: {}", err.to_string());
+
+                                // Update state
+                                log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                            },
+                        }
+                    });
+                }, "Save profile" }
+
+                div {
+                    id: "username_field",
+                    "Avatar: "
+                    input {
+                        r#type: "file",
+                        accept: "image/png, image/jpeg, image/gif, image/webp",
+                        onchange: move |event| {
+                            let client = client_avatar_uploader.clone();
+
+                            async move {
+                                let Some(file_engine) = event.files() else {
+                                    return;
+                                };
+
+                                let Some(file_name) = file_engine.files().first().cloned() else {
+                                    return;
+                                };
+
+                                let Some(avatar_image) = file_engine.read_file(&file_name).await else {
+                                    log_res.set(Some(AttemptResult::Failed("The selected file could not be read.".to_string())));
+
+                                    return;
+                                };
+
+                                // Update state
+                                log_res.set(Some(AttemptResult::Attempted("Uploading avatar...".to_string())));
+
+                                match client.update_avatar(avatar_image).await {
+                                    Ok(_) => {
+                                        log_res.set(Some(AttemptResult::Succeeded("Your avatar has been updated!".to_string())));
+                                    },
+                                    Err(err) => {
+                                        tracing::error!("Error occured when uploading the avatar: {}", err.to_string());
+
+                                        // Update state
+                                        log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                                    },
+                                }
+                            }
+                        },
+                    }
+                }
+
+                button { id: "ui_button", class: "button", onclick: move |_| {
+                    let client = client_avatar_remover.clone();
+
+                    spawn(async move {
+                        // Uploading an empty image removes the avatar
+                        match client.update_avatar(Vec::new()).await {
+                            Ok(_) => {
+                                log_res.set(Some(AttemptResult::Succeeded("Your avatar has been removed.".to_string())));
+                            },
+                            Err(err) => {
+                                tracing::error!("Error occured when removing the avatar: {}", err.to_string());
+
+                                // Update state
+                                log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                            },
+                        }
+                    });
+                }, "Remove avatar" }
+
+                div {
+                    id: "username_field",
+                    input {
+                        r#type: "checkbox",
+                        checked: discoverable,
+                        onchange: move |event| {
+                            let client = client_privacy_updater.clone();
+                            let is_discoverable = event.checked();
+
+                            spawn(async move {
+                                match client.update_privacy(is_discoverable).await {
+                                    Ok(_) => {
+                                        discoverable.set(is_discoverable);
+
+                                        log_res.set(Some(AttemptResult::Succeeded("Your privacy settings have been saved!".to_string())));
+                                    },
+                                    Err(err) => {
+                                        tracing::error!("Error occured when updating the privacy settings: {}", err.to_string());
+
+                                        // Update state
+                                        log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                                    },
+                                }
+                            });
+                        },
+                    }
+                    "Let others find me in the user search"
+                }
+
+                button { id: "ui_button", class: "button", onclick: move |_| {
+                    navigator.push(Route::MainPage {});
+                }, "Back" }
+
+                // Check if there is an existing error message
+                div {
+                    id: "login_result",
+                    {
+                        if let Some(profile_result) = &*log_res.read() {
+                            // Display the result
+                            match profile_result {
+                                AttemptResult::Attempted(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "attempted",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                                AttemptResult::Succeeded(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "succeeded",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                                AttemptResult::Failed(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "failed",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                            }
+                        }
+                        else {
+                            rsx!()
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
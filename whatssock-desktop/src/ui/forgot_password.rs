@@ -0,0 +1,191 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::{HttpClient, Route};
+use dioxus::{logger::tracing, prelude::*};
+use parking_lot::Mutex;
+
+enum AttemptResult {
+    Attempted(String),
+    Succeeded(String),
+    Failed(String),
+}
+
+impl Display for AttemptResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AttemptResult::Attempted(inner) => inner,
+            AttemptResult::Succeeded(inner) => inner,
+            AttemptResult::Failed(inner) => inner,
+        })
+    }
+}
+
+#[component]
+pub fn ForgotPassword() -> Element {
+    let client = use_context::<Arc<Mutex<HttpClient>>>().lock().clone();
+    let reset_client = client.clone();
+    let navigator = use_navigator();
+    let mut log_res: Signal<Option<AttemptResult>> = use_signal(|| None);
+    let mut reset_code_sent = use_signal(|| false);
+    let mut username = use_signal(String::new);
+    let mut reset_code = use_signal(String::new);
+    let mut new_password = use_signal(String::new);
+    let mut new_password_confirmation = use_signal(String::new);
+
+    rsx! {
+        div {
+            id: "login_page_container",
+            div {
+                id: "main_title",
+                class: "title",
+                "Reset your password"
+            }
+
+            div {
+                id: "user_input_fields",
+
+                if !reset_code_sent() {
+                    div {
+                        id: "username_field",
+                        input {
+                            oninput: move |event| username.set(event.value()),
+                            placeholder: "Username",
+                        }
+                    }
+
+                    button { id: "ui_button", class: "button", onclick: move |_| {
+                        // Update state
+                        log_res.set(Some(AttemptResult::Attempted("Sending reset code...".to_string())));
+
+                        let client = client.clone();
+
+                        spawn(async move {
+                            match client.request_password_reset(username.to_string()).await {
+                                Ok(_) => {
+                                    // The server does not tell whether the account exists
+                                    log_res.set(Some(AttemptResult::Succeeded("If the account exists, a reset code has been sent to its email address.".to_string())));
+
+                                    reset_code_sent.set(true);
+                                },
+                                Err(err) => {
+                                    tracing::error!("Error occured when requesting a password reset: {}", err.to_string());
+
+                                    // Update state
+                                    log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                                },
+                            }
+                        });
+                    }, "Send reset code" }
+                }
+                else {
+                    div {
+                        id: "username_field",
+                        input {
+                            oninput: move |event| reset_code.set(event.value()),
+                            placeholder: "Reset code",
+                        }
+                    }
+
+                    div {
+                        id: "password_field",
+                        input {
+                            oninput: move |event| new_password.set(event.value()),
+                            placeholder: "New password",
+                            r#type: "password",
+                        }
+                    }
+
+                    div {
+                        id: "password_field",
+                        input {
+                            oninput: move |event| new_password_confirmation.set(event.value()),
+                            placeholder: "Confirm new password",
+                            r#type: "password",
+                        }
+                    }
+
+                    button { id: "ui_button", class: "button", onclick: move |_| {
+                        if new_password() != new_password_confirmation() {
+                            log_res.set(Some(AttemptResult::Failed("The passwords do not match.".to_string())));
+
+                            return;
+                        }
+
+                        // Update state
+                        log_res.set(Some(AttemptResult::Attempted("Resetting password...".to_string())));
+
+                        let client = reset_client.clone();
+
+                        spawn(async move {
+                            match client.reset_password(reset_code.to_string(), new_password.to_string()).await {
+                                Ok(_) => {
+                                    log_res.set(Some(AttemptResult::Succeeded("Your password has been reset! Redirecting....".to_string())));
+
+                                    navigator.replace(Route::Login {});
+                                },
+                                Err(err) => {
+                                    tracing::error!("Error occured when resetting the password: {}", err.to_string());
+
+                                    // Update state
+                                    log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                                },
+                            }
+                        });
+                    }, "Reset password" }
+                }
+
+                li {
+                    Link {
+                        to: Route::Login {  },
+                        "Back to login",
+                    },
+                }
+
+                // Check if there is an existing error message
+                div {
+                    id: "login_result",
+                    {
+                        if let Some(reset_result) = &*log_res.read() {
+                            // Display the result
+                            match reset_result {
+                                AttemptResult::Attempted(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "attempted",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                                AttemptResult::Succeeded(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "succeeded",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                                AttemptResult::Failed(inner) => {
+                                    rsx! {
+                                        div {
+                                            id: "failed",
+                                            {
+                                                inner.to_string()
+                                            }
+                                        }
+                                    }
+                                },
+                            }
+                        }
+                        else {
+                            rsx!()
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,12 @@
 use std::{fmt::Display, sync::Arc};
 
 use crate::{
-    api_requests::init_websocket_connection, authentication::auth::{deserialize_into_login_response, store_user_account_on_disk}, HttpClient, Route, SessionEncryptionKey, COOKIE_SAVE_PATH
+    api_requests::init_websocket_connection, authentication::auth::{deserialize_into_login_outcome, deserialize_into_login_response, store_user_session_on_disk}, ActiveUserSession, HttpClient, Route, SessionEncryptionKey, SharedUserSession, COOKIE_SAVE_PATH
 };
 use dioxus::{logger::tracing, prelude::*};
 use parking_lot::Mutex;
 use secure_types::SecureArray;
-use whatssock_lib::{client::UserSessionInformation, UserSession};
+use whatssock_lib::{client::UserSessionInformation, server::{LoginOutcome, LoginResponse, LoginResponseSecure, TwoFactorChallenge}, UserSession};
 
 enum AttemptResult {
     Attempted(String),
@@ -29,11 +29,14 @@ pub fn Login() -> Element {
     let client = use_context::<Arc<Mutex<HttpClient>>>();
     let navigator = use_navigator();
     let valid_token_redirect = use_context::<Signal<Option<(UserSession, UserSessionInformation)>>>();
-    let mut user_session_login: Signal<Option<(UserSession, UserSessionInformation)>, SyncStorage> =
+    let user_session_login: Signal<Option<LoginResponse>, SyncStorage> =
         use_signal_sync(|| None);
     let mut log_res: Signal<Option<AttemptResult>> = use_signal(|| None);
     let mut username = use_signal(String::new);
     let mut password = use_signal(String::new);
+    let mut two_factor_challenge: Signal<Option<TwoFactorChallenge>> = use_signal(|| None);
+    let mut two_factor_code = use_signal(String::new);
+    let two_factor_client = client.clone();
     rsx! {
         {
             if let Some(valid_session) = valid_token_redirect.read().clone() {
@@ -60,58 +63,104 @@ pub fn Login() -> Element {
             div {
                 id: "user_input_fields",
 
-                div {
-                    id: "username_field",
-                    input {
-                        oninput: move |event| username.set(event.value()),
-                        placeholder: "Username",
+                if two_factor_challenge.read().is_none() {
+                    div {
+                        id: "username_field",
+                        input {
+                            oninput: move |event| username.set(event.value()),
+                            placeholder: "Username",
+                        }
                     }
-                }
 
-                div {
-                    id: "password_field",
-                    input {
-                        oninput: move |event| password.set(event.value()),
-                        placeholder: "Password",
-                        r#type: "password",
+                    div {
+                        id: "password_field",
+                        input {
+                            oninput: move |event| password.set(event.value()),
+                            placeholder: "Password",
+                            r#type: "password",
+                        }
                     }
-                }
 
-                button { id: "ui_button", class: "button", onclick: move |_| {
-                    // Update state
-                    log_res.set(Some(AttemptResult::Attempted("Logging in...".to_string())));
+                    button { id: "ui_button", class: "button", onclick: move |_| {
+                        // Update state
+                        log_res.set(Some(AttemptResult::Attempted("Logging in...".to_string())));
+
+                        let client = client.clone();
+
+                        // Spawn async task
+                        spawn(async move {
+                            let client = client.lock();
 
-                    let client = client.clone();
+                            match client.fetch_login(username.to_string(), password.to_string()).await {
+                                Ok(response) => {
+                                    dbg!(&response);
+                                    match deserialize_into_login_outcome(response.text().await.unwrap()).unwrap() {
+                                        LoginOutcome::LoggedIn(login_response) => {
+                                            finish_login(login_response, user_session_login, log_res);
+                                        },
+                                        LoginOutcome::TwoFactorRequired(challenge) => {
+                                            // Update state
+                                            log_res.set(Some(AttemptResult::Attempted("Enter the code from your authenticator app, or one of your recovery codes.".to_string())));
 
-                    // Spawn async task
-                    spawn(async move {
-                        let client = client.lock();
+                                            two_factor_challenge.set(Some(challenge));
+                                        },
+                                    }
+                                },
+                                Err(err) => {
+                                    tracing::error!("Error occured when logging in: {}", err.to_string());
 
-                        match client.fetch_login(username.to_string(), password.to_string()).await {
-                            Ok(response) => {
-                                dbg!(&response);
-                                let login_response = deserialize_into_login_response(response.text().await.unwrap()).unwrap();
+                                    // Update state
+                                    log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                                },
+                            }
+                        });
+                    }, "Login" }
+                }
+                else {
+                    div {
+                        id: "username_field",
+                        input {
+                            oninput: move |event| two_factor_code.set(event.value()),
+                            placeholder: "Authentication code",
+                        }
+                    }
 
-                                let (user_session, encryption_key) = login_response.user_session_secure.pop_secure_key();
+                    button { id: "ui_button", class: "button", onclick: move |_| {
+                        let Some(challenge) = two_factor_challenge.read().clone() else {
+                            return;
+                        };
 
-                                user_session_login.set(Some((user_session.clone(), login_response.user_information)));
-                                
-                                provide_root_context(SessionEncryptionKey(Arc::new(SecureArray::new(encryption_key).unwrap())));
+                        // Update state
+                        log_res.set(Some(AttemptResult::Attempted("Verifying code...".to_string())));
 
-                                store_user_account_on_disk(&crate::UserAccount { username: username.to_string(), password: password.to_string() }, (*COOKIE_SAVE_PATH).clone()).unwrap();
+                        let client = two_factor_client.clone();
 
-                                // Update state
-                                log_res.set(Some(AttemptResult::Succeeded("Login Successful! Redirecting....".to_string())));
-                            },
-                            Err(err) => {
-                                tracing::error!("Error occured when logging in: {}", err.to_string());
+                        // Spawn async task
+                        spawn(async move {
+                            let client = client.lock().clone();
 
-                                // Update state
-                                log_res.set(Some(AttemptResult::Failed(err.to_string())));
-                            },
-                        }
-                    });
-                }, "Login" }
+                            match client.complete_two_factor_login(challenge.challenge_token, two_factor_code.to_string()).await {
+                                Ok(response) => {
+                                    let login_response = deserialize_into_login_response(response.text().await.unwrap()).unwrap();
+
+                                    finish_login(login_response, user_session_login, log_res);
+                                },
+                                Err(err) => {
+                                    tracing::error!("Error occured when verifying the two-factor code: {}", err.to_string());
+
+                                    // Update state
+                                    log_res.set(Some(AttemptResult::Failed(err.to_string())));
+                                },
+                            }
+                        });
+                    }, "Verify" }
+
+                    button { id: "ui_button", class: "button", onclick: move |_| {
+                        // Start the login over
+                        two_factor_challenge.set(None);
+                        log_res.set(None);
+                    }, "Back" }
+                }
 
                 li {
                     Link {
@@ -120,6 +169,13 @@ pub fn Login() -> Element {
                     },
                 }
 
+                li {
+                    Link {
+                        to: crate::Route::ForgotPassword {  },
+                        "Forgot your password?",
+                    },
+                }
+
                 // Check if there is an existing error message
                 div {
                     id: "login_result",
@@ -167,11 +223,16 @@ pub fn Login() -> Element {
 
                 // Check if we have logged in
                 {
-                    if let Some((user_session, user_information)) = user_session_login.read().clone() {
-                        provide_root_context((user_session.clone(), user_information));
+                    if let Some(login_response) = user_session_login.read().clone() {
+                        provide_root_context((login_response.user_session.clone(), login_response.user_information));
+
+                        let shared_user_session: SharedUserSession = provide_root_context(Arc::new(Mutex::new(ActiveUserSession {
+                            user_session: login_response.user_session,
+                            expires_at: login_response.session_expires_at,
+                        })));
 
                         provide_root_context({
-                            let (sender, reciever) = init_websocket_connection(user_session.clone());
+                            let (sender, reciever) = init_websocket_connection(shared_user_session);
 
                             (sender, Arc::new(Mutex::new(reciever)))
                         });
@@ -193,3 +254,21 @@ pub fn Login() -> Element {
         }
     }
 }
+
+/// Stores the session accepted by the server, so that the page can redirect to the chats.
+fn finish_login(
+    login_response: LoginResponseSecure,
+    mut user_session_login: Signal<Option<LoginResponse>, SyncStorage>,
+    mut log_res: Signal<Option<AttemptResult>>,
+) {
+    store_user_session_on_disk(&login_response.user_session_secure, (*COOKIE_SAVE_PATH).clone()).unwrap();
+
+    let (login_response, encryption_key) = login_response.pop_secure_key();
+
+    user_session_login.set(Some(login_response));
+
+    provide_root_context(SessionEncryptionKey(Arc::new(SecureArray::new(encryption_key).unwrap())));
+
+    // Update state
+    log_res.set(Some(AttemptResult::Succeeded("Login Successful! Redirecting....".to_string())));
+}
@@ -4,10 +4,12 @@ use std::{
 };
 
 use dashmap::DashMap;
-use dioxus::prelude::*;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use dioxus::{logger::tracing, prelude::*};
 use dioxus_toast::{ToastInfo, ToastManager};
 use futures_util::StreamExt;
 use parking_lot::Mutex;
+use reqwest::header::CONTENT_TYPE;
 use tokio::{
     select,
     sync::{
@@ -17,14 +19,29 @@ use tokio::{
 };
 use tokio_tungstenite::tungstenite::Message;
 use whatssock_lib::{
-    client::{UserSessionInformation, WebSocketChatroomMessageClient},
-    server::WebSocketChatroomMessageServer,
-    BulkMessagesFromId, BulkMessagesFromLatest, ChatroomMessageResponse, FetchChatroomResponse,
-    FetchKnownChatroomResponse, FetchMessagesResponse, MessageFetchType, UserLookup, UserSession,
+    client::{MessageRejectionReason, ParticipantRemovalReason, UserSessionInformation, WebSocketChatroomEventClient, WebSocketChatroomMessageClient},
+    server::{SearchUsersResponse, WebSocketChatroomMessageServer},
+    BulkMessagesFromId, BulkMessagesFromLatest, BulkThreadReplies, ChatroomBan, ChatroomInvite, ChatroomMessageResponse, CreateChatroomInviteResponse, ChatroomPermission, ChatroomRole, FetchChatroomResponse,
+    FetchKnownChatroomResponse, FetchMessageEditsResponse, FetchMessagesResponse, ListChatroomBansResponse, ListChatroomInvitesResponse, MessageEdit, MessageFetchType, MessageReaction, UserLookup, UserSession,
     WebSocketChatroomMessages,
 };
 
-use crate::{ApplicationContext, AuthHttpClient, HttpClient, RequestQueueState, Route, SessionEncryptionKey};
+use crate::{authentication::auth::remove_user_session_from_disk, ApplicationContext, AuthHttpClient, HttpClient, RequestQueueState, Route, SessionEncryptionKey, SharedUserSession, COOKIE_SAVE_PATH};
+
+/// The amount of time members are muted for from the member actions.
+const MEMBER_MUTE_DURATION_SECS: u32 = 60 * 60;
+
+/// The amount of time the invites created from the invite list are valid for.
+const DEFAULT_INVITE_DURATION_SECS: u32 = 7 * 24 * 60 * 60;
+
+/// The amount of thread replies fetched at once.
+const THREAD_REPLIES_PAGE_SIZE: i32 = 20;
+
+/// The amount of characters shown of the messages which are replied to.
+const MESSAGE_PREVIEW_LENGTH: usize = 80;
+
+/// The emoji offered by the reaction picker.
+const REACTION_PICKER_EMOJI: [&str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🎉"];
 
 #[component]
 pub fn MainPage() -> Element {
@@ -39,7 +56,7 @@ pub fn MainPage() -> Element {
     let encryption_key = use_context::<SessionEncryptionKey>();
 
     let application_ctx = provide_root_context(ApplicationContext {
-        authed_http_client: AuthHttpClient::new(http_client, user_session.clone(), encryption_key),
+        authed_http_client: AuthHttpClient::new(http_client, use_context::<SharedUserSession>(), encryption_key),
         websocket_client_out: websocket_sender,
         websocket_client_in: remote_receiver,
     });
@@ -50,17 +67,37 @@ pub fn MainPage() -> Element {
     let client = application_ctx.authed_http_client;
     let client_clone = client.clone();
     let client_clone_add_chatroom = client.clone();
+    let client_invite_redeemer = client.clone();
+    let client_message_sender = client.clone();
+    let client_thread_message_sender = client.clone();
+    let client_message_editor = client.clone();
+    let client_message_deleter = client.clone();
 
     let navigator = navigator();
 
     let mut available_chatrooms: Signal<Vec<FetchChatroomResponse>> = use_signal(Vec::new);
     let mut cached_chat_messages: Signal<HashMap<i32, VecDeque<WebSocketChatroomMessageClient>>> =
         use_signal(HashMap::new);
+    // The replies of the threads which have been opened, stored by the id of their root
+    let mut cached_thread_replies: Signal<HashMap<i32, VecDeque<WebSocketChatroomMessageClient>>> =
+        use_signal(HashMap::new);
+    let mut open_thread_root_id: Signal<Option<i32>> = use_signal(|| None);
 
     let mut chatroom_id_buffer = use_signal(String::new);
     let mut new_chatroom_name_buffer = use_signal(String::new);
     let mut chatroom_passw_buffer = use_signal(String::new);
+    let mut invite_code_buffer = use_signal(String::new);
     let mut chatroom_message_buffer = use_signal(String::new);
+    let mut thread_message_buffer = use_signal(String::new);
+    // The message which is currently being edited, along with its new content
+    let mut editing_message_id: Signal<Option<i32>> = use_signal(|| None);
+    let mut edited_message_buffer = use_signal(String::new);
+    // Deleting a message has to be confirmed with a second click
+    let mut pending_message_deletion: Signal<Option<i32>> = use_signal(|| None);
+    // The message the next sent message replies to
+    let mut replying_to_message: Signal<Option<WebSocketChatroomMessageClient>> = use_signal(|| None);
+    // The message which has been jumped to from one of its replies
+    let mut highlighted_message_id: Signal<Option<i32>> = use_signal(|| None);
     let mut selected_chatroom_node_idx = use_signal(|| 0);
 
     let mut chatroom_last_messages_cache: Signal<HashMap<i32, ChatroomMessageResponse>> =
@@ -69,8 +106,32 @@ pub fn MainPage() -> Element {
     let users_cache: Signal<HashMap<i32, UserLookup>> = use_signal(HashMap::new);
     let mut users_cache_writer = users_cache;
 
+    // The avatars of the users stored as data urls, so that they can be displayed directly
+    let avatars_cache: Signal<HashMap<i32, String>> = use_signal(HashMap::new);
+    let mut avatars_cache_writer = avatars_cache;
+
+    // The icons of the chatrooms stored as data urls, `None` is stored while the icon is being fetched
+    let chatroom_icons_cache: Signal<HashMap<i32, Option<String>>> = use_signal(HashMap::new);
+    let mut chatroom_icons_cache_writer = chatroom_icons_cache;
+
+    let is_email_verified = user_information.email_verified;
+    let mut email_verified = use_signal(move || is_email_verified);
+    let mut verification_code_buffer = use_signal(String::new);
+    let client_verify_email = client.clone();
+    let client_resend_verification = client.clone();
+
+    let mut user_search_buffer = use_signal(String::new);
+    let mut user_search_results: Signal<Vec<UserLookup>> = use_signal(Vec::new);
+    let mut user_search_page = use_signal(|| 0);
+    let mut user_search_has_more = use_signal(|| false);
+    let client_user_search = client.clone();
+    let client_user_search_more = client.clone();
+
     let chatrooms_joined = user_information.chatrooms_joined;
     let client_chatroom_requester = client.clone();
+    let client_incoming_chatroom_requester = client.clone();
+    let client_direct_message_starter = client.clone();
+    let client_chatroom_leaver = client.clone();
 
     let currently_selected_chatroom_node: Memo<Option<FetchChatroomResponse>> =
         use_memo(move || {
@@ -82,6 +143,7 @@ pub fn MainPage() -> Element {
 
     let chatroom_message_sender = application_ctx.websocket_client_out;
     let websocket_receiver = application_ctx.websocket_client_in;
+    let own_user_id = user_session.user_id;
 
     use_hook(|| {
         spawn(async move {
@@ -91,10 +153,220 @@ pub fn MainPage() -> Element {
                 select! {
                     recv = websocket.recv() => {
                         if let Some(received_bytes) = recv {
-                            let ws_msg = rmp_serde::from_slice::<WebSocketChatroomMessageClient>(&received_bytes.into_data()).unwrap();
+                            let ws_event = rmp_serde::from_slice::<WebSocketChatroomEventClient>(&received_bytes.into_data()).unwrap();
+
+                            match ws_event {
+                                WebSocketChatroomEventClient::Message(ws_msg) => {
+                                    let chatroom_uid = ws_msg.sent_to;
+
+                                    let is_chatroom_known = match cached_chat_messages.write().get_mut(&chatroom_uid) {
+                                        Some(chatroom) => {
+                                            match ws_msg.thread_root_id {
+                                                // Thread replies are only shown in their thread, which is only kept up to date once it has been opened
+                                                Some(thread_root_id) => {
+                                                    if let Some(thread_replies) = cached_thread_replies.write().get_mut(&thread_root_id) {
+                                                        thread_replies.push_back(ws_msg);
+                                                    }
+                                                },
+                                                None => chatroom.push_back(ws_msg),
+                                            }
+
+                                            true
+                                        },
+                                        None => false,
+                                    };
+
+                                    // Someone has started a direct message with us while we were online, so we have to fetch the chatroom first
+                                    if !is_chatroom_known {
+                                        let client = client_incoming_chatroom_requester.clone();
+
+                                        spawn(async move {
+                                            let response = match client.fetch_known_chatrooms(vec![chatroom_uid]).await {
+                                                Ok(response) => response,
+                                                Err(err) => {
+                                                    tracing::error!("Error occured when fetching chatroom {chatroom_uid}: {}", err.to_string());
+
+                                                    return;
+                                                },
+                                            };
+
+                                            let verified_chatrooms = serde_json::from_str::<FetchKnownChatroomResponse>(&response.text().await.unwrap()).unwrap();
+
+                                            for chatroom in verified_chatrooms.chatrooms {
+                                                // Multiple messages might have arrived before the chatroom has been fetched
+                                                if cached_chat_messages.read().contains_key(&chatroom.chatroom_uid) {
+                                                    continue;
+                                                }
+
+                                                cached_chat_messages.write().insert(chatroom.chatroom_uid, VecDeque::new());
+                                                available_chatrooms.write().push(chatroom);
+                                            }
+                                        });
+                                    }
+                                },
+                                WebSocketChatroomEventClient::MessageEdited { chatroom_uid, message_id, message, edited_at } => {
+                                    if let Some(last_message) = chatroom_last_messages_cache.write().get_mut(&message_id) {
+                                        last_message.raw_message = rmp_serde::to_vec(&message).unwrap();
+                                        last_message.edited_at = Some(edited_at);
+                                    }
+
+                                    for thread_replies in cached_thread_replies.write().values_mut() {
+                                        if let Some(thread_reply) = thread_replies.iter_mut().find(|thread_reply| thread_reply.message_id == message_id) {
+                                            thread_reply.message = message.clone();
+                                            thread_reply.edited_at = Some(edited_at);
+                                        }
+                                    }
+
+                                    if let Some(chatroom_msgs) = cached_chat_messages.write().get_mut(&chatroom_uid) {
+                                        if let Some(chatroom_msg) = chatroom_msgs.iter_mut().find(|chatroom_msg| chatroom_msg.message_id == message_id) {
+                                            chatroom_msg.message = message;
+                                            chatroom_msg.edited_at = Some(edited_at);
+                                        }
+                                    }
+                                },
+                                WebSocketChatroomEventClient::MessageDeleted { chatroom_uid, message_id, removed_by_moderator, last_message_id } => {
+                                    let tombstone = WebSocketChatroomMessages::Deleted { removed_by_moderator };
+
+                                    if let Some(last_message) = chatroom_last_messages_cache.write().get_mut(&message_id) {
+                                        last_message.raw_message = rmp_serde::to_vec(&tombstone).unwrap();
+                                        last_message.edited_at = None;
+                                    }
+
+                                    for thread_replies in cached_thread_replies.write().values_mut() {
+                                        if let Some(thread_reply) = thread_replies.iter_mut().find(|thread_reply| thread_reply.message_id == message_id) {
+                                            thread_reply.message = tombstone.clone();
+                                            thread_reply.edited_at = None;
+                                            thread_reply.reactions.clear();
+                                        }
+                                    }
+
+                                    if let Some(chatroom_msgs) = cached_chat_messages.write().get_mut(&chatroom_uid) {
+                                        if let Some(chatroom_msg) = chatroom_msgs.iter_mut().find(|chatroom_msg| chatroom_msg.message_id == message_id) {
+                                            chatroom_msg.message = tombstone;
+                                            chatroom_msg.edited_at = None;
+                                            chatroom_msg.reactions.clear();
+                                        }
+                                    }
+
+                                    if let Some(chatroom) = available_chatrooms.write().iter_mut().find(|chatroom| chatroom.chatroom_uid == chatroom_uid) {
+                                        chatroom.last_message_id = last_message_id;
+                                    }
+
+                                    if *editing_message_id.read() == Some(message_id) {
+                                        editing_message_id.set(None);
+                                    }
+
+                                    if replying_to_message.read().as_ref().is_some_and(|replied_message| replied_message.message_id == message_id) {
+                                        replying_to_message.set(None);
+                                    }
+                                },
+                                WebSocketChatroomEventClient::ThreadUpdated { chatroom_uid, thread_root_id, reply_count, last_reply_at } => {
+                                    if let Some(chatroom_msgs) = cached_chat_messages.write().get_mut(&chatroom_uid) {
+                                        if let Some(thread_root) = chatroom_msgs.iter_mut().find(|chatroom_msg| chatroom_msg.message_id == thread_root_id) {
+                                            thread_root.thread_reply_count = reply_count;
+                                            thread_root.thread_last_reply_at = last_reply_at;
+                                        }
+                                    }
+                                },
+                                WebSocketChatroomEventClient::ReactionAdded { chatroom_uid, message_id, user_id, emoji } => {
+                                    if let Some(chatroom_msgs) = cached_chat_messages.write().get_mut(&chatroom_uid) {
+                                        if let Some(chatroom_msg) = chatroom_msgs.iter_mut().find(|chatroom_msg| chatroom_msg.message_id == message_id) {
+                                            match chatroom_msg.reactions.iter_mut().find(|reaction| reaction.emoji == emoji) {
+                                                Some(reaction) => {
+                                                    reaction.count += 1;
+                                                    reaction.reacted |= user_id == own_user_id;
+                                                },
+                                                None => chatroom_msg.reactions.push(MessageReaction { emoji, count: 1, reacted: user_id == own_user_id }),
+                                            }
+                                        }
+                                    }
+                                },
+                                WebSocketChatroomEventClient::ReactionRemoved { chatroom_uid, message_id, user_id, emoji } => {
+                                    if let Some(chatroom_msgs) = cached_chat_messages.write().get_mut(&chatroom_uid) {
+                                        if let Some(chatroom_msg) = chatroom_msgs.iter_mut().find(|chatroom_msg| chatroom_msg.message_id == message_id) {
+                                            if let Some(reaction) = chatroom_msg.reactions.iter_mut().find(|reaction| reaction.emoji == emoji) {
+                                                reaction.count = reaction.count.saturating_sub(1);
+
+                                                if user_id == own_user_id {
+                                                    reaction.reacted = false;
+                                                }
+                                            }
+
+                                            chatroom_msg.reactions.retain(|reaction| reaction.count > 0);
+                                        }
+                                    }
+                                },
+                                WebSocketChatroomEventClient::ParticipantLeft { chatroom_uid, user_id } => {
+                                    // We have left the chatroom from another device
+                                    if user_id == own_user_id {
+                                        cached_chat_messages.write().remove(&chatroom_uid);
+                                        available_chatrooms.write().retain(|chatroom| chatroom.chatroom_uid != chatroom_uid);
+                                        selected_chatroom_node_idx.set(0);
+                                    }
+                                    else if let Some(chatroom) = available_chatrooms.write().iter_mut().find(|chatroom| chatroom.chatroom_uid == chatroom_uid) {
+                                        chatroom.participants.retain(|participant| participant.user_id != user_id);
+                                    }
+                                },
+                                WebSocketChatroomEventClient::ParticipantRemoved { chatroom_uid, user_id, reason } => {
+                                    if user_id == own_user_id {
+                                        cached_chat_messages.write().remove(&chatroom_uid);
+                                        available_chatrooms.write().retain(|chatroom| chatroom.chatroom_uid != chatroom_uid);
+                                        selected_chatroom_node_idx.set(0);
+
+                                        toast.write().popup(ToastInfo::simple(&match reason {
+                                            ParticipantRemovalReason::Kicked => "You have been kicked from a chatroom.".to_string(),
+                                            ParticipantRemovalReason::Banned { expires_at: Some(expires_at) } => format!("You have been banned from a chatroom until {}.", expires_at.format("%Y-%m-%d %H:%M UTC")),
+                                            ParticipantRemovalReason::Banned { expires_at: None } => "You have been permanently banned from a chatroom.".to_string(),
+                                        }));
+                                    }
+                                    else if let Some(chatroom) = available_chatrooms.write().iter_mut().find(|chatroom| chatroom.chatroom_uid == chatroom_uid) {
+                                        chatroom.participants.retain(|participant| participant.user_id != user_id);
+                                    }
+                                },
+                                WebSocketChatroomEventClient::MemberMuted { chatroom_uid, user_id, muted_until } => {
+                                    if let Some(chatroom) = available_chatrooms.write().iter_mut().find(|chatroom| chatroom.chatroom_uid == chatroom_uid) {
+                                        if let Some(participant) = chatroom.participants.iter_mut().find(|participant| participant.user_id == user_id) {
+                                            participant.muted_until = muted_until;
+                                        }
+                                    }
+                                },
+                                WebSocketChatroomEventClient::MemberRoleChanged { chatroom_uid, user_id, role } => {
+                                    if let Some(chatroom) = available_chatrooms.write().iter_mut().find(|chatroom| chatroom.chatroom_uid == chatroom_uid) {
+                                        if let Some(participant) = chatroom.participants.iter_mut().find(|participant| participant.user_id == user_id) {
+                                            participant.role = role;
+                                        }
+                                    }
+                                },
+                                WebSocketChatroomEventClient::ChatroomSettingsChanged { chatroom_uid, chatroom_name, description, has_icon, has_password } => {
+                                    if let Some(chatroom) = available_chatrooms.write().iter_mut().find(|chatroom| chatroom.chatroom_uid == chatroom_uid) {
+                                        chatroom.chatroom_name = chatroom_name;
+                                        chatroom.description = description;
+                                        chatroom.has_icon = has_icon;
+                                        chatroom.has_password = has_password;
+                                    }
 
-                            if let Some(chatroom) = cached_chat_messages.write().get_mut(&ws_msg.sent_to) {
-                                chatroom.push_back(ws_msg);
+                                    // The icon might have been replaced, so it is fetched again when it is displayed next time
+                                    chatroom_icons_cache_writer.write().remove(&chatroom_uid);
+                                },
+                                WebSocketChatroomEventClient::ChatroomDeleted { chatroom_uid } => {
+                                    // This is also how the owner's own client learns about the deletion
+                                    chatroom_icons_cache_writer.write().remove(&chatroom_uid);
+                                    cached_chat_messages.write().remove(&chatroom_uid);
+                                    available_chatrooms.write().retain(|chatroom| chatroom.chatroom_uid != chatroom_uid);
+                                    selected_chatroom_node_idx.set(0);
+
+                                    toast.write().popup(ToastInfo::simple("A chatroom has been deleted."));
+                                },
+                                WebSocketChatroomEventClient::MessageRejected { chatroom_uid, reason } => {
+                                    tracing::error!("Message sent to chatroom {chatroom_uid} has been rejected: {reason:?}");
+
+                                    toast.write().popup(ToastInfo::simple(&match reason {
+                                        MessageRejectionReason::MissingPermission => "You are not allowed to send messages to this chatroom.".to_string(),
+                                        MessageRejectionReason::Muted { until } => format!("You are muted in this chatroom until {}.", until.format("%Y-%m-%d %H:%M UTC")),
+                                        MessageRejectionReason::InvalidReply => "The message you replied to has been deleted.".to_string(),
+                                        MessageRejectionReason::InvalidThread => "The thread you replied to has been deleted.".to_string(),
+                                    }));
+                                },
                             }
                         }
                     }
@@ -113,9 +385,7 @@ pub fn MainPage() -> Element {
                 return;
             }
 
-            let chatroom_ids: Vec<i32> = chatrooms_joined.iter().map(|id| id.unwrap()).collect();
-
-            let response = client.fetch_known_chatrooms(chatroom_ids).await.unwrap();
+            let response = client.fetch_known_chatrooms(chatrooms_joined).await.unwrap();
 
             let verified_chatrooms =
                 serde_json::from_str::<FetchKnownChatroomResponse>(&response.text().await.unwrap())
@@ -146,6 +416,20 @@ pub fn MainPage() -> Element {
 
                             let lookup = serde_json::from_str::<UserLookup>(&lookup_response.text().await.unwrap()).unwrap();
 
+                            if lookup.has_avatar {
+                                match user_requester_client.fetch_avatar(message_owner_id).await {
+                                    Ok(avatar_response) => {
+                                        let mime_type = avatar_response.headers().get(CONTENT_TYPE).and_then(|mime_type| mime_type.to_str().ok()).unwrap_or("image/png").to_string();
+                                        let avatar_image = avatar_response.bytes().await.unwrap();
+
+                                        avatars_cache_writer.write().insert(message_owner_id, format!("data:{mime_type};base64,{}", BASE64_STANDARD.encode(avatar_image)));
+                                    },
+                                    Err(err) => {
+                                        tracing::error!("Error occured when fetching the avatar of {message_owner_id}: {}", err.to_string());
+                                    },
+                                }
+                            }
+
                             users_cache_writer.write().insert(message_owner_id, lookup);
                         }
                         else => {
@@ -157,6 +441,38 @@ pub fn MainPage() -> Element {
         },
     ));
 
+    let chatroom_icon_requester_client = client.clone();
+
+    // Create a chatroom icon requesting coroutine
+    // It receives the chatroom's id and stores the fetched icon in `chatroom_icons_cache_writer`
+    let chatroom_icon_requester_sender = Arc::new(use_coroutine(
+        move |mut receiver: UnboundedReceiver<i32>| {
+            let chatroom_icon_requester_client = chatroom_icon_requester_client.clone();
+            async move {
+                while let Some(chatroom_uid) = receiver.next().await {
+                    // The icon might have been requested multiple times before it was fetched
+                    if chatroom_icons_cache_writer.read().contains_key(&chatroom_uid) {
+                        continue;
+                    }
+
+                    chatroom_icons_cache_writer.write().insert(chatroom_uid, None);
+
+                    match chatroom_icon_requester_client.fetch_chatroom_icon(chatroom_uid).await {
+                        Ok(icon_response) => {
+                            let mime_type = icon_response.headers().get(CONTENT_TYPE).and_then(|mime_type| mime_type.to_str().ok()).unwrap_or("image/png").to_string();
+                            let icon_image = icon_response.bytes().await.unwrap();
+
+                            chatroom_icons_cache_writer.write().insert(chatroom_uid, Some(format!("data:{mime_type};base64,{}", BASE64_STANDARD.encode(icon_image))));
+                        },
+                        Err(err) => {
+                            tracing::error!("Error occured when fetching the icon of chatroom {chatroom_uid}: {}", err.to_string());
+                        },
+                    }
+                }
+            }
+        },
+    ));
+
     let user_requester_client = client.clone();
 
     // Create a last message requesting coroutine
@@ -232,6 +548,19 @@ pub fn MainPage() -> Element {
                                                 msg_list.push_front(incoming_msg.into());
                                             }
                                         }
+                                        MessageFetchType::ThreadReplies(thread_replies_request) => {
+                                            let mut cached_threads = cached_thread_replies.write();
+                                            let thread_replies = cached_threads
+                                                .entry(thread_replies_request.thread_root_id)
+                                                .or_default();
+
+                                            // The replies sent since the thread was opened have already been received over the WebSocket connection
+                                            for incoming_msg in messages_fetched.messages {
+                                                if !thread_replies.iter().any(|thread_reply| thread_reply.message_id == incoming_msg.message_id) {
+                                                    thread_replies.push_front(incoming_msg.into());
+                                                }
+                                            }
+                                        }
                                     };
 
                                     *value.lock() = RequestQueueState::Completed;
@@ -265,6 +594,9 @@ pub fn MainPage() -> Element {
 
     let mut stick_to_bottom = use_signal(|| false);
 
+    let thread_message_sender = chatroom_message_sender.clone();
+    let thread_replies_requester_sender = chatroom_message_requester_sender.clone();
+
     rsx! {
         div {
             class: "window",
@@ -301,7 +633,14 @@ pub fn MainPage() -> Element {
 
                                     div {
                                         id: "chat_icon",
-                                        img {}
+                                        img {
+                                            // Direct messages are displayed with the avatar of the other participant
+                                            src: match chatroom_node.direct_message_partner(user_information.user_id) {
+                                                Some(partner_id) => avatars_cache.read().get(&partner_id).cloned(),
+                                                None if chatroom_node.has_icon => get_or_request_chatroom_icon(chatroom_icons_cache, chatroom_icon_requester_sender.clone(), chatroom_node.chatroom_uid),
+                                                None => None,
+                                            },
+                                        }
                                     }
 
                                     div {
@@ -309,7 +648,10 @@ pub fn MainPage() -> Element {
 
                                         div {
                                             {
-                                                chatroom_node.chatroom_name.clone()
+                                                match get_chatroom_display_name(chatroom_node, user_information.user_id, users_cache, user_requester_sender.clone()) {
+                                                    Some(chatroom_name) => rsx!({ chatroom_name }),
+                                                    None => display_loading_svg(),
+                                                }
                                             }
                                         }
                                     }
@@ -333,43 +675,45 @@ pub fn MainPage() -> Element {
 
                                                                         // We should only display the last message if we know who wrote it else we just display the loading svg
                                                                         if let Some(info) = user_info {
-                                                                            let username = info.username;
-
-                                                                            match message_type {
-                                                                                WebSocketChatroomMessages::StringMessage(message) => {
-                                                                                    rsx!(
-                                                                                        div {
-                                                                                            id: "chatroom_last_message",
-
-                                                                                            div {
-                                                                                                id: {
-                                                                                                    if username.clone() == user_information.username.clone() {
-                                                                                                        "chatroom_last_message_name_owned"
-                                                                                                    }
-                                                                                                    else {
-                                                                                                        "chatroom_last_message_name"
-                                                                                                    }
-                                                                                                },
-
-                                                                                                {
-                                                                                                    if username.clone() == user_information.username.clone() {
-                                                                                                        "Me"
-                                                                                                    }
-                                                                                                    else {
-                                                                                                        &username
-                                                                                                    }
-                                                                                                }
+                                                                            let is_own_message = info.user_id == user_information.user_id;
+                                                                            let display_name = info.display_name().to_string();
+
+                                                                            let message = match message_type {
+                                                                                WebSocketChatroomMessages::StringMessage(message) => message,
+                                                                                WebSocketChatroomMessages::Deleted { .. } => String::from("Message deleted"),
+                                                                            };
+
+                                                                            rsx!(
+                                                                                div {
+                                                                                    id: "chatroom_last_message",
+
+                                                                                    div {
+                                                                                        id: {
+                                                                                            if is_own_message {
+                                                                                                "chatroom_last_message_name_owned"
                                                                                             }
+                                                                                            else {
+                                                                                                "chatroom_last_message_name"
+                                                                                            }
+                                                                                        },
 
-                                                                                            div {
-                                                                                                id: "chatroom_last_message_body",
-
-                                                                                                { message }
+                                                                                        {
+                                                                                            if is_own_message {
+                                                                                                "Me"
+                                                                                            }
+                                                                                            else {
+                                                                                                &display_name
                                                                                             }
                                                                                         }
-                                                                                    )
-                                                                                },
-                                                                            }
+                                                                                    }
+
+                                                                                    div {
+                                                                                        id: "chatroom_last_message_body",
+
+                                                                                        { message }
+                                                                                    }
+                                                                                }
+                                                                            )
                                                                         }
                                                                         else {
                                                                             display_loading_svg()
@@ -414,6 +758,22 @@ pub fn MainPage() -> Element {
                             "Settings"
                         }
 
+                        button {
+                            id: "user_control_panel_button",
+                            onclick: move |_event| {
+                                navigator.push(Route::EditProfile {  });
+                            },
+                            "Edit profile"
+                        }
+
+                        button {
+                            id: "user_control_panel_button",
+                            onclick: move |_event| {
+                                navigator.push(Route::ChangePassword {  });
+                            },
+                            "Change password"
+                        }
+
                         button {
                             id: "user_control_panel_button",
                             onclick: move |_event| {
@@ -423,6 +783,9 @@ pub fn MainPage() -> Element {
                                     // Send the logout request
                                     client.request_logout().await.unwrap();
 
+                                    // Forget the stored session so that we wont be logged in automaticly on the next startup
+                                    remove_user_session_from_disk((*COOKIE_SAVE_PATH).clone()).unwrap();
+
                                     // Reset root ctx for the session
                                     let mut session_ctx = use_context::<Signal<Option<(UserSession, UserSessionInformation)>>>();
                                     session_ctx.set(None);
@@ -437,6 +800,67 @@ pub fn MainPage() -> Element {
                             "Logout"
                         }
 
+                        if !email_verified() {
+                            div {
+                                class: "dropdown",
+                                button {
+                                    id: "user_control_panel_button",
+                                    "Verify your email!"
+                                }
+                                div {
+                                    class: "dropdown_content",
+
+                                    div {
+                                        id: "chat_id_input_row",
+                                        button {
+                                            class: "button",
+                                            onclick: move |_| {
+                                                let client = client_verify_email.clone();
+
+                                                spawn(async move {
+                                                    match client.verify_email(verification_code_buffer.to_string()).await {
+                                                        Ok(_) => {
+                                                            email_verified.set(true);
+
+                                                            toast.write().popup(ToastInfo::simple("Your email address has been verified!"));
+                                                        },
+                                                        Err(err) => {
+                                                            toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                                        },
+                                                    }
+                                                });
+                                            },
+                                            "Verify"
+                                        }
+                                        input {
+                                            oninput: move |event| {
+                                                verification_code_buffer.set(event.value());
+                                            },
+                                            placeholder: "Verification code",
+                                        }
+                                        button {
+                                            class: "button",
+                                            onclick: move |_| {
+                                                let client = client_resend_verification.clone();
+
+                                                spawn(async move {
+                                                    match client.resend_verification_email().await {
+                                                        Ok(_) => {
+                                                            toast.write().popup(ToastInfo::simple("A new verification code has been sent to your email address."));
+                                                        },
+                                                        Err(err) => {
+                                                            toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                                        },
+                                                    }
+                                                });
+                                            },
+                                            "Resend code"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+
                         div {
                             class: "dropdown",
                             button {
@@ -491,6 +915,169 @@ pub fn MainPage() -> Element {
                                         placeholder: "Chat Password",
                                     }
                                 }
+
+                                div {
+                                    id: "chat_id_input_row",
+
+                                    button {
+                                        class: "button",
+                                        onclick: move |_| {
+                                            let client = client_invite_redeemer.clone();
+
+                                            spawn(async move {
+                                                match client.join_by_invite(invite_code_buffer.to_string()).await {
+                                                    Ok(response) => {
+                                                        let serialized_response = serde_json::from_str::<FetchChatroomResponse>(&response.text().await.unwrap()).unwrap();
+
+                                                        // Redeeming an invite of a chatroom we are already in does not add it again
+                                                        if !cached_chat_messages.read().contains_key(&serialized_response.chatroom_uid) {
+                                                            cached_chat_messages.write().insert(serialized_response.chatroom_uid, VecDeque::new());
+                                                            available_chatrooms.write().push(serialized_response);
+                                                        }
+
+                                                        invite_code_buffer.set(String::new());
+                                                    },
+                                                    Err(err) => {
+                                                        toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                                    },
+                                                }
+                                            });
+                                        },
+
+                                        "Join"
+                                    }
+
+                                    input {
+                                        value: "{invite_code_buffer}",
+                                        oninput: move |event| {
+                                            invite_code_buffer.set(event.value());
+                                        },
+                                        placeholder: "Invite code",
+                                    }
+                                }
+                            }
+                        }
+                        div {
+                            class: "dropdown",
+                            button {
+                                id: "user_control_panel_button",
+                                "Find people"
+                            }
+                            div {
+                                class: "dropdown_content",
+
+                                div {
+                                    id: "chat_id_input_row",
+                                    button {
+                                        class: "button",
+                                        onclick: move |_| {
+                                            let client = client_user_search.clone();
+
+                                            spawn(async move {
+                                                match client.search_users(user_search_buffer.to_string(), 0).await {
+                                                    Ok(response) => {
+                                                        let search_response = serde_json::from_str::<SearchUsersResponse>(&response.text().await.unwrap()).unwrap();
+
+                                                        user_search_page.set(0);
+                                                        user_search_has_more.set(search_response.has_more);
+                                                        user_search_results.set(search_response.users);
+                                                    },
+                                                    Err(err) => {
+                                                        toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                                    },
+                                                }
+                                            });
+                                        },
+                                        "Search"
+                                    }
+                                    input {
+                                        oninput: move |event| {
+                                            user_search_buffer.set(event.value());
+                                        },
+                                        placeholder: "Username or display name",
+                                    }
+                                }
+
+                                div {
+                                    id: "user_search_results",
+
+                                    for user_result in user_search_results.read().clone() {
+                                        button {
+                                            id: "user_search_result",
+                                            onclick: {
+                                                let client = client_direct_message_starter.clone();
+                                                let partner_id = user_result.user_id;
+
+                                                move |_| {
+                                                    let client = client.clone();
+
+                                                    spawn(async move {
+                                                        // Open the direct message with the user, the server creates it if we have not talked yet
+                                                        let response = match client.start_direct_message(partner_id).await {
+                                                            Ok(response) => response,
+                                                            Err(err) => {
+                                                                toast.write().popup(ToastInfo::simple(&err.to_string()));
+
+                                                                return;
+                                                            },
+                                                        };
+
+                                                        let direct_message = serde_json::from_str::<FetchChatroomResponse>(&response.text().await.unwrap()).unwrap();
+
+                                                        let existing_chatroom_idx = available_chatrooms.read().iter().position(|chatroom| chatroom.chatroom_uid == direct_message.chatroom_uid);
+
+                                                        let chatroom_idx = match existing_chatroom_idx {
+                                                            Some(chatroom_idx) => chatroom_idx,
+                                                            None => {
+                                                                cached_chat_messages.write().insert(direct_message.chatroom_uid, VecDeque::new());
+                                                                available_chatrooms.write().push(direct_message);
+
+                                                                available_chatrooms.read().len() - 1
+                                                            },
+                                                        };
+
+                                                        selected_chatroom_node_idx.set(chatroom_idx);
+                                                    });
+                                                }
+                                            },
+
+                                            div {
+                                                id: "profile_popover_display_name",
+                                                { user_result.display_name().to_string() }
+                                            }
+                                            div {
+                                                id: "profile_popover_username",
+                                                { format!("@{}", user_result.username) }
+                                            }
+                                        }
+                                    }
+
+                                    if user_search_has_more() {
+                                        button {
+                                            class: "button",
+                                            onclick: move |_| {
+                                                let client = client_user_search_more.clone();
+                                                let next_page = user_search_page() + 1;
+
+                                                spawn(async move {
+                                                    match client.search_users(user_search_buffer.to_string(), next_page).await {
+                                                        Ok(response) => {
+                                                            let search_response = serde_json::from_str::<SearchUsersResponse>(&response.text().await.unwrap()).unwrap();
+
+                                                            user_search_page.set(next_page);
+                                                            user_search_has_more.set(search_response.has_more);
+                                                            user_search_results.write().extend(search_response.users);
+                                                        },
+                                                        Err(err) => {
+                                                            toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                                        },
+                                                    }
+                                                });
+                                            },
+                                            "More results"
+                                        }
+                                    }
+                                }
                             }
                         }
                         div {
@@ -510,7 +1097,7 @@ pub fn MainPage() -> Element {
                                             let client = client_clone_add_chatroom.clone();
 
                                             spawn(async move {
-                                                let response = client.create_new_chatroom(
+                                                let response = match client.create_new_chatroom(
                                                     new_chatroom_name_buffer.to_string(),
                                                     {
                                                         let entered_passw = chatroom_passw_buffer.to_string();
@@ -522,7 +1109,14 @@ pub fn MainPage() -> Element {
                                                             Some(entered_passw)
                                                         }
                                                     }
-                                                ).await.unwrap();
+                                                ).await {
+                                                    Ok(response) => response,
+                                                    Err(err) => {
+                                                        toast.write().popup(ToastInfo::simple(&err.to_string()));
+
+                                                        return;
+                                                    },
+                                                };
 
                                                 let added_chatroom = serde_json::from_str::<FetchChatroomResponse>(&response.text().await.unwrap()).unwrap();
 
@@ -607,11 +1201,15 @@ pub fn MainPage() -> Element {
                                         for chatroom_msg in chatroom_msgs {
                                             div {
                                                 id: "message_node",
+                                                class: if *highlighted_message_id.read() == Some(chatroom_msg.message_id) { "highlighted_message" },
+                                                // Used to scroll to the message when jumping to it from a reply
+                                                "data-message-id": chatroom_msg.message_id,
                                                 // Display who sent the message
                                                 {
                                                     rsx!(
                                                         div {
                                                             id: "message_author",
+                                                            class: "dropdown",
 
                                                             title: {
                                                                 format!("User ID: {}", chatroom_msg.message_owner_id)
@@ -622,15 +1220,33 @@ pub fn MainPage() -> Element {
 
                                                                 match get_or_request_user_information(users_cache, user_requester_sender.clone(), message_owner_id) {
                                                                     Some(user_information) => {
+                                                                        let avatar = avatars_cache.read().get(&message_owner_id).cloned();
+
                                                                         rsx!(
                                                                             {
                                                                                 if message_owner_id == user_session.user_id {
                                                                                     String::from("Me")
                                                                                 }
                                                                                 else {
-                                                                                    user_information.username.clone()
+                                                                                    user_information.display_name().to_string()
                                                                                 }
                                                                             }
+
+                                                                            // Show the profile of the author when hovering over their name
+                                                                            {
+                                                                                display_profile_popover(
+                                                                                    &user_information,
+                                                                                    avatar,
+                                                                                    currently_selected_chatroom_node.role_of(message_owner_id),
+                                                                                    rsx!(
+                                                                                        ChatroomMemberActions {
+                                                                                            chatroom: currently_selected_chatroom_node.clone(),
+                                                                                            member_id: message_owner_id,
+                                                                                            own_user_id: user_session.user_id,
+                                                                                        }
+                                                                                    ),
+                                                                                )
+                                                                            }
                                                                         )
                                                                     },
                                                                     None => {
@@ -643,35 +1259,290 @@ pub fn MainPage() -> Element {
                                                     )
                                                 }
 
-                                                // Display the message itself
-                                                {
-                                                    rsx!(
-                                                        div {
-                                                            id: "message_content",
-                                                            match &chatroom_msg.message {
-                                                                WebSocketChatroomMessages::StringMessage(message) => {
-                                                                    rsx!(
-                                                                        div {
-                                                                            id: "string_message",
+                                                // Quote the message this message is replying to
+                                                if let Some(replied_message_id) = chatroom_msg.replying_to_msg_id {
+                                                    {
+                                                        // The replied message is only requested from the server if it has not been loaded with the chatroom's messages
+                                                        let replied_message = match chatroom_msgs.iter().find(|loaded_msg| loaded_msg.message_id == replied_message_id) {
+                                                            Some(loaded_msg) => Some((loaded_msg.message_owner_id, loaded_msg.message.clone())),
+                                                            None => get_or_request_message_from_id(chatroom_last_messages_cache, chatroom_message_requester_sender.clone(), replied_message_id)
+                                                                .map(|fetched_msg| (fetched_msg.message_owner_id, rmp_serde::from_slice::<WebSocketChatroomMessages>(&fetched_msg.raw_message).unwrap())),
+                                                        };
+                                                        let chatroom_uid = currently_selected_chatroom_node.chatroom_uid;
+
+                                                        rsx!(
+                                                            div {
+                                                                class: "reply_preview",
+                                                                title: "Jump to the original message",
+                                                                onclick: move |_| {
+                                                                    let is_loaded = cached_chat_messages.read().get(&chatroom_uid).is_some_and(|chatroom_msgs| chatroom_msgs.iter().any(|loaded_msg| loaded_msg.message_id == replied_message_id));
+
+                                                                    if !is_loaded {
+                                                                        toast.write().popup(ToastInfo::simple("The original message is older than the loaded messages, scroll up to load it."));
+
+                                                                        return;
+                                                                    }
 
-                                                                            { message.to_string() }
-                                                                        }
-                                                                    )
+                                                                    // Stop sticking to the bottom, so that the original message stays in view
+                                                                    stick_to_bottom.set(false);
+                                                                    highlighted_message_id.set(Some(replied_message_id));
+
+                                                                    document::eval(&format!("document.querySelector('[data-message-id=\"{replied_message_id}\"]')?.scrollIntoView({{ behavior: 'smooth', block: 'center' }})"));
+                                                                },
+
+                                                                match replied_message {
+                                                                    Some((replied_owner_id, replied_message)) => {
+                                                                        let author_name = get_or_request_user_information(users_cache, user_requester_sender.clone(), replied_owner_id)
+                                                                            .map(|user_information| user_information.display_name().to_string())
+                                                                            .unwrap_or_default();
+
+                                                                        rsx!(
+                                                                            span {
+                                                                                class: "reply_preview_author",
+                                                                                "{author_name}"
+                                                                            }
+                                                                            span {
+                                                                                class: "reply_preview_message",
+                                                                                { message_preview_text(&replied_message) }
+                                                                            }
+                                                                        )
+                                                                    },
+                                                                    None => display_loading_svg(),
                                                                 }
                                                             }
-                                                        }
-                                                    )
+                                                        )
+                                                    }
                                                 }
 
-                                                // Display the date it was sent
+                                                // Display the message itself
                                                 {
                                                     rsx!(
                                                         div {
-                                                            id: "message_date",
+                                                            id: "message_content",
+                                                            if *editing_message_id.read() == Some(chatroom_msg.message_id) {
+                                                                div {
+                                                                    id: "message_editor",
+
+                                                                    input {
+                                                                        id: "edit_message_input",
+                                                                        value: "{edited_message_buffer}",
+                                                                        oninput: move |event| {
+                                                                            edited_message_buffer.set(event.value());
+                                                                        },
+                                                                    }
+                                                                    button {
+                                                                        class: "button",
+                                                                        onclick: {
+                                                                            let client = client_message_editor.clone();
+                                                                            let message_id = chatroom_msg.message_id;
+
+                                                                            move |_| {
+                                                                                let client = client.clone();
+                                                                                let message = edited_message_buffer.read().clone();
+
+                                                                                spawn(async move {
+                                                                                    // The edited message is received over the WebSocket connection
+                                                                                    match client.edit_message(message_id, WebSocketChatroomMessages::StringMessage(message)).await {
+                                                                                        Ok(_) => {
+                                                                                            editing_message_id.set(None);
+                                                                                        },
+                                                                                        Err(err) => {
+                                                                                            tracing::error!("Error occured when editing message {message_id}: {}", err.to_string());
+
+                                                                                            toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                                                                        },
+                                                                                    }
+                                                                                });
+                                                                            }
+                                                                        },
 
-                                                            { chatroom_msg.date_issued.to_string() }
-                                                        }
-                                                    )
+                                                                        "Save"
+                                                                    }
+                                                                    button {
+                                                                        class: "button",
+                                                                        onclick: move |_| {
+                                                                            editing_message_id.set(None);
+                                                                        },
+
+                                                                        "Cancel"
+                                                                    }
+                                                                }
+                                                            }
+                                                            else {
+                                                                match &chatroom_msg.message {
+                                                                    WebSocketChatroomMessages::StringMessage(message) => {
+                                                                        rsx!(
+                                                                            div {
+                                                                                id: "string_message",
+
+                                                                                { message.to_string() }
+                                                                            }
+                                                                        )
+                                                                    }
+                                                                    WebSocketChatroomMessages::Deleted { removed_by_moderator } => {
+                                                                        rsx!(
+                                                                            div {
+                                                                                class: "deleted_message",
+
+                                                                                if *removed_by_moderator {
+                                                                                    "This message has been removed by a moderator."
+                                                                                }
+                                                                                else {
+                                                                                    "This message has been deleted."
+                                                                                }
+                                                                            }
+                                                                        )
+                                                                    }
+                                                                }
+                                                            }
+                                                        }
+                                                    )
+                                                }
+
+                                                // Display the date it was sent
+                                                {
+                                                    rsx!(
+                                                        div {
+                                                            id: "message_date",
+
+                                                            { chatroom_msg.date_issued.to_string() }
+                                                        }
+                                                    )
+                                                }
+
+                                                // Summarize the thread started from the message
+                                                if chatroom_msg.thread_reply_count > 0 {
+                                                    div {
+                                                        class: "thread_summary",
+                                                        onclick: {
+                                                            let chatroom_message_requester_sender = chatroom_message_requester_sender.clone();
+                                                            let message_id = chatroom_msg.message_id;
+
+                                                            move |_| {
+                                                                open_thread(open_thread_root_id, cached_thread_replies, chatroom_message_requester_sender.clone(), message_id);
+                                                            }
+                                                        },
+
+                                                        {
+                                                            let replies_text = if chatroom_msg.thread_reply_count == 1 { "1 reply".to_string() } else { format!("{} replies", chatroom_msg.thread_reply_count) };
+
+                                                            match chatroom_msg.thread_last_reply_at {
+                                                                Some(last_reply_at) => format!("{replies_text}, last reply at {}", last_reply_at.format("%Y-%m-%d %H:%M UTC")),
+                                                                None => replies_text,
+                                                            }
+                                                        }
+                                                    }
+                                                }
+
+                                                // Deleted messages cannot be reacted to
+                                                if let WebSocketChatroomMessages::StringMessage(_) = &chatroom_msg.message {
+                                                    MessageReactions {
+                                                        message_id: chatroom_msg.message_id,
+                                                        reactions: chatroom_msg.reactions.clone(),
+                                                    }
+                                                }
+
+                                                div {
+                                                    class: "message_actions",
+
+                                                    if let Some(edited_at) = chatroom_msg.edited_at {
+                                                        MessageEditHistory {
+                                                            message_id: chatroom_msg.message_id,
+                                                            edited_at,
+                                                        }
+                                                    }
+
+                                                    // Only the author can edit their messages, deleted messages cannot be edited
+                                                    if let WebSocketChatroomMessages::StringMessage(current_message) = &chatroom_msg.message {
+                                                        button {
+                                                            class: "button",
+                                                            id: "reply_message_button",
+                                                            onclick: {
+                                                                let chatroom_msg = chatroom_msg.clone();
+
+                                                                move |_| {
+                                                                    replying_to_message.set(Some(chatroom_msg.clone()));
+                                                                }
+                                                            },
+
+                                                            "Reply"
+                                                        }
+
+                                                        // Thread replies cannot start threads themselves
+                                                        if chatroom_msg.thread_root_id.is_none() {
+                                                            button {
+                                                                class: "button",
+                                                                id: "open_thread_button",
+                                                                onclick: {
+                                                                    let chatroom_message_requester_sender = chatroom_message_requester_sender.clone();
+                                                                    let message_id = chatroom_msg.message_id;
+
+                                                                    move |_| {
+                                                                        open_thread(open_thread_root_id, cached_thread_replies, chatroom_message_requester_sender.clone(), message_id);
+                                                                    }
+                                                                },
+
+                                                                "Reply in thread"
+                                                            }
+                                                        }
+
+                                                        if chatroom_msg.message_owner_id == user_session.user_id && editing_message_id.read().is_none() {
+                                                            button {
+                                                                class: "button",
+                                                                id: "edit_message_button",
+                                                                onclick: {
+                                                                    let message_id = chatroom_msg.message_id;
+                                                                    let current_message = current_message.clone();
+
+                                                                    move |_| {
+                                                                        edited_message_buffer.set(current_message.clone());
+                                                                        editing_message_id.set(Some(message_id));
+                                                                    }
+                                                                },
+
+                                                                "Edit"
+                                                            }
+                                                        }
+
+                                                        if currently_selected_chatroom_node.can_delete_message(chatroom_msg.message_owner_id, user_session.user_id) {
+                                                            button {
+                                                                class: "button",
+                                                                id: "delete_message_button",
+                                                                onclick: {
+                                                                    let client = client_message_deleter.clone();
+                                                                    let message_id = chatroom_msg.message_id;
+
+                                                                    move |_| {
+                                                                        if *pending_message_deletion.read() != Some(message_id) {
+                                                                            pending_message_deletion.set(Some(message_id));
+
+                                                                            return;
+                                                                        }
+
+                                                                        pending_message_deletion.set(None);
+
+                                                                        let client = client.clone();
+
+                                                                        spawn(async move {
+                                                                            // The tombstone is received over the WebSocket connection
+                                                                            if let Err(err) = client.delete_message(message_id).await {
+                                                                                tracing::error!("Error occured when deleting message {message_id}: {}", err.to_string());
+
+                                                                                toast.write().popup(ToastInfo::simple(&format!("Failed to delete the message: {err}")));
+                                                                            }
+                                                                        });
+                                                                    }
+                                                                },
+
+                                                                if *pending_message_deletion.read() == Some(chatroom_msg.message_id) {
+                                                                    "Click again to delete"
+                                                                }
+                                                                else {
+                                                                    "Delete"
+                                                                }
+                                                            }
+                                                        }
+                                                    }
                                                 }
                                             }
                                         }
@@ -689,7 +1560,35 @@ pub fn MainPage() -> Element {
 
                     {
                         if let Some(chatroom_info) = currently_selected_chatroom_node.read().clone() {
+                            let chatroom_uid = chatroom_info.chatroom_uid;
+
+                            // Only show the reply if it belongs to this chatroom
+                            let replied_message = replying_to_message.read().clone().filter(|replied_message| replied_message.sent_to == chatroom_uid);
+
                             rsx! {
+                                if let Some(replied_message) = replied_message {
+                                    div {
+                                        id: "reply_banner",
+
+                                        span {
+                                            {
+                                                let author_name = get_or_request_user_information(users_cache, user_requester_sender.clone(), replied_message.message_owner_id)
+                                                    .map(|user_information| user_information.display_name().to_string())
+                                                    .unwrap_or_default();
+
+                                                format!("Replying to {author_name}: {}", message_preview_text(&replied_message.message))
+                                            }
+                                        }
+                                        button {
+                                            class: "button",
+                                            onclick: move |_| {
+                                                replying_to_message.set(None);
+                                            },
+
+                                            "Cancel"
+                                        }
+                                    }
+                                }
                                 div {
                                     id: "chat_input_row",
                                     input {
@@ -698,27 +1597,73 @@ pub fn MainPage() -> Element {
                                             chatroom_message_buffer.set(event.value());
                                         },
                                         placeholder: {
-                                            format!("Message: {}", chatroom_info.chatroom_name)
+                                            format!("Message: {}", get_chatroom_display_name(&chatroom_info, user_session.user_id, users_cache, user_requester_sender.clone()).unwrap_or_default())
                                         },
                                     }
                                     button {
                                         class: "button",
                                         id: "send_message_button",
                                         onclick: move |_| {
-                                            let user_session = (*user_session).clone();
+                                            let client = client_message_sender.clone();
                                             let chatroom_message_sender = chatroom_message_sender.clone();
                                             let message = chatroom_message_buffer.to_string();
 
                                             // Make it so that we cant send out empty messages
                                             if !message.trim().is_empty() {
+                                                let replying_to_msg_id = replying_to_message.read().as_ref().filter(|replied_message| replied_message.sent_to == chatroom_uid).map(|replied_message| replied_message.message_id);
+
+                                                if replying_to_msg_id.is_some() {
+                                                    replying_to_message.set(None);
+                                                }
+
                                                 spawn(async move {
-                                                    chatroom_message_sender.send(WebSocketChatroomMessageServer::new(user_session, None, chatroom_info.chatroom_uid, WebSocketChatroomMessages::StringMessage(message.to_string()),  chrono::Utc::now().naive_local())).await.unwrap();
+                                                    // Make sure the message is sent with a session token which has not expired yet
+                                                    let user_session = client.current_user_session().await.unwrap();
+
+                                                    chatroom_message_sender.send(WebSocketChatroomMessageServer::new(user_session, replying_to_msg_id, None, chatroom_info.chatroom_uid, WebSocketChatroomMessages::StringMessage(message.to_string()),  chrono::Utc::now().naive_local())).await.unwrap();
                                                 });
                                             }
                                         },
 
                                         "Send"
                                     }
+                                    button {
+                                        class: "button",
+                                        id: "leave_chatroom_button",
+                                        onclick: move |_| {
+                                            let client = client_chatroom_leaver.clone();
+
+                                            spawn(async move {
+                                                match client.leave_chatroom(chatroom_uid).await {
+                                                    Ok(_) => {
+                                                        cached_chat_messages.write().remove(&chatroom_uid);
+                                                        available_chatrooms.write().retain(|chatroom| chatroom.chatroom_uid != chatroom_uid);
+                                                        selected_chatroom_node_idx.set(0);
+                                                    },
+                                                    Err(err) => {
+                                                        tracing::error!("Error occured when leaving chatroom {chatroom_uid}: {}", err.to_string());
+
+                                                        toast.write().popup(ToastInfo::simple(&format!("Failed to leave the chatroom: {err}")));
+                                                    },
+                                                }
+                                            });
+                                        },
+
+                                        "Leave"
+                                    }
+                                    ChatroomInviteList {
+                                        chatroom: chatroom_info.clone(),
+                                        own_user_id: user_session.user_id,
+                                    }
+                                    ChatroomBanList {
+                                        chatroom: chatroom_info.clone(),
+                                        own_user_id: user_session.user_id,
+                                        user_info_cache: users_cache,
+                                    }
+                                    ChatroomSettingsPanel {
+                                        chatroom: chatroom_info.clone(),
+                                        own_user_id: user_session.user_id,
+                                    }
                                 }
                             }
                         }
@@ -729,6 +1674,124 @@ pub fn MainPage() -> Element {
 
                 }
             }
+
+            // Threadpanel
+            // Shows the replies of the opened thread next to the chat, replies can also be sent to the thread from here.
+            {
+                let opened_thread = open_thread_root_id.read().and_then(|thread_root_id| {
+                    let chatroom_uid = currently_selected_chatroom_node.read().as_ref()?.chatroom_uid;
+                    let thread_root = cached_chat_messages.read().get(&chatroom_uid)?.iter().find(|chatroom_msg| chatroom_msg.message_id == thread_root_id).cloned()?;
+                    let thread_replies = cached_thread_replies.read().get(&thread_root_id).cloned().unwrap_or_default();
+
+                    Some((thread_root, thread_replies))
+                });
+
+                match opened_thread {
+                    Some((thread_root, thread_replies)) => {
+                        let thread_root_id = thread_root.message_id;
+                        let chatroom_uid = thread_root.sent_to;
+                        // Deleted replies are not counted by the server
+                        let loaded_reply_count = thread_replies.iter().filter(|thread_reply| matches!(thread_reply.message, WebSocketChatroomMessages::StringMessage(_))).count();
+                        let oldest_reply_id = thread_replies.front().map(|thread_reply| thread_reply.message_id);
+
+                        rsx!(
+                            div {
+                                class: "sidepanel",
+                                id: "thread_panel",
+
+                                div {
+                                    id: "thread_panel_title",
+
+                                    "Thread"
+
+                                    button {
+                                        class: "button",
+                                        onclick: move |_| {
+                                            open_thread_root_id.set(None);
+                                        },
+
+                                        "Close"
+                                    }
+                                }
+
+                                div {
+                                    class: "thread_root",
+
+                                    {
+                                        display_thread_message(&thread_root, get_or_request_user_information(users_cache, user_requester_sender.clone(), thread_root.message_owner_id))
+                                    }
+                                }
+
+                                if let Some(oldest_reply_id) = oldest_reply_id.filter(|_| loaded_reply_count < thread_root.thread_reply_count as usize) {
+                                    button {
+                                        class: "button",
+                                        onclick: {
+                                            let thread_replies_requester_sender = thread_replies_requester_sender.clone();
+
+                                            move |_| {
+                                                thread_replies_requester_sender.send(MessageFetchType::ThreadReplies(BulkThreadReplies { thread_root_id, count: THREAD_REPLIES_PAGE_SIZE, offset_id: Some(oldest_reply_id) }));
+                                            }
+                                        },
+
+                                        "Load older replies"
+                                    }
+                                }
+
+                                div {
+                                    id: "thread_replies",
+
+                                    for thread_reply in thread_replies {
+                                        div {
+                                            class: "thread_reply",
+
+                                            {
+                                                display_thread_message(&thread_reply, get_or_request_user_information(users_cache, user_requester_sender.clone(), thread_reply.message_owner_id))
+                                            }
+                                        }
+                                    }
+                                }
+
+                                div {
+                                    id: "thread_input_row",
+
+                                    input {
+                                        id: "thread_input",
+                                        value: "{thread_message_buffer}",
+                                        oninput: move |event| {
+                                            thread_message_buffer.set(event.value());
+                                        },
+                                        placeholder: "Reply in thread",
+                                    }
+                                    button {
+                                        class: "button",
+                                        onclick: move |_| {
+                                            let client = client_thread_message_sender.clone();
+                                            let thread_message_sender = thread_message_sender.clone();
+                                            let message = thread_message_buffer.to_string();
+
+                                            if message.trim().is_empty() {
+                                                return;
+                                            }
+
+                                            thread_message_buffer.set(String::new());
+
+                                            spawn(async move {
+                                                // Make sure the message is sent with a session token which has not expired yet
+                                                let user_session = client.current_user_session().await.unwrap();
+
+                                                thread_message_sender.send(WebSocketChatroomMessageServer::new(user_session, None, Some(thread_root_id), chatroom_uid, WebSocketChatroomMessages::StringMessage(message), chrono::Utc::now().naive_local())).await.unwrap();
+                                            });
+                                        },
+
+                                        "Send"
+                                    }
+                                }
+                            }
+                        )
+                    },
+                    None => rsx!(),
+                }
+            }
         }
     }
 }
@@ -751,6 +1814,38 @@ pub fn get_or_request_user_information(
     }
 }
 
+pub fn get_or_request_chatroom_icon(
+    chatroom_icons_cache: Signal<HashMap<i32, Option<String>>>,
+    chatroom_icon_requester_sender: Arc<Coroutine<i32>>,
+    chatroom_uid: i32,
+) -> Option<String> {
+    match chatroom_icons_cache.read().get(&chatroom_uid) {
+        Some(chatroom_icon) => chatroom_icon.clone(),
+        None => {
+            chatroom_icon_requester_sender.send(chatroom_uid);
+
+            None
+        }
+    }
+}
+
+/// Returns the name the chatroom should be displayed with, direct messages are displayed with the name of the other participant.
+/// This is `None` while the other participant's information is being fetched.
+pub fn get_chatroom_display_name(
+    chatroom: &FetchChatroomResponse,
+    user_id: i32,
+    user_info_cache: Signal<HashMap<i32, UserLookup>>,
+    user_requester_sender: Arc<Coroutine<i32>>,
+) -> Option<String> {
+    match chatroom.direct_message_partner(user_id) {
+        Some(partner_id) => {
+            get_or_request_user_information(user_info_cache, user_requester_sender, partner_id)
+                .map(|partner_information| partner_information.display_name().to_string())
+        }
+        None => Some(chatroom.chatroom_name.clone()),
+    }
+}
+
 pub fn get_or_request_message_from_id(
     last_message_cache: Signal<HashMap<i32, ChatroomMessageResponse>>,
     message_requester_sender: Arc<Coroutine<MessageFetchType>>,
@@ -767,6 +1862,942 @@ pub fn get_or_request_message_from_id(
     }
 }
 
+/// Opens the thread in the thread panel, its latest replies are fetched the first time it is opened.
+pub fn open_thread(
+    mut open_thread_root_id: Signal<Option<i32>>,
+    mut thread_replies_cache: Signal<HashMap<i32, VecDeque<WebSocketChatroomMessageClient>>>,
+    message_requester_sender: Arc<Coroutine<MessageFetchType>>,
+    thread_root_id: i32,
+) {
+    open_thread_root_id.set(Some(thread_root_id));
+
+    if thread_replies_cache.read().contains_key(&thread_root_id) {
+        return;
+    }
+
+    // The replies received over the WebSocket connection are collected from now on
+    thread_replies_cache.write().insert(thread_root_id, VecDeque::new());
+
+    message_requester_sender.send(MessageFetchType::ThreadReplies(BulkThreadReplies { thread_root_id, count: THREAD_REPLIES_PAGE_SIZE, offset_id: None }));
+}
+
+/// Displays a message of the thread panel along with its author.
+pub fn display_thread_message(thread_message: &WebSocketChatroomMessageClient, author_information: Option<UserLookup>) -> Element {
+    rsx!(
+        div {
+            class: "thread_message_header",
+
+            match author_information {
+                Some(author_information) => rsx!(span { class: "thread_message_author", { author_information.display_name().to_string() } }),
+                None => display_loading_svg(),
+            }
+            span {
+                class: "thread_message_date",
+                { thread_message.date_issued.format("%Y-%m-%d %H:%M UTC").to_string() }
+            }
+        }
+        match &thread_message.message {
+            WebSocketChatroomMessages::StringMessage(message) => rsx!(div { "{message}" }),
+            WebSocketChatroomMessages::Deleted { removed_by_moderator: true } => rsx!(div { class: "deleted_message", "This message has been removed by a moderator." }),
+            WebSocketChatroomMessages::Deleted { removed_by_moderator: false } => rsx!(div { class: "deleted_message", "This message has been deleted." }),
+        }
+    )
+}
+
+/// Returns a shortened version of the message, used when quoting it.
+pub fn message_preview_text(message: &WebSocketChatroomMessages) -> String {
+    match message {
+        WebSocketChatroomMessages::StringMessage(message) => {
+            if message.chars().count() > MESSAGE_PREVIEW_LENGTH {
+                format!("{}...", message.chars().take(MESSAGE_PREVIEW_LENGTH).collect::<String>())
+            }
+            else {
+                message.clone()
+            }
+        },
+        WebSocketChatroomMessages::Deleted { .. } => String::from("Message deleted"),
+    }
+}
+
+pub fn display_profile_popover(user_information: &UserLookup, avatar: Option<String>, chatroom_role: Option<ChatroomRole>, member_actions: Element) -> Element {
+    rsx!(
+        div {
+            class: "dropdown_content",
+            id: "profile_popover",
+
+            div {
+                id: "profile_popover_header",
+
+                if let Some(avatar) = avatar {
+                    img {
+                        id: "profile_popover_avatar",
+                        src: avatar,
+                    }
+                }
+
+                div {
+                    div {
+                        id: "profile_popover_display_name",
+                        { user_information.display_name().to_string() }
+                    }
+                    div {
+                        id: "profile_popover_username",
+                        { format!("@{}", user_information.username) }
+                    }
+                }
+            }
+
+            if let Some(status) = &user_information.status {
+                div {
+                    id: "profile_popover_status",
+                    { status.clone() }
+                }
+            }
+
+            if let Some(bio) = &user_information.bio {
+                div {
+                    id: "profile_popover_bio",
+                    { bio.clone() }
+                }
+            }
+
+            if let Some(chatroom_role) = chatroom_role {
+                div {
+                    id: "profile_popover_role",
+                    { chatroom_role.to_string() }
+                }
+            }
+
+            { member_actions }
+        }
+    )
+}
+
+/// The actions the user can take on another member of the chatroom, based on their roles.
+#[component]
+fn ChatroomMemberActions(chatroom: FetchChatroomResponse, member_id: i32, own_user_id: i32) -> Element {
+    let client = use_context::<ApplicationContext>().authed_http_client;
+    let mut toast: Signal<ToastManager> = use_context();
+
+    let (Some(own_role), Some(member_role)) = (chatroom.role_of(own_user_id), chatroom.role_of(member_id)) else {
+        return rsx!();
+    };
+
+    // Only the members ranked below the user can be managed
+    if member_id == own_user_id || member_role >= own_role {
+        return rsx!();
+    }
+
+    let chatroom_uid = chatroom.chatroom_uid;
+
+    // Members cannot be ranked as high as the user, ownership can only be transferred
+    let assignable_roles: Vec<ChatroomRole> = [ChatroomRole::Admin, ChatroomRole::Member, ChatroomRole::ReadOnly]
+        .into_iter()
+        .filter(|role| *role < own_role && *role != member_role)
+        .collect();
+
+    let client_ownership_transferer = client.clone();
+    let client_member_kicker = client.clone();
+    let client_member_banner = client.clone();
+    let client_member_muter = client.clone();
+
+    let is_member_muted = chatroom
+        .participants
+        .iter()
+        .find(|participant| participant.user_id == member_id)
+        .and_then(|participant| participant.muted_until)
+        .is_some_and(|muted_until| muted_until > chrono::Utc::now().naive_utc());
+
+    rsx!(
+        div {
+            id: "member_actions",
+
+            if own_role.has_permission(ChatroomPermission::ManageRoles) {
+                for role in assignable_roles {
+                    button {
+                        class: "button",
+                        onclick: {
+                            let client = client.clone();
+
+                            move |_| {
+                                let client = client.clone();
+
+                                spawn(async move {
+                                    if let Err(err) = client.update_member_role(chatroom_uid, member_id, role).await {
+                                        tracing::error!("Error occured when updating the role of {member_id}: {}", err.to_string());
+
+                                        toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                    }
+                                });
+                            }
+                        },
+
+                        { format!("Make {}", role.to_string().to_lowercase()) }
+                    }
+                }
+            }
+
+            if own_role.has_permission(ChatroomPermission::TransferOwnership) {
+                button {
+                    class: "button",
+                    onclick: move |_| {
+                        let client = client_ownership_transferer.clone();
+
+                        spawn(async move {
+                            if let Err(err) = client.transfer_chatroom_ownership(chatroom_uid, member_id).await {
+                                tracing::error!("Error occured when transferring chatroom {chatroom_uid}: {}", err.to_string());
+
+                                toast.write().popup(ToastInfo::simple(&err.to_string()));
+                            }
+                        });
+                    },
+
+                    "Transfer ownership"
+                }
+            }
+
+            if own_role.has_permission(ChatroomPermission::ModerateMembers) {
+                button {
+                    class: "button",
+                    onclick: move |_| {
+                        let client = client_member_muter.clone();
+
+                        spawn(async move {
+                            // Members are muted for an hour, muting them again unmutes them
+                            let duration_secs = (!is_member_muted).then_some(MEMBER_MUTE_DURATION_SECS);
+
+                            if let Err(err) = client.mute_member(chatroom_uid, member_id, duration_secs).await {
+                                tracing::error!("Error occured when muting {member_id}: {}", err.to_string());
+
+                                toast.write().popup(ToastInfo::simple(&err.to_string()));
+                            }
+                        });
+                    },
+
+                    if is_member_muted { "Unmute" } else { "Mute for an hour" }
+                }
+                button {
+                    class: "button",
+                    onclick: move |_| {
+                        let client = client_member_kicker.clone();
+
+                        spawn(async move {
+                            if let Err(err) = client.kick_member(chatroom_uid, member_id).await {
+                                tracing::error!("Error occured when kicking {member_id}: {}", err.to_string());
+
+                                toast.write().popup(ToastInfo::simple(&err.to_string()));
+                            }
+                        });
+                    },
+
+                    "Kick"
+                }
+                button {
+                    class: "button",
+                    onclick: move |_| {
+                        let client = client_member_banner.clone();
+
+                        spawn(async move {
+                            if let Err(err) = client.ban_member(chatroom_uid, member_id, None, None).await {
+                                tracing::error!("Error occured when banning {member_id}: {}", err.to_string());
+
+                                toast.write().popup(ToastInfo::simple(&err.to_string()));
+                            }
+                        });
+                    },
+
+                    "Ban"
+                }
+            }
+        }
+    )
+}
+
+/// Lets the admins of the chatroom create invites, and lists the ones which can still be redeemed.
+#[component]
+fn ChatroomInviteList(chatroom: FetchChatroomResponse, own_user_id: i32) -> Element {
+    let client = use_context::<ApplicationContext>().authed_http_client;
+    let mut toast: Signal<ToastManager> = use_context();
+
+    let mut chatroom_invites: Signal<Option<Vec<ChatroomInvite>>> = use_signal(|| None);
+    // The code of the invite created last, codes cannot be fetched again later
+    let mut created_invite_code: Signal<Option<String>> = use_signal(|| None);
+
+    let can_manage_invites = chatroom
+        .role_of(own_user_id)
+        .is_some_and(|role| role.has_permission(ChatroomPermission::ManageInvites));
+
+    if !can_manage_invites {
+        return rsx!();
+    }
+
+    let chatroom_uid = chatroom.chatroom_uid;
+    let client_invite_fetcher = client.clone();
+    let client_invite_creator = client.clone();
+
+    rsx!(
+        button {
+            class: "button",
+            id: "invite_list_button",
+            onclick: move |_| {
+                // Close the list if it is open
+                if chatroom_invites.read().is_some() {
+                    chatroom_invites.set(None);
+                    created_invite_code.set(None);
+
+                    return;
+                }
+
+                let client = client_invite_fetcher.clone();
+
+                spawn(async move {
+                    match client.list_chatroom_invites(chatroom_uid).await {
+                        Ok(response) => {
+                            let invites_response = serde_json::from_str::<ListChatroomInvitesResponse>(&response.text().await.unwrap()).unwrap();
+
+                            chatroom_invites.set(Some(invites_response.invites));
+                        },
+                        Err(err) => {
+                            tracing::error!("Error occured when fetching the invites of chatroom {chatroom_uid}: {}", err.to_string());
+
+                            toast.write().popup(ToastInfo::simple(&format!("Failed to fetch the invites: {err}")));
+                        },
+                    }
+                });
+            },
+
+            "Invites"
+        }
+
+        {
+            match chatroom_invites.read().clone() {
+                Some(invites) => rsx!(
+                    div {
+                        id: "invite_list",
+
+                        button {
+                            class: "button",
+                            onclick: move |_| {
+                                let client = client_invite_creator.clone();
+
+                                spawn(async move {
+                                    match client.create_chatroom_invite(chatroom_uid, None, Some(DEFAULT_INVITE_DURATION_SECS)).await {
+                                        Ok(response) => {
+                                            let invite_response = serde_json::from_str::<CreateChatroomInviteResponse>(&response.text().await.unwrap()).unwrap();
+
+                                            if let Some(invites) = chatroom_invites.write().as_mut() {
+                                                invites.insert(0, invite_response.invite);
+                                            }
+
+                                            created_invite_code.set(Some(invite_response.invite_code));
+                                        },
+                                        Err(err) => {
+                                            tracing::error!("Error occured when creating an invite to chatroom {chatroom_uid}: {}", err.to_string());
+
+                                            toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                        },
+                                    }
+                                });
+                            },
+
+                            "Create invite for a week"
+                        }
+
+                        if let Some(invite_code) = created_invite_code.read().clone() {
+                            div {
+                                class: "invite_entry",
+
+                                div {
+                                    class: "invite_details",
+                                    "Share this code, it will not be shown again:"
+                                }
+                                input {
+                                    id: "created_invite_code",
+                                    readonly: true,
+                                    value: "{invite_code}",
+                                }
+                            }
+                        }
+
+                        for invite in invites {
+                            div {
+                                class: "invite_entry",
+
+                                div {
+                                    {
+                                        match invite.max_uses {
+                                            Some(max_uses) => format!("Used {}/{} times", invite.uses, max_uses),
+                                            None => format!("Used {} times", invite.uses),
+                                        }
+                                    }
+                                }
+                                div {
+                                    class: "invite_details",
+                                    {
+                                        match invite.expires_at {
+                                            Some(expires_at) => format!("Expires at {}", expires_at.format("%Y-%m-%d %H:%M UTC")),
+                                            None => "Never expires".to_string(),
+                                        }
+                                    }
+                                }
+                                button {
+                                    class: "button",
+                                    onclick: {
+                                        let client = client.clone();
+                                        let invite_id = invite.invite_id;
+
+                                        move |_| {
+                                            let client = client.clone();
+
+                                            spawn(async move {
+                                                match client.revoke_chatroom_invite(chatroom_uid, invite_id).await {
+                                                    Ok(_) => {
+                                                        if let Some(invites) = chatroom_invites.write().as_mut() {
+                                                            invites.retain(|invite| invite.invite_id != invite_id);
+                                                        }
+                                                    },
+                                                    Err(err) => {
+                                                        tracing::error!("Error occured when revoking invite {invite_id}: {}", err.to_string());
+
+                                                        toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                                    },
+                                                }
+                                            });
+                                        }
+                                    },
+
+                                    "Revoke"
+                                }
+                            }
+                        }
+                    }
+                ),
+                None => rsx!(),
+            }
+        }
+    )
+}
+
+/// Marks the message as edited, clicking the marker shows the previous versions of the message.
+#[component]
+fn MessageEditHistory(message_id: i32, edited_at: chrono::NaiveDateTime) -> Element {
+    let client = use_context::<ApplicationContext>().authed_http_client;
+    let mut toast: Signal<ToastManager> = use_context();
+
+    let mut message_edits: Signal<Option<Vec<MessageEdit>>> = use_signal(|| None);
+
+    rsx!(
+        span {
+            class: "edited_marker",
+            title: format!("Edited at {}", edited_at.format("%Y-%m-%d %H:%M UTC")),
+            onclick: move |_| {
+                // Close the history if it is open
+                if message_edits.read().is_some() {
+                    message_edits.set(None);
+
+                    return;
+                }
+
+                let client = client.clone();
+
+                spawn(async move {
+                    match client.fetch_message_edits(message_id).await {
+                        Ok(response) => {
+                            let edits_response = serde_json::from_str::<FetchMessageEditsResponse>(&response.text().await.unwrap()).unwrap();
+
+                            message_edits.set(Some(edits_response.edits));
+                        },
+                        Err(err) => {
+                            tracing::error!("Error occured when fetching the edits of message {message_id}: {}", err.to_string());
+
+                            toast.write().popup(ToastInfo::simple(&format!("Failed to fetch the edit history: {err}")));
+                        },
+                    }
+                });
+            },
+
+            "(edited)"
+        }
+
+        if let Some(edits) = message_edits.read().clone() {
+            div {
+                class: "message_edits",
+
+                for edit in edits {
+                    div {
+                        class: "message_edit",
+
+                        div {
+                            class: "message_edit_details",
+                            { format!("Replaced at {}", edit.edited_at.format("%Y-%m-%d %H:%M UTC")) }
+                        }
+                        match edit.message {
+                            WebSocketChatroomMessages::StringMessage(message) => rsx!(div { "{message}" }),
+                            // The history of deleted messages is removed with their content
+                            WebSocketChatroomMessages::Deleted { .. } => rsx!(),
+                        }
+                    }
+                }
+            }
+        }
+    )
+}
+
+/// Shows the reactions of the message, clicking a reaction adds or removes the user's own.
+#[component]
+fn MessageReactions(message_id: i32, reactions: Vec<MessageReaction>) -> Element {
+    let client = use_context::<ApplicationContext>().authed_http_client;
+    let mut toast: Signal<ToastManager> = use_context();
+
+    let mut is_picker_open = use_signal(|| false);
+
+    // The reactions are updated once the server broadcasts the change
+    let toggle_reaction = move |emoji: String, reacted: bool| {
+        let client = client.clone();
+
+        spawn(async move {
+            let result = if reacted {
+                client.remove_reaction(message_id, emoji).await
+            }
+            else {
+                client.add_reaction(message_id, emoji).await
+            };
+
+            if let Err(err) = result {
+                tracing::error!("Error occured when reacting to message {message_id}: {}", err.to_string());
+
+                toast.write().popup(ToastInfo::simple(&format!("Failed to update the reaction: {err}")));
+            }
+        });
+    };
+
+    rsx!(
+        div {
+            class: "message_reactions",
+
+            for reaction in reactions.clone() {
+                button {
+                    class: if reaction.reacted { "reaction_chip reacted" } else { "reaction_chip" },
+                    onclick: {
+                        let toggle_reaction = toggle_reaction.clone();
+
+                        move |_| toggle_reaction(reaction.emoji.clone(), reaction.reacted)
+                    },
+
+                    { format!("{} {}", reaction.emoji, reaction.count) }
+                }
+            }
+
+            button {
+                class: "reaction_chip",
+                id: "add_reaction_button",
+                onclick: move |_| {
+                    let was_open = *is_picker_open.read();
+
+                    is_picker_open.set(!was_open);
+                },
+
+                "+"
+            }
+
+            if *is_picker_open.read() {
+                div {
+                    class: "reaction_picker",
+
+                    for emoji in REACTION_PICKER_EMOJI {
+                        button {
+                            class: "reaction_chip",
+                            onclick: {
+                                let toggle_reaction = toggle_reaction.clone();
+                                // Picking an emoji the user has already reacted with removes it
+                                let reacted = reactions.iter().any(|reaction| reaction.emoji == emoji && reaction.reacted);
+
+                                move |_| {
+                                    is_picker_open.set(false);
+
+                                    toggle_reaction(emoji.to_string(), reacted);
+                                }
+                            },
+
+                            "{emoji}"
+                        }
+                    }
+                }
+            }
+        }
+    )
+}
+
+/// Shows the details of the chatroom, its admins can also change its settings and its owner can delete it from here.
+#[component]
+fn ChatroomSettingsPanel(chatroom: FetchChatroomResponse, own_user_id: i32) -> Element {
+    let client = use_context::<ApplicationContext>().authed_http_client;
+    let mut toast: Signal<ToastManager> = use_context();
+
+    let mut is_panel_open = use_signal(|| false);
+    // Deleting the chatroom has to be confirmed with a second click
+    let mut is_deletion_requested = use_signal(|| false);
+    let mut chatroom_name_buffer = use_signal(String::new);
+    let mut description_buffer = use_signal(String::new);
+    let mut password_buffer = use_signal(String::new);
+
+    // Direct messages have no settings
+    if chatroom.is_direct_message {
+        return rsx!();
+    }
+
+    let own_role = chatroom.role_of(own_user_id);
+    let can_manage_settings = own_role.is_some_and(|role| role.has_permission(ChatroomPermission::ManageSettings));
+    let can_delete_chatroom = own_role.is_some_and(|role| role.has_permission(ChatroomPermission::DeleteChatroom));
+
+    let chatroom_uid = chatroom.chatroom_uid;
+    let current_chatroom_name = chatroom.chatroom_name.clone();
+    let current_description = chatroom.description.clone().unwrap_or_default();
+    let client_settings_updater = client.clone();
+    let client_password_remover = client.clone();
+    let client_icon_uploader = client.clone();
+    let client_icon_remover = client.clone();
+    let client_chatroom_deleter = client.clone();
+
+    rsx!(
+        button {
+            class: "button",
+            id: "settings_button",
+            onclick: move |_| {
+                let was_open = *is_panel_open.read();
+
+                // Start editing from the current settings every time the panel is opened
+                if !was_open {
+                    chatroom_name_buffer.set(current_chatroom_name.clone());
+                    description_buffer.set(current_description.clone());
+                    password_buffer.set(String::new());
+                }
+
+                is_deletion_requested.set(false);
+                is_panel_open.set(!was_open);
+            },
+
+            "Settings"
+        }
+
+        if *is_panel_open.read() {
+            div {
+                id: "chatroom_settings",
+
+                div {
+                    class: "settings_entry",
+
+                    div { "{chatroom.chatroom_name}" }
+                    div {
+                        class: "settings_details",
+                        {
+                            match &chatroom.description {
+                                Some(description) => description.clone(),
+                                None => "This chatroom has no description.".to_string(),
+                            }
+                        }
+                    }
+                    div {
+                        class: "settings_details",
+                        if chatroom.has_password {
+                            "Joining with the chatroom's id requires a password."
+                        }
+                        else {
+                            "Anyone with the chatroom's id can join."
+                        }
+                    }
+                }
+
+                if can_manage_settings {
+                    div {
+                        class: "settings_entry",
+
+                        input {
+                            value: "{chatroom_name_buffer}",
+                            placeholder: "Chatroom name",
+                            oninput: move |event| {
+                                chatroom_name_buffer.set(event.value());
+                            },
+                        }
+                        input {
+                            value: "{description_buffer}",
+                            placeholder: "Description",
+                            oninput: move |event| {
+                                description_buffer.set(event.value());
+                            },
+                        }
+                        input {
+                            r#type: "password",
+                            value: "{password_buffer}",
+                            placeholder: "New password (optional)",
+                            oninput: move |event| {
+                                password_buffer.set(event.value());
+                            },
+                        }
+                        button {
+                            class: "button",
+                            onclick: move |_| {
+                                let client = client_settings_updater.clone();
+                                let chatroom_name = chatroom_name_buffer.read().clone();
+                                let description = description_buffer.read().clone();
+                                // An empty password field leaves the current password unchanged
+                                let password = Some(password_buffer.read().clone()).filter(|password| !password.is_empty());
+
+                                spawn(async move {
+                                    // The updated settings are received over the WebSocket connection
+                                    match client.update_chatroom_settings(chatroom_uid, Some(chatroom_name), Some(description), password).await {
+                                        Ok(_) => {
+                                            password_buffer.set(String::new());
+
+                                            toast.write().popup(ToastInfo::simple("The chatroom's settings have been saved."));
+                                        },
+                                        Err(err) => {
+                                            tracing::error!("Error occured when updating the settings of chatroom {chatroom_uid}: {}", err.to_string());
+
+                                            toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                        },
+                                    }
+                                });
+                            },
+
+                            "Save"
+                        }
+                        if chatroom.has_password {
+                            button {
+                                class: "button",
+                                onclick: move |_| {
+                                    let client = client_password_remover.clone();
+
+                                    spawn(async move {
+                                        if let Err(err) = client.update_chatroom_settings(chatroom_uid, None, None, Some(String::new())).await {
+                                            tracing::error!("Error occured when removing the password of chatroom {chatroom_uid}: {}", err.to_string());
+
+                                            toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                        }
+                                    });
+                                },
+
+                                "Remove password"
+                            }
+                        }
+                    }
+                    div {
+                        class: "settings_entry",
+
+                        "Icon: "
+                        input {
+                            r#type: "file",
+                            accept: "image/png, image/jpeg, image/gif, image/webp",
+                            onchange: move |event| {
+                                let client = client_icon_uploader.clone();
+
+                                async move {
+                                    let Some(file_engine) = event.files() else {
+                                        return;
+                                    };
+
+                                    let Some(file_name) = file_engine.files().first().cloned() else {
+                                        return;
+                                    };
+
+                                    let Some(icon_image) = file_engine.read_file(&file_name).await else {
+                                        toast.write().popup(ToastInfo::simple("The selected file could not be read."));
+
+                                        return;
+                                    };
+
+                                    if let Err(err) = client.update_chatroom_icon(chatroom_uid, icon_image).await {
+                                        tracing::error!("Error occured when uploading the icon of chatroom {chatroom_uid}: {}", err.to_string());
+
+                                        toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                    }
+                                }
+                            },
+                        }
+                        if chatroom.has_icon {
+                            button {
+                                class: "button",
+                                onclick: move |_| {
+                                    let client = client_icon_remover.clone();
+
+                                    spawn(async move {
+                                        if let Err(err) = client.update_chatroom_icon(chatroom_uid, Vec::new()).await {
+                                            tracing::error!("Error occured when removing the icon of chatroom {chatroom_uid}: {}", err.to_string());
+
+                                            toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                        }
+                                    });
+                                },
+
+                                "Remove icon"
+                            }
+                        }
+                    }
+                }
+
+                if can_delete_chatroom {
+                    button {
+                        class: "button",
+                        id: "delete_chatroom_button",
+                        onclick: move |_| {
+                            if !*is_deletion_requested.read() {
+                                is_deletion_requested.set(true);
+
+                                return;
+                            }
+
+                            let client = client_chatroom_deleter.clone();
+
+                            spawn(async move {
+                                // The chatroom is removed locally once the deletion event arrives
+                                if let Err(err) = client.delete_chatroom(chatroom_uid).await {
+                                    tracing::error!("Error occured when deleting chatroom {chatroom_uid}: {}", err.to_string());
+
+                                    toast.write().popup(ToastInfo::simple(&format!("Failed to delete the chatroom: {err}")));
+                                }
+                            });
+                        },
+
+                        if *is_deletion_requested.read() {
+                            "Click again to delete the chatroom"
+                        }
+                        else {
+                            "Delete chatroom"
+                        }
+                    }
+                }
+            }
+        }
+    )
+}
+
+/// Lists the active bans of the chatroom to its moderators, the bans are fetched every time the list is opened.
+#[component]
+fn ChatroomBanList(chatroom: FetchChatroomResponse, own_user_id: i32, user_info_cache: Signal<HashMap<i32, UserLookup>>) -> Element {
+    let client = use_context::<ApplicationContext>().authed_http_client;
+    let mut toast: Signal<ToastManager> = use_context();
+
+    let mut chatroom_bans: Signal<Option<Vec<ChatroomBan>>> = use_signal(|| None);
+
+    let is_moderator = chatroom
+        .role_of(own_user_id)
+        .is_some_and(|role| role.has_permission(ChatroomPermission::ModerateMembers));
+
+    if !is_moderator {
+        return rsx!();
+    }
+
+    let chatroom_uid = chatroom.chatroom_uid;
+    let client_ban_fetcher = client.clone();
+
+    rsx!(
+        button {
+            class: "button",
+            id: "ban_list_button",
+            onclick: move |_| {
+                // Close the list if it is open
+                if chatroom_bans.read().is_some() {
+                    chatroom_bans.set(None);
+
+                    return;
+                }
+
+                let client = client_ban_fetcher.clone();
+
+                spawn(async move {
+                    match client.list_chatroom_bans(chatroom_uid).await {
+                        Ok(response) => {
+                            let bans_response = serde_json::from_str::<ListChatroomBansResponse>(&response.text().await.unwrap()).unwrap();
+
+                            chatroom_bans.set(Some(bans_response.bans));
+                        },
+                        Err(err) => {
+                            tracing::error!("Error occured when fetching the bans of chatroom {chatroom_uid}: {}", err.to_string());
+
+                            toast.write().popup(ToastInfo::simple(&format!("Failed to fetch the bans: {err}")));
+                        },
+                    }
+                });
+            },
+
+            "Bans"
+        }
+
+        {
+            match chatroom_bans.read().clone() {
+                Some(bans) => rsx!(
+                    div {
+                        id: "ban_list",
+
+                        if bans.is_empty() {
+                            div {
+                                class: "ban_entry",
+                                "Nobody is banned from this chatroom."
+                            }
+                        }
+
+                        for ban in bans {
+                            div {
+                                class: "ban_entry",
+
+                                div {
+                                    {
+                                        match user_info_cache.read().get(&ban.user_id) {
+                                            Some(user_information) => user_information.display_name().to_string(),
+                                            None => format!("User #{}", ban.user_id),
+                                        }
+                                    }
+                                }
+                                div {
+                                    class: "ban_details",
+                                    {
+                                        match ban.expires_at {
+                                            Some(expires_at) => format!("Until {}", expires_at.format("%Y-%m-%d %H:%M UTC")),
+                                            None => "Permanent".to_string(),
+                                        }
+                                    }
+                                    if let Some(reason) = &ban.reason {
+                                        { format!(" - {reason}") }
+                                    }
+                                }
+                                button {
+                                    class: "button",
+                                    onclick: {
+                                        let client = client.clone();
+                                        let banned_user_id = ban.user_id;
+
+                                        move |_| {
+                                            let client = client.clone();
+
+                                            spawn(async move {
+                                                match client.unban_member(chatroom_uid, banned_user_id).await {
+                                                    Ok(_) => {
+                                                        if let Some(bans) = chatroom_bans.write().as_mut() {
+                                                            bans.retain(|ban| ban.user_id != banned_user_id);
+                                                        }
+                                                    },
+                                                    Err(err) => {
+                                                        tracing::error!("Error occured when unbanning {banned_user_id}: {}", err.to_string());
+
+                                                        toast.write().popup(ToastInfo::simple(&err.to_string()));
+                                                    },
+                                                }
+                                            });
+                                        }
+                                    },
+
+                                    "Unban"
+                                }
+                            }
+                        }
+                    }
+                ),
+                None => rsx!(),
+            }
+        }
+    )
+}
+
 pub fn display_loading_svg() -> Element {
     rsx!(div {
         id: "loading_animation",
@@ -1,3 +1,6 @@
+pub mod change_password;
+pub mod edit_profile;
+pub mod forgot_password;
 pub mod login;
 pub mod main_page;
 pub mod not_found;
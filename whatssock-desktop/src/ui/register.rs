@@ -1,7 +1,7 @@
 use std::{fmt::Display, sync::Arc};
 
 use crate::{
-    api_requests::init_websocket_connection, authentication::auth::{deserialize_into_login_response, store_user_account_on_disk}, HttpClient, SessionEncryptionKey, COOKIE_SAVE_PATH
+    api_requests::init_websocket_connection, authentication::auth::{deserialize_into_login_response, store_user_session_on_disk}, ActiveUserSession, HttpClient, SessionEncryptionKey, SharedUserSession, COOKIE_SAVE_PATH
 };
 use dioxus::{logger::tracing, prelude::*};
 use parking_lot::Mutex;
@@ -84,6 +84,8 @@ pub fn Register() -> Element {
                             Ok(response) => {
                                 let login_response_secure = deserialize_into_login_response(response.text().await.unwrap()).unwrap();
 
+                                store_user_session_on_disk(&login_response_secure.user_session_secure, (*COOKIE_SAVE_PATH).clone()).unwrap();
+
                                 let (login_response, encryption_key) = login_response_secure.pop_secure_key();
 
                                 provide_root_context(SessionEncryptionKey(Arc::new(SecureArray::new(encryption_key).unwrap())));
@@ -92,8 +94,6 @@ pub fn Register() -> Element {
                                 log_res.set(Some(AttemptResult::Succeeded("Register Successful! Redirecting....".to_string())));
 
                                 user_login_response.set(Some(login_response.clone()));
-
-                                store_user_account_on_disk(&crate::UserAccount { username: username.to_string(), password: password.to_string() }, (*COOKIE_SAVE_PATH).clone()).unwrap();
                             },
                             Err(err) => {
                                 tracing::error!("Error occured when registering: {}", err.to_string());
@@ -155,8 +155,13 @@ pub fn Register() -> Element {
                     if let Some(login_response) = user_login_response.read().clone() {
                         provide_root_context((login_response.user_session.clone(), login_response.user_information));
 
+                        let shared_user_session: SharedUserSession = provide_root_context(Arc::new(Mutex::new(ActiveUserSession {
+                            user_session: login_response.user_session,
+                            expires_at: login_response.session_expires_at,
+                        })));
+
                         provide_root_context({
-                            let (sender, reciever) = init_websocket_connection(login_response.user_session.clone());
+                            let (sender, reciever) = init_websocket_connection(shared_user_session);
 
                             (sender, Arc::new(Mutex::new(reciever)))
                         });
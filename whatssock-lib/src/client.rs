@@ -1,11 +1,13 @@
 use chrono::NaiveDateTime;
 
-use crate::{MessageFetchType, UserSession, WebSocketChatroomMessages};
+use crate::{ChatroomRole, MessageFetchType, MessageReaction, WebSocketChatroomMessages};
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    /// A human readable name of the device the session is created for.
+    pub device_label: String,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -13,13 +15,101 @@ pub struct RegisterRequest {
     pub username: String,
     pub password: String,
     pub email: String,
+    /// A human readable name of the device the session is created for.
+    pub device_label: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TwoFactorLoginRequest {
+    /// The token of the challenge returned by the login endpoint.
+    pub challenge_token: String,
+    /// A code generated by the user's authenticator app, or one of the user's recovery codes.
+    pub code: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ConfirmTotpRequest {
+    /// The first code generated by the authenticator app after adding the provisioning URI.
+    pub code: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct DisableTotpRequest {
+    pub password: String,
+    /// A code generated by the user's authenticator app, or one of the user's recovery codes.
+    pub code: String,
+}
+
+/// Replaces the editable parts of the user's profile, fields left empty are cleared.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UpdateProfileRequest {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UpdatePrivacyRequest {
+    pub discoverable: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SearchUsersRequest {
+    /// Matched against the beginning of the usernames and display names first, then fuzzily.
+    pub query: String,
+    /// The index of the requested page, starting from 0.
+    pub page: i64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+    /// Required if the account has two-factor authentication enabled, either a TOTP code or a recovery code.
+    pub totp_code: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RevokeSessionRequest {
+    /// The id of the session which should be revoked, this can be any of the user's sessions.
+    pub session_id: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct VerifyEmailRequest {
+    /// The verification code which was sent to the user's email address.
+    pub token: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ForgotPasswordRequest {
+    /// The username of the account, the reset code is sent to the email address of the account.
+    pub username: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ResetPasswordRequest {
+    /// The reset code which was sent to the user's email address.
+    pub token: String,
+    pub new_password: String,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct UserSessionInformation {
     pub username: String,
-    pub chatrooms_joined: Vec<Option<i32>>,
+    pub chatrooms_joined: Vec<i32>,
     pub user_id: i32,
+    /// Unverified accounts cannot create chatrooms.
+    pub email_verified: bool,
+    /// Whether logging in requires a TOTP code.
+    pub totp_enabled: bool,
+    /// Whether the user shows up in the results of the user search.
+    pub discoverable: bool,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -36,9 +126,119 @@ pub struct WebSocketChatroomMessageClient {
     /// The date when it was sent.
     /// This will be overwritten by the server.
     pub date_issued: NaiveDateTime,
+    /// When the message was last edited, `None` if it has never been edited.
+    pub edited_at: Option<NaiveDateTime>,
+    /// The reactions of the message, the changes are received as [`WebSocketChatroomEventClient::ReactionAdded`] and [`WebSocketChatroomEventClient::ReactionRemoved`] events.
+    pub reactions: Vec<MessageReaction>,
+    /// The root of the thread this message was sent to, thread replies are not part of the main timeline.
+    pub thread_root_id: Option<i32>,
+    /// The amount of replies in the thread started from this message, the changes are received as [`WebSocketChatroomEventClient::ThreadUpdated`] events.
+    pub thread_reply_count: u32,
+    pub thread_last_reply_at: Option<NaiveDateTime>,
+}
+
+/// Every event the server sends to the clients over the WebSocket connection.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum WebSocketChatroomEventClient {
+    /// A new message has been sent to one of the user's chatrooms.
+    Message(WebSocketChatroomMessageClient),
+    /// A participant has left the chatroom.
+    ParticipantLeft { chatroom_uid: i32, user_id: i32 },
+    /// A participant has been removed from the chatroom by a moderator.
+    ParticipantRemoved {
+        chatroom_uid: i32,
+        user_id: i32,
+        reason: ParticipantRemovalReason,
+    },
+    /// A participant has been muted, or unmuted if `muted_until` is `None`.
+    MemberMuted {
+        chatroom_uid: i32,
+        user_id: i32,
+        muted_until: Option<NaiveDateTime>,
+    },
+    /// The role of a participant has changed.
+    MemberRoleChanged {
+        chatroom_uid: i32,
+        user_id: i32,
+        role: ChatroomRole,
+    },
+    /// A message has been edited by its author, its previous content is kept in its edit history.
+    MessageEdited {
+        chatroom_uid: i32,
+        message_id: i32,
+        message: WebSocketChatroomMessages,
+        edited_at: NaiveDateTime,
+    },
+    /// A message has been deleted, its content is replaced with [`WebSocketChatroomMessages::Deleted`].
+    MessageDeleted {
+        chatroom_uid: i32,
+        message_id: i32,
+        removed_by_moderator: bool,
+        /// The latest message of the chatroom after the deletion.
+        last_message_id: Option<i32>,
+    },
+    /// A reply has been sent to or removed from a thread.
+    ThreadUpdated {
+        chatroom_uid: i32,
+        thread_root_id: i32,
+        reply_count: u32,
+        /// `None` if every reply of the thread has been deleted.
+        last_reply_at: Option<NaiveDateTime>,
+    },
+    /// A participant has reacted to a message.
+    ReactionAdded {
+        chatroom_uid: i32,
+        message_id: i32,
+        user_id: i32,
+        emoji: String,
+    },
+    /// A participant has removed their reaction from a message.
+    ReactionRemoved {
+        chatroom_uid: i32,
+        message_id: i32,
+        user_id: i32,
+        emoji: String,
+    },
+    /// The name, description, icon or password of the chatroom has changed.
+    ChatroomSettingsChanged {
+        chatroom_uid: i32,
+        chatroom_name: String,
+        description: Option<String>,
+        has_icon: bool,
+        has_password: bool,
+    },
+    /// The chatroom has been deleted along with its messages.
+    ChatroomDeleted { chatroom_uid: i32 },
+    /// The message the user has sent was not delivered, this is only sent to the user's own connection.
+    MessageRejected {
+        chatroom_uid: i32,
+        reason: MessageRejectionReason,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum MessageRejectionReason {
+    /// The user's role does not allow sending messages to the chatroom.
+    MissingPermission,
+    /// The user has been muted in the chatroom.
+    Muted { until: NaiveDateTime },
+    /// The replied to message is not in the chatroom or has been deleted.
+    InvalidReply,
+    /// The root of the thread is not in the chatroom, has been deleted or is a thread reply itself.
+    InvalidThread,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum ParticipantRemovalReason {
+    Kicked,
+    /// The ban is permanent if `expires_at` is `None`.
+    Banned {
+        expires_at: Option<NaiveDateTime>,
+    },
 }
 
 impl WebSocketChatroomMessageClient {
+    /// Creates a message of the main timeline, which has not been reacted or replied to yet.
     pub fn new(
         message_id: i32,
         message_owner_id: i32,
@@ -46,6 +246,7 @@ impl WebSocketChatroomMessageClient {
         sent_to: i32,
         message: WebSocketChatroomMessages,
         date_issued: NaiveDateTime,
+        edited_at: Option<NaiveDateTime>,
     ) -> Self {
         Self {
             message_id,
@@ -54,12 +255,16 @@ impl WebSocketChatroomMessageClient {
             sent_to,
             message,
             date_issued,
+            edited_at,
+            reactions: Vec::new(),
+            thread_root_id: None,
+            thread_reply_count: 0,
+            thread_last_reply_at: None,
         }
     }
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct FetchMessages {
-    pub user_session: UserSession,
     pub message_request: MessageFetchType,
 }
@@ -1,10 +1,52 @@
 pub const POST_SESSION_VERIFICATION: &str = "/api/session";
+pub const POST_REFRESH_SESSION: &str = "/api/session_refresh";
 pub const POST_LOGIN: &str = "/api/login";
+pub const POST_LOGIN_TOTP: &str = "/api/login_totp";
 pub const POST_REGISTER: &str = "/api/register";
 pub const POST_LOGOUT: &str = "/api/logout";
+pub const POST_VERIFY_EMAIL: &str = "/api/verify_email";
+pub const POST_RESEND_VERIFICATION_EMAIL: &str = "/api/verify_email_resend";
+pub const POST_CHANGE_PASSWORD: &str = "/api/password_change";
+pub const POST_FORGOT_PASSWORD: &str = "/api/password_forgot";
+pub const POST_RESET_PASSWORD: &str = "/api/password_reset";
+pub const POST_TOTP_ENROLL: &str = "/api/totp_enroll";
+pub const POST_TOTP_CONFIRM: &str = "/api/totp_confirm";
+pub const POST_TOTP_DISABLE: &str = "/api/totp_disable";
+pub const POST_EXPORT_ACCOUNT: &str = "/api/account_export";
+pub const POST_DELETE_ACCOUNT: &str = "/api/account_delete";
+pub const POST_UPDATE_PROFILE: &str = "/api/profile_update";
+pub const POST_UPDATE_AVATAR: &str = "/api/avatar_update";
+pub const POST_UPDATE_PRIVACY: &str = "/api/privacy_update";
+pub const POST_SEARCH_USERS: &str = "/api/user_search";
+pub const POST_LIST_SESSIONS: &str = "/api/sessions";
+pub const POST_REVOKE_SESSION: &str = "/api/session_revoke";
+pub const POST_REVOKE_OTHER_SESSIONS: &str = "/api/session_revoke_others";
 pub const POST_REQUEST_UK_CHATROOM: &str = "/api/request_unknown_chatroom";
 pub const POST_REQUEST_K_CHATROOM: &str = "/api/request_known_chatroom";
 pub const POST_NEW_CHATROOM: &str = "/api/chatroom_new";
+pub const POST_LEAVE_CHATROOM: &str = "/api/chatroom_leave";
+pub const POST_UPDATE_MEMBER_ROLE: &str = "/api/chatroom_member_role";
+pub const POST_TRANSFER_OWNERSHIP: &str = "/api/chatroom_transfer_ownership";
+pub const POST_KICK_MEMBER: &str = "/api/chatroom_kick";
+pub const POST_BAN_MEMBER: &str = "/api/chatroom_ban";
+pub const POST_UNBAN_MEMBER: &str = "/api/chatroom_unban";
+pub const POST_LIST_BANS: &str = "/api/chatroom_bans";
+pub const POST_MUTE_MEMBER: &str = "/api/chatroom_mute";
+pub const POST_CREATE_INVITE: &str = "/api/chatroom_invite_new";
+pub const POST_LIST_INVITES: &str = "/api/chatroom_invites";
+pub const POST_REVOKE_INVITE: &str = "/api/chatroom_invite_revoke";
+pub const POST_JOIN_BY_INVITE: &str = "/api/chatroom_join_invite";
+pub const POST_UPDATE_CHATROOM_SETTINGS: &str = "/api/chatroom_settings";
+pub const POST_UPDATE_CHATROOM_ICON: &str = "/api/chatroom_icon";
+pub const POST_DELETE_CHATROOM: &str = "/api/chatroom_delete";
+pub const POST_START_DIRECT_MESSAGE: &str = "/api/direct_message_start";
+pub const POST_EDIT_MESSAGE: &str = "/api/message_edit";
+pub const POST_DELETE_MESSAGE: &str = "/api/message_delete";
+pub const POST_ADD_REACTION: &str = "/api/message_reaction_add";
+pub const POST_REMOVE_REACTION: &str = "/api/message_reaction_remove";
+pub const POST_FETCH_MESSAGE_EDITS: &str = "/api/message_edits";
 pub const GET_FETCH_USER: &str = "/api/fetch_user";
+pub const GET_FETCH_AVATAR: &str = "/api/fetch_avatar";
 pub const GET_FETCH_MESSAGES: &str = "/api/fetch_messages";
+pub const GET_FETCH_CHATROOM_ICON: &str = "/api/fetch_chatroom_icon";
 pub const WS_ESTABLISH_CHATROOM_CONNECTION: &str = "/ws/chatroom";
\ No newline at end of file
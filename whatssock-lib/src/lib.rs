@@ -2,6 +2,8 @@ pub mod client;
 pub mod server;
 pub mod domain_paths;
 
+use std::{fmt::Display, str::FromStr};
+
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
@@ -13,11 +15,51 @@ pub struct UserSession {
     pub session_token: [u8; 32],
 }
 
+impl UserSession {
+    /// Encodes the session into the token sent in the `Authorization: Bearer` header.
+    /// The format is the user's id and the hex encoded session token separated by a dot.
+    pub fn to_bearer_token(&self) -> String {
+        format!("{}.{}", self.user_id, encode_secure_key(&self.session_token))
+    }
+
+    /// Decodes a token created by [`UserSession::to_bearer_token`], returns `None` if the token is malformed.
+    pub fn from_bearer_token(bearer_token: &str) -> Option<Self> {
+        let (user_id, encoded_session_token) = bearer_token.split_once('.')?;
+
+        Some(Self {
+            user_id: user_id.parse().ok()?,
+            session_token: decode_secure_key(encoded_session_token)?,
+        })
+    }
+}
+
+/// Encodes a 32 byte key as hex, so that it can be sent in a header or to the user in an email.
+pub fn encode_secure_key(key: &[u8; 32]) -> String {
+    key.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decodes a key encoded with [`encode_secure_key`], returns `None` if it is malformed.
+pub fn decode_secure_key(encoded_key: &str) -> Option<[u8; 32]> {
+    if encoded_key.len() != 64 || !encoded_key.is_ascii() {
+        return None;
+    }
+
+    let mut key = [0_u8; 32];
+
+    for (idx, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&encoded_key[idx * 2..idx * 2 + 2], 16).ok()?;
+    }
+
+    Some(key)
+}
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq)]
 pub struct UserSessionSecure {
     pub user_id: i32,
     pub session_token: [u8; 32],
     pub encryption_key: [u8; 32],
+    /// The session token has to be refreshed before this date, otherwise it becomes invalid.
+    pub expires_at: NaiveDateTime,
 }
 
 impl UserSessionSecure {
@@ -35,14 +77,245 @@ impl UserSessionSecure {
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct FetchUnknownChatroom {
-    pub user_session: UserSession,
     pub chatroom_id: String,
+    /// Only chatrooms with a password can be joined with their id, the others can only be joined through an invite.
     pub password: Option<String>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct LeaveChatroomRequest {
+    pub chatroom_uid: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct LeaveChatroomResponse {}
+
+/// The role of a member in a chatroom, every role can do everything the roles ranked below it can.
+/// The variants are declared from the lowest to the highest rank, so that roles can be compared.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChatroomRole {
+    /// Can read the chatroom, but cannot send messages to it.
+    ReadOnly,
+    Member,
+    /// Can manage the members ranked below them.
+    Admin,
+    /// The user who has created the chatroom, or the one it has been transferred to.
+    Owner,
+}
+
+/// The actions which are restricted to certain roles.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChatroomPermission {
+    SendMessages,
+    /// Kicking, banning and muting the members ranked below the user, and deleting their messages.
+    ModerateMembers,
+    /// Changing the roles of the members ranked below the user.
+    ManageRoles,
+    /// Creating, listing and revoking the invites of the chatroom.
+    ManageInvites,
+    /// Changing the name, description, icon and password of the chatroom.
+    ManageSettings,
+    TransferOwnership,
+    DeleteChatroom,
+}
+
+impl ChatroomRole {
+    pub fn has_permission(&self, permission: ChatroomPermission) -> bool {
+        match permission {
+            ChatroomPermission::SendMessages => *self >= ChatroomRole::Member,
+            ChatroomPermission::ModerateMembers => *self >= ChatroomRole::Admin,
+            ChatroomPermission::ManageRoles => *self >= ChatroomRole::Admin,
+            ChatroomPermission::ManageInvites => *self >= ChatroomRole::Admin,
+            ChatroomPermission::ManageSettings => *self >= ChatroomRole::Admin,
+            ChatroomPermission::TransferOwnership => *self == ChatroomRole::Owner,
+            ChatroomPermission::DeleteChatroom => *self == ChatroomRole::Owner,
+        }
+    }
+
+    /// The name of the role as it is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatroomRole::ReadOnly => "read_only",
+            ChatroomRole::Member => "member",
+            ChatroomRole::Admin => "admin",
+            ChatroomRole::Owner => "owner",
+        }
+    }
+}
+
+impl FromStr for ChatroomRole {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "read_only" => Ok(ChatroomRole::ReadOnly),
+            "member" => Ok(ChatroomRole::Member),
+            "admin" => Ok(ChatroomRole::Admin),
+            "owner" => Ok(ChatroomRole::Owner),
+            _ => Err(format!("Unknown chatroom role: `{role}`")),
+        }
+    }
+}
+
+impl Display for ChatroomRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ChatroomRole::ReadOnly => "Read-only",
+            ChatroomRole::Member => "Member",
+            ChatroomRole::Admin => "Admin",
+            ChatroomRole::Owner => "Owner",
+        })
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Hash, Eq)]
+pub struct ChatroomMember {
+    pub user_id: i32,
+    pub role: ChatroomRole,
+    /// The member cannot send messages until this date.
+    pub muted_until: Option<NaiveDateTime>,
+}
+
+/// Promotes or demotes a member, ownership can only be changed with [`TransferOwnershipRequest`].
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct UpdateMemberRoleRequest {
+    pub chatroom_uid: i32,
+    pub user_id: i32,
+    pub role: ChatroomRole,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct UpdateMemberRoleResponse {}
+
+/// Makes another member the owner of the chatroom, the previous owner becomes an admin.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct TransferOwnershipRequest {
+    pub chatroom_uid: i32,
+    pub user_id: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct TransferOwnershipResponse {}
+
+/// Removes a member from the chatroom, they can join it again.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct KickMemberRequest {
+    pub chatroom_uid: i32,
+    pub user_id: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct KickMemberResponse {}
+
+/// Removes the user from the chatroom and stops them from joining it again.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct BanMemberRequest {
+    pub chatroom_uid: i32,
+    pub user_id: i32,
+    /// How long the ban lasts in seconds, the ban is permanent if this is `None`.
+    pub duration_secs: Option<u32>,
+    pub reason: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct BanMemberResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct UnbanMemberRequest {
+    pub chatroom_uid: i32,
+    pub user_id: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct UnbanMemberResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ListChatroomBansRequest {
+    pub chatroom_uid: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatroomBan {
+    pub user_id: i32,
+    pub banned_by: i32,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    /// The ban is permanent if this is `None`.
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// The bans of the chatroom which have not expired yet.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ListChatroomBansResponse {
+    pub bans: Vec<ChatroomBan>,
+}
+
+/// Stops a member from sending messages for a while.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct MuteMemberRequest {
+    pub chatroom_uid: i32,
+    pub user_id: i32,
+    /// How long the mute lasts in seconds, the member is unmuted if this is `None`.
+    pub duration_secs: Option<u32>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct MuteMemberResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct CreateChatroomInviteRequest {
+    pub chatroom_uid: i32,
+    /// How many times the invite can be redeemed, it can be redeemed any number of times if this is `None`.
+    pub max_uses: Option<u32>,
+    /// How long the invite is valid for in seconds, it never expires if this is `None`.
+    pub duration_secs: Option<u32>,
+}
+
+/// The invite code is only returned once, as the server only stores its hash.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CreateChatroomInviteResponse {
+    pub invite: ChatroomInvite,
+    pub invite_code: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatroomInvite {
+    pub invite_id: i32,
+    pub created_by: i32,
+    pub uses: u32,
+    pub max_uses: Option<u32>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ListChatroomInvitesRequest {
+    pub chatroom_uid: i32,
+}
+
+/// The invites of the chatroom which can still be redeemed.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ListChatroomInvitesResponse {
+    pub invites: Vec<ChatroomInvite>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RevokeChatroomInviteRequest {
+    pub chatroom_uid: i32,
+    pub invite_id: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RevokeChatroomInviteResponse {}
+
+/// Joins the chatroom the invite was created for, the server responds with a [`FetchChatroomResponse`].
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct JoinByInviteRequest {
+    pub invite_code: String,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct FetchKnownChatrooms {
-    pub user_session: UserSession,
     pub chatroom_uids: Vec<i32>,
 }
 
@@ -56,27 +329,179 @@ pub struct FetchChatroomResponse {
     pub chatroom_uid: i32,
     pub chatroom_id: String,
     pub chatroom_name: String,
-    /// The reason it is an option is because this is what diesel hehe
-    pub participants: Vec<Option<i32>>,
+    pub description: Option<String>,
+    /// The icon itself has to be fetched separately, from [`domain_paths::GET_FETCH_CHATROOM_ICON`].
+    pub has_icon: bool,
+    /// The chatroom can be joined with its id and password, chatrooms without a password can only be joined through an invite.
+    pub has_password: bool,
+    pub participants: Vec<ChatroomMember>,
+    /// Direct messages have exactly two participants, and cannot be joined by anyone else.
     pub is_direct_message: bool,
     pub last_message_id: Option<i32>,
 }
 
+impl FetchChatroomResponse {
+    /// Returns the other participant of a direct message, this is `None` for regular chatrooms.
+    pub fn direct_message_partner(&self, user_id: i32) -> Option<i32> {
+        if !self.is_direct_message {
+            return None;
+        }
+
+        self.participants
+            .iter()
+            .map(|participant| participant.user_id)
+            .find(|participant| *participant != user_id)
+    }
+
+    /// Returns the role of the user, this is `None` if they are not a participant of the chatroom.
+    pub fn role_of(&self, user_id: i32) -> Option<ChatroomRole> {
+        self.participants
+            .iter()
+            .find(|participant| participant.user_id == user_id)
+            .map(|participant| participant.role)
+    }
+
+    /// Authors can delete their own messages, moderators can delete the messages of the members ranked below them.
+    pub fn can_delete_message(&self, message_owner_id: i32, user_id: i32) -> bool {
+        if message_owner_id == user_id {
+            return true;
+        }
+
+        let Some(own_role) = self.role_of(user_id) else {
+            return false;
+        };
+
+        // The messages of former members can be deleted by every moderator
+        own_role.has_permission(ChatroomPermission::ModerateMembers)
+            && self
+                .role_of(message_owner_id)
+                .is_none_or(|owner_role| owner_role < own_role)
+    }
+}
+
+/// Changes the settings of the chatroom, the fields which are `None` are left unchanged.
+/// The server responds with the updated [`FetchChatroomResponse`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UpdateChatroomSettingsRequest {
+    pub chatroom_uid: i32,
+    pub chatroom_name: Option<String>,
+    /// An empty description removes the current one.
+    pub description: Option<String>,
+    /// An empty password removes the current one, after which the chatroom can only be joined through an invite.
+    pub password: Option<String>,
+}
+
+/// Identifies the chatroom whose icon is fetched or uploaded, it is sent in the query string as the body holds the image.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+pub struct ChatroomIconQuery {
+    pub chatroom_uid: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct DeleteChatroomRequest {
+    pub chatroom_uid: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct DeleteChatroomResponse {}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct CreateChatroomRequest {
-    pub user_session: UserSession,
     pub chatroom_name: String,
+    /// The chatroom can only be joined through an invite if this is `None` or empty.
     pub chatroom_passw: Option<String>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct StartDirectMessageRequest {
+    /// The user the direct message is started with.
+    pub user_id: i32,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum WebSocketChatroomMessages {
     StringMessage(String),
+    /// Replaces the content of deleted messages, the message itself is kept so that replies and pagination keep working.
+    /// This cannot be sent by the clients.
+    Deleted { removed_by_moderator: bool },
+}
+
+/// Only the author of a message can edit it, within the server's edit window if one has been configured.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct EditMessageRequest {
+    pub message_id: i32,
+    /// The new content of the message, the previous content is kept in the message's edit history.
+    pub message: WebSocketChatroomMessages,
+}
+
+/// Authors can delete their own messages, moderators can delete the messages of the members ranked below them.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DeleteMessageRequest {
+    pub message_id: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DeleteMessageResponse {}
+
+/// The aggregated reactions of a message with the same emoji.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MessageReaction {
+    pub emoji: String,
+    pub count: u32,
+    /// Whether the user who has fetched the message has reacted with this emoji.
+    pub reacted: bool,
 }
 
+/// Adds or removes the user's reaction, depending on the endpoint it is sent to.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct MessageReactionRequest {
+    pub message_id: i32,
+    /// A single emoji, which may be a sequence such as a flag or an emoji with a skin tone.
+    pub emoji: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct MessageReactionResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct FetchMessageEditsRequest {
+    pub message_id: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct FetchMessageEditsResponse {
+    /// The previous versions of the message, the oldest one first.
+    pub edits: Vec<MessageEdit>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct MessageEdit {
+    /// The content of the message before this edit.
+    pub message: WebSocketChatroomMessages,
+    /// When this version of the message was replaced.
+    pub edited_at: NaiveDateTime,
+}
+
+/// The owner id of the messages whose author has deleted their account, when the server keeps these messages.
+pub const DELETED_USER_ID: i32 = 0;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct UserLookup {
+    pub user_id: i32,
     pub username: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    /// The custom status text set by the user.
+    pub status: Option<String>,
+    /// The avatar itself has to be fetched separately, from [`domain_paths::GET_FETCH_AVATAR`].
+    pub has_avatar: bool,
+}
+
+impl UserLookup {
+    /// The name the user should be displayed with, falls back to the username if no display name has been set.
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
+    }
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Copy, Hash, PartialEq, Eq)]
@@ -92,11 +517,21 @@ pub struct BulkMessagesFromLatest {
     pub count: i32,
 }
 
+/// Fetches the replies of a thread, starting from the latest one.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Copy, Hash, PartialEq, Eq)]
+pub struct BulkThreadReplies {
+    pub thread_root_id: i32,
+    pub count: i32,
+    /// Only the replies older than this reply are fetched, the latest replies are fetched if this is `None`.
+    pub offset_id: Option<i32>,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Copy, Hash, PartialEq, Eq)]
 pub enum MessageFetchType {
     NextFromId(BulkMessagesFromId),
     SingluarFromId(i32),
     NextFromLatest(BulkMessagesFromLatest),
+    ThreadReplies(BulkThreadReplies),
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -118,6 +553,17 @@ pub struct ChatroomMessageResponse {
     pub raw_message: Vec<u8>,
     /// This will be overwritten by the server.
     pub date_issued: NaiveDateTime,
+    /// When the message was last edited, `None` if it has never been edited.
+    pub edited_at: Option<NaiveDateTime>,
+    /// When the message was deleted, the content of deleted messages is [`WebSocketChatroomMessages::Deleted`].
+    pub deleted_at: Option<NaiveDateTime>,
+    /// The reactions of the message, in the order they were first added.
+    pub reactions: Vec<MessageReaction>,
+    /// The root of the thread this message was sent to.
+    pub thread_root_id: Option<i32>,
+    /// The amount of replies in the thread started from this message, deleted replies are not counted.
+    pub thread_reply_count: u32,
+    pub thread_last_reply_at: Option<NaiveDateTime>,
 }
 
 impl From<ChatroomMessageResponse> for WebSocketChatroomMessageClient {
@@ -131,6 +577,11 @@ impl From<ChatroomMessageResponse> for WebSocketChatroomMessageClient {
                 rmp_serde::from_slice::<WebSocketChatroomMessages>(&val.raw_message).expect("Raw message bytes from `ChatroomMessageResponse` cannot be converted into a valid `WebSocketChatroomMessages` type.")
             },
             date_issued: val.date_issued,
+            edited_at: val.edited_at,
+            reactions: val.reactions,
+            thread_root_id: val.thread_root_id,
+            thread_reply_count: val.thread_reply_count,
+            thread_last_reply_at: val.thread_last_reply_at,
         }
     }
 }
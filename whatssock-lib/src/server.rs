@@ -1,6 +1,6 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 
-use crate::{client::UserSessionInformation, UserSession, UserSessionSecure, WebSocketChatroomMessages};
+use crate::{client::UserSessionInformation, MessageEdit, UserLookup, UserSession, UserSessionSecure, WebSocketChatroomMessages};
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct LoginResponseSecure {
@@ -10,12 +10,14 @@ pub struct LoginResponseSecure {
 
 impl LoginResponseSecure {
     pub fn pop_secure_key(self) -> (LoginResponse, [u8; 32]) {
+        let session_expires_at = self.user_session_secure.expires_at;
         let (user_session, encryption_key) = self.user_session_secure.pop_secure_key();
 
         (
             LoginResponse {
                 user_information: self.user_information,
                 user_session,
+                session_expires_at,
             },
 
             encryption_key
@@ -23,21 +25,178 @@ impl LoginResponseSecure {
     }
 }
 
+/// The response of the login endpoint.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum LoginOutcome {
+    LoggedIn(LoginResponseSecure),
+    /// The account has two-factor authentication enabled, the login has to be finished by submitting a code with the challenge's token.
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TwoFactorChallenge {
+    pub challenge_token: String,
+    /// The code has to be submitted before this date, otherwise the user has to log in again.
+    pub expires_at: NaiveDateTime,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct LoginResponse {
     pub user_information: UserSessionInformation,
     pub user_session: UserSession,
+    /// The session token has to be refreshed before this date, otherwise it becomes invalid.
+    pub session_expires_at: NaiveDateTime,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RefreshSessionResponse {
+    /// The same session with a newly issued session token, the previous token is no longer valid.
+    pub user_session: UserSession,
+    pub expires_at: NaiveDateTime,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct LogoutResponse {}
 
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct VerifyEmailResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ResendVerificationEmailResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ChangePasswordResponse {
+    /// The amount of the user's other sessions which have been logged out.
+    pub revoked_count: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TotpEnrollmentResponse {
+    /// The `otpauth://` URI which can be added to an authenticator app.
+    pub provisioning_uri: String,
+    /// The base32 encoded secret, for authenticator apps which cannot read the URI.
+    pub secret: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TotpConfirmationResponse {
+    /// Single-use codes which can be used instead of a TOTP code, these are only shown once.
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct DisableTotpResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ForgotPasswordResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ResetPasswordResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ActiveSession {
+    pub session_id: i32,
+    pub device_label: String,
+    pub created_at: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+    /// Whether this is the session the request was sent with.
+    pub is_current: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ActiveSessionsResponse {
+    pub sessions: Vec<ActiveSession>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RevokeSessionResponse {
+    /// The amount of sessions which have been revoked.
+    pub revoked_count: usize,
+}
+
+/// Everything the server stores about a user, returned by the data export endpoint.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct AccountDataExport {
+    pub exported_at: NaiveDateTime,
+    pub profile: ExportedProfile,
+    pub sessions: Vec<ActiveSession>,
+    pub chatrooms: Vec<ExportedChatroomMembership>,
+    pub messages: Vec<ExportedMessage>,
+    /// The reactions the user has added to messages, including the messages of other users.
+    pub reactions: Vec<ExportedReaction>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ExportedProfile {
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub totp_enabled: bool,
+    pub discoverable: bool,
+    pub created_at: NaiveDate,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub status: Option<String>,
+    /// The raw bytes of the user's avatar image.
+    pub avatar: Option<Vec<u8>>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ExportedChatroomMembership {
+    pub chatroom_uid: i32,
+    pub chatroom_id: String,
+    pub chatroom_name: String,
+    pub is_direct_message: bool,
+    pub role: String,
+    pub joined_at: NaiveDateTime,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ExportedMessage {
+    pub message_id: i32,
+    pub chatroom_uid: i32,
+    pub replying_to_msg_id: Option<i32>,
+    pub send_date: NaiveDateTime,
+    /// The decoded message, this is `None` if the stored message could not be decoded.
+    pub message: Option<WebSocketChatroomMessages>,
+    /// When the message was last edited.
+    pub edited_at: Option<NaiveDateTime>,
+    /// The root of the thread the message was sent to.
+    pub thread_root_id: Option<i32>,
+    /// The previous versions of the message, the oldest comes first.
+    pub edits: Vec<MessageEdit>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ExportedReaction {
+    pub message_id: i32,
+    pub emoji: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UpdatePrivacyResponse {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SearchUsersResponse {
+    /// The best matches come first.
+    pub users: Vec<UserLookup>,
+    /// Whether there are more results on the next page.
+    pub has_more: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct DeleteAccountResponse {}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct WebSocketChatroomMessageServer {
     /// The userid of the sender of this message.
     pub message_owner_session: UserSession,
     /// The message's id this message was replying to.
     pub replying_to_msg_id: Option<i32>,
+    /// The root of the thread this message is sent to, `None` if it is sent to the main timeline.
+    pub thread_root_id: Option<i32>,
     /// The ID of the chatroom this message has been sent to.
     pub sent_to: i32,
     /// The message itself.
@@ -51,6 +210,7 @@ impl WebSocketChatroomMessageServer {
     pub fn new(
         message_owner_session: UserSession,
         replying_to_msg_id: Option<i32>,
+        thread_root_id: Option<i32>,
         sent_to: i32,
         message: WebSocketChatroomMessages,
         date_issued: NaiveDateTime,
@@ -58,6 +218,7 @@ impl WebSocketChatroomMessageServer {
         Self {
             message_owner_session,
             replying_to_msg_id,
+            thread_root_id,
             sent_to,
             message,
             date_issued,
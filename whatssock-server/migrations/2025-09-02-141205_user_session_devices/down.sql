@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE user_session_auth
+    DROP COLUMN device_label,
+    DROP COLUMN created_at,
+    DROP COLUMN last_seen;
@@ -0,0 +1,5 @@
+-- Every login creates its own session row, the device label lets the user tell them apart
+ALTER TABLE user_session_auth
+    ADD COLUMN device_label VARCHAR NOT NULL DEFAULT 'Unknown device',
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    ADD COLUMN last_seen TIMESTAMP NOT NULL DEFAULT NOW();
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE user_session_auth
+    DROP COLUMN expires_at;
@@ -0,0 +1,3 @@
+-- Session tokens expire, they have to be refreshed before this date to keep the session alive
+ALTER TABLE user_session_auth
+    ADD COLUMN expires_at TIMESTAMP NOT NULL DEFAULT (NOW() + INTERVAL '1 day');
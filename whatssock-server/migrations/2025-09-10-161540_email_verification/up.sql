@@ -0,0 +1,15 @@
+-- Accounts have to verify their email address before they can create chatrooms
+ALTER TABLE users
+    ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Accounts created before verification existed were never sent a code, so they are not locked out of creating chatrooms
+UPDATE users SET email_verified = TRUE;
+
+-- Only the hash of the token is stored, the token itself is only sent to the user's email address
+CREATE TABLE email_verification_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    token_hash BYTEA NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
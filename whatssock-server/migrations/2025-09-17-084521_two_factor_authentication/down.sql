@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE login_challenges;
+
+DROP TABLE totp_recovery_codes;
+
+ALTER TABLE users
+    DROP COLUMN totp_last_used_step,
+    DROP COLUMN totp_enabled,
+    DROP COLUMN totp_secret;
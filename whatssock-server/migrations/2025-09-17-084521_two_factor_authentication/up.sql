@@ -0,0 +1,22 @@
+-- The secret is stored as soon as the enrolment starts, two-factor authentication is only enforced once it has been confirmed with a code
+ALTER TABLE users
+    ADD COLUMN totp_secret BYTEA,
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN totp_last_used_step BIGINT;
+
+-- Single-use codes which can be used instead of a TOTP code, only their hashes are stored
+CREATE TABLE totp_recovery_codes (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    code_hash BYTEA NOT NULL
+);
+
+-- Logins which have passed the password check, but still have to submit a second factor
+CREATE TABLE login_challenges (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    challenge_hash BYTEA NOT NULL UNIQUE,
+    device_label VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE user_avatars;
+
+ALTER TABLE users
+    DROP COLUMN status_text,
+    DROP COLUMN bio,
+    DROP COLUMN display_name;
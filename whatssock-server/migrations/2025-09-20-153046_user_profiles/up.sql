@@ -0,0 +1,12 @@
+-- Optional, user editable profile information, the username is shown wherever these are not set
+ALTER TABLE users
+    ADD COLUMN display_name VARCHAR,
+    ADD COLUMN bio VARCHAR,
+    ADD COLUMN status_text VARCHAR;
+
+-- Avatars are kept out of `users`, so that they are only loaded when they are actually requested
+CREATE TABLE user_avatars (
+    user_id INT PRIMARY KEY,
+    image BYTEA NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_display_name_trgm_idx;
+DROP INDEX users_username_trgm_idx;
+
+ALTER TABLE users
+    DROP COLUMN discoverable;
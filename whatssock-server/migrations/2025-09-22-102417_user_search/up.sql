@@ -0,0 +1,9 @@
+-- Trigram indexes make the fuzzy username and display name search fast
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Users can opt out of showing up in the search results
+ALTER TABLE users
+    ADD COLUMN discoverable BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE INDEX users_username_trgm_idx ON users USING GIN (username gin_trgm_ops);
+CREATE INDEX users_display_name_trgm_idx ON users USING GIN (display_name gin_trgm_ops);
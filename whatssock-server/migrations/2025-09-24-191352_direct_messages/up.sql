@@ -0,0 +1,2 @@
+-- The chatroom id of a direct message is derived from the ids of its two participants, so there can only be one direct message per pair of users
+CREATE UNIQUE INDEX chatrooms_direct_message_pair_idx ON chatrooms (chatroom_id) WHERE is_direct_message;
@@ -0,0 +1,21 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE chatrooms ADD COLUMN participants INT[] NOT NULL DEFAULT '{}';
+ALTER TABLE users ADD COLUMN chatrooms_joined INT[] NOT NULL DEFAULT '{}';
+
+UPDATE chatrooms SET participants = members.user_ids
+FROM (
+    SELECT chatroom_id, array_agg(user_id ORDER BY joined_at) AS user_ids
+    FROM chatroom_members
+    GROUP BY chatroom_id
+) AS members
+WHERE chatrooms.id = members.chatroom_id;
+
+UPDATE users SET chatrooms_joined = memberships.chatroom_ids
+FROM (
+    SELECT user_id, array_agg(chatroom_id ORDER BY joined_at) AS chatroom_ids
+    FROM chatroom_members
+    GROUP BY user_id
+) AS memberships
+WHERE users.id = memberships.user_id;
+
+DROP TABLE chatroom_members;
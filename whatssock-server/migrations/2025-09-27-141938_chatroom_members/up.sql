@@ -0,0 +1,34 @@
+-- Every membership is a single row, instead of being duplicated in `chatrooms.participants` and `users.chatrooms_joined`
+CREATE TABLE chatroom_members (
+    chatroom_id INT NOT NULL,
+    user_id INT NOT NULL,
+    role VARCHAR NOT NULL DEFAULT 'member',
+    joined_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chatroom_id, user_id)
+);
+
+-- The joined chatrooms of a user are looked up on every login and WebSocket connection
+CREATE INDEX chatroom_members_user_id_idx ON chatroom_members (user_id);
+
+-- The first participant of a regular chatroom is the user who has created it
+INSERT INTO chatroom_members (chatroom_id, user_id, role)
+SELECT chatrooms.id,
+    participant.user_id,
+    CASE WHEN participant.position = 1 AND NOT chatrooms.is_direct_message THEN 'owner' ELSE 'member' END
+FROM chatrooms
+CROSS JOIN LATERAL unnest(chatrooms.participants) WITH ORDINALITY AS participant(user_id, position)
+WHERE participant.user_id IS NOT NULL
+    AND EXISTS (SELECT 1 FROM users WHERE users.id = participant.user_id)
+ON CONFLICT DO NOTHING;
+
+-- The two arrays were not always updated together, so memberships which were only recorded on the user are kept too
+INSERT INTO chatroom_members (chatroom_id, user_id)
+SELECT joined.chatroom_id, users.id
+FROM users
+CROSS JOIN LATERAL unnest(users.chatrooms_joined) AS joined(chatroom_id)
+WHERE joined.chatroom_id IS NOT NULL
+    AND EXISTS (SELECT 1 FROM chatrooms WHERE chatrooms.id = joined.chatroom_id)
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE chatrooms DROP COLUMN participants;
+ALTER TABLE users DROP COLUMN chatrooms_joined;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX chatroom_members_owner_idx;
+ALTER TABLE chatroom_members DROP CONSTRAINT chatroom_members_role_check;
@@ -0,0 +1,22 @@
+-- Memberships can only have the roles known by the server
+ALTER TABLE chatroom_members
+    ADD CONSTRAINT chatroom_members_role_check CHECK (role IN ('owner', 'admin', 'member', 'read_only'));
+
+-- Regular chatrooms without an owner are handed to their longest standing member
+UPDATE chatroom_members SET role = 'owner'
+FROM (
+    SELECT DISTINCT ON (chatroom_members.chatroom_id) chatroom_members.chatroom_id, chatroom_members.user_id
+    FROM chatroom_members
+    JOIN chatrooms ON chatrooms.id = chatroom_members.chatroom_id
+    WHERE NOT chatrooms.is_direct_message
+        AND NOT EXISTS (
+            SELECT 1 FROM chatroom_members AS owners
+            WHERE owners.chatroom_id = chatroom_members.chatroom_id AND owners.role = 'owner'
+        )
+    ORDER BY chatroom_members.chatroom_id, chatroom_members.joined_at, chatroom_members.user_id
+) AS successors
+WHERE chatroom_members.chatroom_id = successors.chatroom_id
+    AND chatroom_members.user_id = successors.user_id;
+
+-- A chatroom can only have a single owner
+CREATE UNIQUE INDEX chatroom_members_owner_idx ON chatroom_members (chatroom_id) WHERE role = 'owner';
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE chatroom_bans;
+ALTER TABLE chatroom_members DROP COLUMN muted_until;
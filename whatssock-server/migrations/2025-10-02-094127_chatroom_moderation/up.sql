@@ -0,0 +1,13 @@
+-- Muted members stay in the chatroom, but cannot send messages to it until the mute expires
+ALTER TABLE chatroom_members ADD COLUMN muted_until TIMESTAMP;
+
+-- Banned users cannot join the chatroom again, bans without an expiry are permanent
+CREATE TABLE chatroom_bans (
+    chatroom_id INT NOT NULL,
+    user_id INT NOT NULL,
+    banned_by INT NOT NULL,
+    reason VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP,
+    PRIMARY KEY (chatroom_id, user_id)
+);
@@ -0,0 +1,13 @@
+-- Only the hash of the invite codes is stored, the codes themselves are shown once when the invite is created
+CREATE TABLE chatroom_invites (
+    id SERIAL PRIMARY KEY,
+    chatroom_id INT NOT NULL,
+    created_by INT NOT NULL,
+    code_hash BYTEA NOT NULL UNIQUE,
+    uses INT NOT NULL DEFAULT 0,
+    max_uses INT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP
+);
+
+CREATE INDEX chatroom_invites_chatroom_id_idx ON chatroom_invites (chatroom_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- The regenerated ids cannot be restored, the chatroom passwords were not changed by `up.sql`
+DROP INDEX chatrooms_chatroom_id_idx;
+CREATE UNIQUE INDEX chatrooms_direct_message_pair_idx ON chatrooms (chatroom_id) WHERE is_direct_message;
@@ -0,0 +1,46 @@
+-- Chatroom ids used to be sampled from the whole printable ASCII range without a uniqueness check.
+-- The ids which are not URL-safe or which collide with an earlier chatroom are regenerated, direct messages keep their derived ids.
+-- The ids have to be unguessable, so they are generated from pgcrypto's secure random bytes instead of random().
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+-- The chatroom passwords cannot be hashed here, as Argon2 is not available in SQL.
+-- The plaintext passwords stay in the db until the server replaces them with a hash, the next time the chatroom is joined with its password.
+
+DO $$
+DECLARE
+    chatroom RECORD;
+    id_alphabet CONSTANT TEXT := 'ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_';
+    random_bytes BYTEA;
+    new_chatroom_id TEXT;
+BEGIN
+    FOR chatroom IN
+        SELECT id FROM chatrooms
+        WHERE NOT is_direct_message
+            AND (
+                chatroom_id !~ '^[A-Za-z0-9_-]+$'
+                OR EXISTS (
+                    SELECT 1 FROM chatrooms AS earlier_chatroom
+                    WHERE earlier_chatroom.chatroom_id = chatrooms.chatroom_id
+                        AND earlier_chatroom.id < chatrooms.id
+                )
+            )
+        ORDER BY id
+    LOOP
+        LOOP
+            -- Every byte maps to a character of the 64 character alphabet without bias
+            random_bytes := gen_random_bytes(16);
+
+            SELECT string_agg(substr(id_alphabet, get_byte(random_bytes, byte_index) % 64 + 1, 1), '' ORDER BY byte_index)
+            INTO new_chatroom_id
+            FROM generate_series(0, 15) AS byte_index;
+
+            EXIT WHEN NOT EXISTS (SELECT 1 FROM chatrooms WHERE chatroom_id = new_chatroom_id);
+        END LOOP;
+
+        UPDATE chatrooms SET chatroom_id = new_chatroom_id WHERE id = chatroom.id;
+    END LOOP;
+END $$;
+
+-- Covers the direct messages too, so their partial index is not needed anymore
+DROP INDEX chatrooms_direct_message_pair_idx;
+CREATE UNIQUE INDEX chatrooms_chatroom_id_idx ON chatrooms (chatroom_id);
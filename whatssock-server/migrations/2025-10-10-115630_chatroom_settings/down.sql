@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE chatroom_icons;
+ALTER TABLE chatrooms DROP COLUMN chatroom_description;
@@ -0,0 +1,8 @@
+ALTER TABLE chatrooms ADD COLUMN chatroom_description VARCHAR;
+
+-- Icons are kept out of `chatrooms`, so that they are only loaded when they are actually requested
+CREATE TABLE chatroom_icons (
+    chatroom_id INT PRIMARY KEY,
+    image BYTEA NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
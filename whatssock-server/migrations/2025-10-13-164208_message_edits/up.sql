@@ -0,0 +1,11 @@
+ALTER TABLE messages ADD COLUMN edited_at TIMESTAMP;
+
+-- Every row is a previous version of a message, `edited_at` is when it was replaced
+CREATE TABLE message_edits (
+    id SERIAL PRIMARY KEY,
+    message_id INT NOT NULL,
+    raw_message BYTEA NOT NULL,
+    edited_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX message_edits_message_id_idx ON message_edits (message_id);
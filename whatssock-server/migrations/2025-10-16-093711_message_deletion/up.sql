@@ -0,0 +1,2 @@
+-- Deleted messages are kept as tombstones, their content is replaced when they are deleted
+ALTER TABLE messages ADD COLUMN deleted_at TIMESTAMP;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX messages_thread_root_id_idx;
+
+ALTER TABLE messages DROP COLUMN thread_last_reply_at;
+ALTER TABLE messages DROP COLUMN thread_reply_count;
+ALTER TABLE messages DROP COLUMN thread_root_id;
@@ -0,0 +1,7 @@
+-- Thread replies are kept out of the main timeline of the chatroom
+ALTER TABLE messages ADD COLUMN thread_root_id INT;
+-- The summary of the thread is stored on its root, so that it does not have to be counted for every fetched message
+ALTER TABLE messages ADD COLUMN thread_reply_count INT NOT NULL DEFAULT 0;
+ALTER TABLE messages ADD COLUMN thread_last_reply_at TIMESTAMP;
+
+CREATE INDEX messages_thread_root_id_idx ON messages (thread_root_id);
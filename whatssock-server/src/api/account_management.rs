@@ -0,0 +1,522 @@
+use std::{collections::HashMap, env};
+
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    Connection, ExpressionMethods, JoinOnDsl, NullableExpressionMethods, OptionalExtension,
+    QueryDsl, RunQueryDsl, SelectableHelper, delete, update,
+};
+use log::{error, info, warn};
+use whatssock_lib::{
+    ChatroomRole, DELETED_USER_ID, MessageEdit, WebSocketChatroomMessages,
+    client::{DeleteAccountRequest, WebSocketChatroomEventClient},
+    server::{
+        AccountDataExport, ActiveSession, DeleteAccountResponse, ExportedChatroomMembership,
+        ExportedMessage, ExportedProfile, ExportedReaction,
+    },
+};
+
+use crate::{
+    ServerState,
+    api::{
+        authentication::AuthenticatedUser,
+        memberships::hand_over_ownership,
+        messages::refresh_thread_summary,
+        two_factor::verify_second_factor,
+        user_account_control::{PasswordVerification, lookup_joined_chatrooms, verify_password},
+        websocket::{broadcast_chatroom_event, unsubscribe_user_from_chatroom},
+    },
+    models::{
+        ChatroomEntry, ChatroomMemberEntry, MessageEditEntry, MessageEntry, UserAccountEntry,
+        UserSessionEntry,
+    },
+    rate_limit::{RateLimitKey, RateLimitedError},
+    schema::{
+        chatroom_bans::{self, dsl::chatroom_bans as bans},
+        chatroom_invites::{self, dsl::chatroom_invites as invites},
+        chatroom_members::{self, dsl::chatroom_members as memberships},
+        chatrooms::{self, dsl::chatrooms as chatroom_entries},
+        email_verification_tokens::{self, dsl::email_verification_tokens as verification_tokens},
+        login_challenges::{self, dsl::login_challenges as challenges},
+        message_edits::{self, dsl::message_edits as edits},
+        message_reactions::{self, dsl::message_reactions as reactions},
+        messages::{self, dsl::messages as message_entries},
+        password_reset_tokens::{self, dsl::password_reset_tokens as reset_tokens},
+        totp_recovery_codes::{self, dsl::totp_recovery_codes as recovery_codes},
+        user_avatars::{self, dsl::user_avatars as avatars},
+        user_session_auth::{self, dsl::user_session_auth as user_sessions},
+        users::{self, dsl::users as user_accounts},
+    },
+};
+
+/// What happens to the messages of a deleted account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletedAccountMessagePolicy {
+    /// The messages are deleted along with the account.
+    Delete,
+    /// The messages are kept, but their owner is replaced with [`DELETED_USER_ID`], so that conversations stay readable.
+    Anonymise,
+}
+
+impl DeletedAccountMessagePolicy {
+    /// Reads the policy from `DELETED_ACCOUNT_MESSAGE_POLICY`, which is either `delete` or `anonymise`.
+    /// Messages are anonymised if the variable is not set.
+    pub fn from_env() -> anyhow::Result<Self> {
+        match env::var("DELETED_ACCOUNT_MESSAGE_POLICY") {
+            Ok(policy) => match policy.to_lowercase().as_str() {
+                "delete" => Ok(Self::Delete),
+                "anonymise" | "anonymize" => Ok(Self::Anonymise),
+                _ => anyhow::bail!(
+                    "DELETED_ACCOUNT_MESSAGE_POLICY must be either `delete` or `anonymise`, found: `{policy}`"
+                ),
+            },
+            Err(_) => Ok(Self::Anonymise),
+        }
+    }
+}
+
+pub async fn export_account_data(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+) -> Result<Json<AccountDataExport>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = user_accounts
+        .filter(users::id.eq(authenticated_user.user_id))
+        .get_result::<UserAccountEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching user account with id {}: {}",
+                authenticated_user.user_id, err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let session_entries = user_sessions
+        .filter(user_session_auth::user_id.eq(user_account.id))
+        .order(user_session_auth::created_at.asc())
+        .select(UserSessionEntry::as_select())
+        .load::<UserSessionEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the user's sessions from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let chatroom_memberships = memberships
+        .inner_join(chatroom_entries.on(chatrooms::id.eq(chatroom_members::chatroom_id)))
+        .filter(chatroom_members::user_id.eq(user_account.id))
+        .order(chatrooms::id.asc())
+        .select((ChatroomMemberEntry::as_select(), ChatroomEntry::as_select()))
+        .load::<(ChatroomMemberEntry, ChatroomEntry)>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the user's chatrooms from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let message_list = message_entries
+        .filter(messages::owner_user_id.eq(user_account.id))
+        .order(messages::id.asc())
+        .load::<MessageEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the user's messages from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut message_edit_history: HashMap<i32, Vec<MessageEdit>> = HashMap::new();
+
+    for edit_entry in edits
+        .filter(
+            message_edits::message_id.eq_any(
+                message_entries
+                    .filter(messages::owner_user_id.eq(user_account.id))
+                    .select(messages::id),
+            ),
+        )
+        .order(message_edits::id.asc())
+        .select(MessageEditEntry::as_select())
+        .load::<MessageEditEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the user's message edits from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    {
+        // Versions which cannot be decoded are left out instead of failing the whole export
+        match rmp_serde::from_slice::<WebSocketChatroomMessages>(&edit_entry.raw_message) {
+            Ok(message) => message_edit_history
+                .entry(edit_entry.message_id)
+                .or_default()
+                .push(MessageEdit {
+                    message,
+                    edited_at: edit_entry.edited_at,
+                }),
+            Err(err) => {
+                warn!(
+                    "The edit with id {} could not be decoded: {}",
+                    edit_entry.id, err
+                );
+            }
+        }
+    }
+
+    let reaction_list = reactions
+        .filter(message_reactions::user_id.eq(user_account.id))
+        .order(message_reactions::created_at.asc())
+        .select((
+            message_reactions::message_id,
+            message_reactions::emoji,
+            message_reactions::created_at,
+        ))
+        .load::<(i32, String, NaiveDateTime)>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the user's reactions from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let avatar_image = avatars
+        .filter(user_avatars::user_id.eq(user_account.id))
+        .select(user_avatars::image)
+        .get_result::<Vec<u8>>(&mut pg_connection)
+        .optional()
+        .map_err(|err| {
+            error!("An error occured while fetching the user's avatar: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(AccountDataExport {
+        exported_at: Utc::now().naive_utc(),
+        profile: ExportedProfile {
+            user_id: user_account.id,
+            username: user_account.username,
+            email: user_account.email,
+            email_verified: user_account.email_verified,
+            totp_enabled: user_account.totp_enabled,
+            discoverable: user_account.discoverable,
+            created_at: user_account.created_at,
+            display_name: user_account.display_name,
+            bio: user_account.bio,
+            status: user_account.status_text,
+            avatar: avatar_image,
+        },
+        // The tokens and keys of the sessions are left out, they are secrets and not personal data
+        sessions: session_entries
+            .into_iter()
+            .map(|session_entry| ActiveSession {
+                session_id: session_entry.token_id,
+                device_label: session_entry.device_label,
+                created_at: session_entry.created_at,
+                last_seen: session_entry.last_seen,
+                is_current: session_entry.token_id == authenticated_user.session.token_id,
+            })
+            .collect(),
+        chatrooms: chatroom_memberships
+            .into_iter()
+            .map(|(membership, chatroom)| ExportedChatroomMembership {
+                chatroom_uid: chatroom.id,
+                chatroom_id: chatroom.chatroom_id,
+                chatroom_name: chatroom.chatroom_name,
+                is_direct_message: chatroom.is_direct_message,
+                role: membership.role,
+                joined_at: membership.joined_at,
+            })
+            .collect(),
+        messages: message_list
+            .into_iter()
+            .map(|message| ExportedMessage {
+                message_id: message.id,
+                chatroom_uid: message.parent_chatroom_id,
+                replying_to_msg_id: message.replying_to_msg,
+                send_date: message.send_date,
+                message: rmp_serde::from_slice::<WebSocketChatroomMessages>(&message.raw_message)
+                    .map_err(|err| {
+                        warn!(
+                            "The message with id {} could not be decoded: {}",
+                            message.id, err
+                        );
+                    })
+                    .ok(),
+                edited_at: message.edited_at,
+                thread_root_id: message.thread_root_id,
+                edits: message_edit_history.remove(&message.id).unwrap_or_default(),
+            })
+            .collect(),
+        reactions: reaction_list
+            .into_iter()
+            .map(|(message_id, emoji, created_at)| ExportedReaction {
+                message_id,
+                emoji,
+                created_at,
+            })
+            .collect(),
+    }))
+}
+
+pub async fn delete_account(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(delete_request): Json<DeleteAccountRequest>,
+) -> Result<Json<DeleteAccountResponse>, RateLimitedError> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = user_accounts
+        .filter(users::id.eq(authenticated_user.user_id))
+        .get_result::<UserAccountEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching user account with id {}: {}",
+                authenticated_user.user_id, err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // The user has to authenticate again, wrong attempts count as failed logins
+    let rate_limit_keys = [RateLimitKey::Username(user_account.username.clone())];
+
+    state.login_rate_limiter.check(&rate_limit_keys)?;
+
+    // The password is checked first, so that a wrong password cannot use up the user's recovery codes or TOTP step
+    let is_authenticated = verify_password(&delete_request.password, &user_account.passw)
+        != PasswordVerification::Invalid
+        && match (user_account.totp_enabled, &delete_request.totp_code) {
+            (false, _) => true,
+            (true, Some(totp_code)) => {
+                verify_second_factor(&mut pg_connection, &user_account, totp_code)?
+            }
+            (true, None) => false,
+        };
+
+    if !is_authenticated {
+        state.login_rate_limiter.record_attempt(&rate_limit_keys);
+
+        return Err(StatusCode::FORBIDDEN.into());
+    }
+
+    let joined_chatroom_ids = lookup_joined_chatrooms(&mut pg_connection, user_account.id)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the user's chatrooms from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let new_owners = pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            let owned_chatroom_ids = memberships
+                .filter(chatroom_members::user_id.eq(user_account.id))
+                .filter(chatroom_members::role.eq(ChatroomRole::Owner.as_str()))
+                .select(chatroom_members::chatroom_id)
+                .load::<i32>(pg_connection)?;
+
+            // Leave every chatroom the user has joined
+            delete(memberships.filter(chatroom_members::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            // The chatrooms owned by the user are handed to their remaining members
+            let mut new_owners = Vec::new();
+
+            for owned_chatroom_id in owned_chatroom_ids {
+                if let Some(new_owner) = hand_over_ownership(pg_connection, owned_chatroom_id)? {
+                    new_owners.push((owned_chatroom_id, new_owner));
+                }
+            }
+
+            delete(reactions.filter(message_reactions::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            match state.deleted_account_message_policy {
+                DeletedAccountMessagePolicy::Delete => {
+                    let affected_chatroom_ids = message_entries
+                        .filter(messages::owner_user_id.eq(user_account.id))
+                        .select(messages::parent_chatroom_id)
+                        .distinct()
+                        .load::<i32>(pg_connection)?;
+
+                    let affected_thread_root_ids = message_entries
+                        .filter(messages::owner_user_id.eq(user_account.id))
+                        .filter(messages::thread_root_id.is_not_null())
+                        .select(messages::thread_root_id.assume_not_null())
+                        .distinct()
+                        .load::<i32>(pg_connection)?;
+
+                    // Only messages of the main timeline can be thread roots
+                    let possible_thread_root_ids = message_entries
+                        .filter(messages::owner_user_id.eq(user_account.id))
+                        .filter(messages::thread_root_id.is_null())
+                        .select(messages::id)
+                        .load::<i32>(pg_connection)?;
+
+                    // The replies to the user's threads are moved to the main timeline, so that they stay reachable
+                    update(
+                        message_entries
+                            .filter(messages::thread_root_id.eq_any(possible_thread_root_ids)),
+                    )
+                    .set(messages::thread_root_id.eq(None::<i32>))
+                    .execute(pg_connection)?;
+
+                    // The previous versions and the reactions of the messages are deleted with them
+                    delete(
+                        edits.filter(
+                            message_edits::message_id.eq_any(
+                                message_entries
+                                    .filter(messages::owner_user_id.eq(user_account.id))
+                                    .select(messages::id),
+                            ),
+                        ),
+                    )
+                    .execute(pg_connection)?;
+
+                    delete(
+                        reactions.filter(
+                            message_reactions::message_id.eq_any(
+                                message_entries
+                                    .filter(messages::owner_user_id.eq(user_account.id))
+                                    .select(messages::id),
+                            ),
+                        ),
+                    )
+                    .execute(pg_connection)?;
+
+                    delete(message_entries.filter(messages::owner_user_id.eq(user_account.id)))
+                        .execute(pg_connection)?;
+
+                    for affected_thread_root_id in affected_thread_root_ids {
+                        refresh_thread_summary(pg_connection, affected_thread_root_id)?;
+                    }
+
+                    // The deleted messages might have been the latest ones of their chatrooms
+                    for affected_chatroom_id in affected_chatroom_ids {
+                        let latest_message_id = message_entries
+                            .filter(messages::parent_chatroom_id.eq(affected_chatroom_id))
+                            .filter(messages::thread_root_id.is_null())
+                            .filter(messages::deleted_at.is_null())
+                            .select(diesel::dsl::max(messages::id))
+                            .get_result::<Option<i32>>(pg_connection)?;
+
+                        update(chatroom_entries.filter(chatrooms::id.eq(affected_chatroom_id)))
+                            .set(chatrooms::last_message_id.eq(latest_message_id))
+                            .execute(pg_connection)?;
+                    }
+                }
+                DeletedAccountMessagePolicy::Anonymise => {
+                    update(message_entries.filter(messages::owner_user_id.eq(user_account.id)))
+                        .set(messages::owner_user_id.eq(DELETED_USER_ID))
+                        .execute(pg_connection)?;
+                }
+            }
+
+            delete(user_sessions.filter(user_session_auth::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            delete(
+                verification_tokens.filter(email_verification_tokens::user_id.eq(user_account.id)),
+            )
+            .execute(pg_connection)?;
+
+            delete(reset_tokens.filter(password_reset_tokens::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            delete(recovery_codes.filter(totp_recovery_codes::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            delete(avatars.filter(user_avatars::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            delete(challenges.filter(login_challenges::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            // The user's bans are lifted, the bans issued by the user stay in effect
+            delete(bans.filter(chatroom_bans::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            update(bans.filter(chatroom_bans::banned_by.eq(user_account.id)))
+                .set(chatroom_bans::banned_by.eq(DELETED_USER_ID))
+                .execute(pg_connection)?;
+
+            // The invites created by the user cannot be redeemed anymore
+            delete(invites.filter(chatroom_invites::created_by.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            delete(user_accounts.filter(users::id.eq(user_account.id))).execute(pg_connection)?;
+
+            Ok(new_owners)
+        })
+        .map_err(|err| {
+            error!(
+                "An error occured while deleting the user's account: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    for joined_chatroom_id in joined_chatroom_ids {
+        // Stop delivering the chatroom's messages to the user's open connection
+        unsubscribe_user_from_chatroom(&state, joined_chatroom_id, user_account.id);
+
+        // Notify the remaining participants, so that the user is removed from their participant lists
+        broadcast_chatroom_event(
+            &state,
+            joined_chatroom_id,
+            &WebSocketChatroomEventClient::ParticipantLeft {
+                chatroom_uid: joined_chatroom_id,
+                user_id: user_account.id,
+            },
+        );
+    }
+
+    for (chatroom_uid, new_owner) in new_owners {
+        broadcast_chatroom_event(
+            &state,
+            chatroom_uid,
+            &WebSocketChatroomEventClient::MemberRoleChanged {
+                chatroom_uid,
+                user_id: new_owner,
+                role: ChatroomRole::Owner,
+            },
+        );
+    }
+
+    info!("The account with id {} has been deleted.", user_account.id);
+
+    Ok(Json(DeleteAccountResponse {}))
+}
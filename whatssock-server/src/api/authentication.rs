@@ -0,0 +1,56 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{StatusCode, header::AUTHORIZATION, request::Parts},
+};
+use log::error;
+use whatssock_lib::UserSession;
+
+use crate::{
+    ServerState, api::user_account_control::verify_user_session, models::UserSessionEntry,
+};
+
+/// An extractor which authenticates the request via its `Authorization: Bearer` header.
+/// The token is created with [`UserSession::to_bearer_token`] and is verified with [`verify_user_session`] before the handler is called.
+///
+/// A missing or malformed header is rejected with [`StatusCode::UNAUTHORIZED`], an invalid session with [`StatusCode::FORBIDDEN`].
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    /// The id of the user who has sent the request.
+    pub user_id: i32,
+    /// The session the request has been authenticated with.
+    pub session: UserSessionEntry,
+}
+
+impl FromRequestParts<ServerState> for AuthenticatedUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &ServerState,
+    ) -> Result<Self, Self::Rejection> {
+        let user_session = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|header_value| header_value.strip_prefix("Bearer "))
+            .and_then(UserSession::from_bearer_token)
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        // Get a db connection from the pool
+        let mut pg_connection = state.pg_pool.get().map_err(|err| {
+            error!(
+                "An error occured while fetching login information from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        let session = verify_user_session(&user_session, &mut pg_connection)?;
+
+        Ok(Self {
+            user_id: session.user_id,
+            session,
+        })
+    }
+}
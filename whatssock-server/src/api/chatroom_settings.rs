@@ -0,0 +1,368 @@
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Query, State},
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
+    delete, insert_into, update,
+};
+use log::error;
+use whatssock_lib::{
+    ChatroomIconQuery, ChatroomPermission, DeleteChatroomRequest, DeleteChatroomResponse,
+    FetchChatroomResponse, UpdateChatroomSettingsRequest, client::WebSocketChatroomEventClient,
+};
+
+use crate::{
+    ServerState,
+    api::{
+        authentication::AuthenticatedUser,
+        chatrooms::fetch_chatroom_response,
+        memberships::{is_chatroom_member, require_chatroom_permission},
+        profiles::{detect_image_mime_type, normalize_profile_field},
+        user_account_control::hash_password,
+        websocket::{broadcast_chatroom_event, close_chatroom},
+    },
+    models::{ChatroomEntry, ChatroomIconEntry, UpdateChatroomSettings},
+    schema::{
+        chatroom_bans::{self, dsl::chatroom_bans as bans},
+        chatroom_icons::{self, dsl::chatroom_icons as icons},
+        chatroom_invites::{self, dsl::chatroom_invites as invites},
+        chatroom_members::{self, dsl::chatroom_members as memberships},
+        chatrooms::{self, dsl::chatrooms as chatroom_entries},
+        message_edits::{self, dsl::message_edits as edits},
+        message_reactions::{self, dsl::message_reactions as reactions},
+        messages::{self, dsl::messages as chatroom_messages},
+    },
+};
+
+/// The maximum length of the chatroom's name in characters.
+pub const MAX_CHATROOM_NAME_LENGTH: usize = 64;
+
+/// The maximum length of the chatroom's description in characters.
+pub const MAX_CHATROOM_DESCRIPTION_LENGTH: usize = 500;
+
+/// The maximum size of a chatroom icon in bytes.
+pub const MAX_CHATROOM_ICON_SIZE: usize = 256 * 1024;
+
+pub async fn update_chatroom_settings(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(settings_request): Json<UpdateChatroomSettingsRequest>,
+) -> Result<Json<FetchChatroomResponse>, StatusCode> {
+    let chatroom_name = settings_request
+        .chatroom_name
+        .map(|chatroom_name| chatroom_name.trim().to_string());
+
+    // The name cannot be removed, only changed
+    if chatroom_name.as_ref().is_some_and(|chatroom_name| {
+        chatroom_name.is_empty() || chatroom_name.chars().count() > MAX_CHATROOM_NAME_LENGTH
+    }) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let updated_settings = UpdateChatroomSettings {
+        chatroom_name,
+        chatroom_description: settings_request
+            .description
+            .map(|description| {
+                normalize_profile_field(Some(description), MAX_CHATROOM_DESCRIPTION_LENGTH)
+            })
+            .transpose()?,
+        chatroom_password: settings_request
+            .password
+            .map(|password| {
+                (!password.is_empty())
+                    .then(|| hash_password(&password))
+                    .transpose()
+            })
+            .transpose()?,
+    };
+
+    // There is nothing to update
+    if updated_settings.chatroom_name.is_none()
+        && updated_settings.chatroom_description.is_none()
+        && updated_settings.chatroom_password.is_none()
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        settings_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::ManageSettings,
+    )?;
+
+    let chatroom_entry =
+        update(chatroom_entries.filter(chatrooms::id.eq(settings_request.chatroom_uid)))
+            .set(&updated_settings)
+            .returning(ChatroomEntry::as_returning())
+            .get_result(&mut pg_connection)
+            .map_err(|err| {
+                error!(
+                    "An error occured while updating the chatroom's settings: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+    let chatroom_response = fetch_chatroom_response(&mut pg_connection, chatroom_entry)?;
+
+    broadcast_settings_change(&state, &chatroom_response);
+
+    Ok(Json(chatroom_response))
+}
+
+/// Replaces the chatroom's icon with the image in the request's body, an empty body removes the icon.
+pub async fn update_chatroom_icon(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Query(icon_query): Query<ChatroomIconQuery>,
+    icon_image: Bytes,
+) -> Result<Json<FetchChatroomResponse>, StatusCode> {
+    if icon_image.len() > MAX_CHATROOM_ICON_SIZE {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    if !icon_image.is_empty() && detect_image_mime_type(&icon_image).is_none() {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        icon_query.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::ManageSettings,
+    )?;
+
+    if icon_image.is_empty() {
+        delete(icons.filter(chatroom_icons::chatroom_id.eq(icon_query.chatroom_uid)))
+            .execute(&mut pg_connection)
+            .map_err(|err| {
+                error!(
+                    "An error occured while removing the chatroom's icon: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    } else {
+        let icon_entry = ChatroomIconEntry {
+            chatroom_id: icon_query.chatroom_uid,
+            image: icon_image.to_vec(),
+            updated_at: Utc::now().naive_utc(),
+        };
+
+        insert_into(icons)
+            .values(&icon_entry)
+            .on_conflict(chatroom_icons::chatroom_id)
+            .do_update()
+            .set((
+                chatroom_icons::image.eq(&icon_entry.image),
+                chatroom_icons::updated_at.eq(icon_entry.updated_at),
+            ))
+            .execute(&mut pg_connection)
+            .map_err(|err| {
+                error!(
+                    "An error occured while storing the chatroom's icon: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+
+    let chatroom_entry = chatroom_entries
+        .filter(chatrooms::id.eq(icon_query.chatroom_uid))
+        .select(ChatroomEntry::as_select())
+        .get_result(&mut pg_connection)
+        .map_err(|err| {
+            error!("An error occured while fetching chatrooms from db: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let chatroom_response = fetch_chatroom_response(&mut pg_connection, chatroom_entry)?;
+
+    broadcast_settings_change(&state, &chatroom_response);
+
+    Ok(Json(chatroom_response))
+}
+
+/// Returns the icon of the chatroom, only its members can fetch it.
+pub async fn fetch_chatroom_icon(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Query(icon_query): Query<ChatroomIconQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let is_member = is_chatroom_member(
+        &mut pg_connection,
+        icon_query.chatroom_uid,
+        authenticated_user.user_id,
+    )
+    .map_err(|err| {
+        error!(
+            "An error occured while fetching chatroom members from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Non-members are not told whether the chatroom exists
+    if !is_member {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let icon_image = icons
+        .filter(chatroom_icons::chatroom_id.eq(icon_query.chatroom_uid))
+        .select(chatroom_icons::image)
+        .get_result::<Vec<u8>>(&mut pg_connection)
+        .optional()
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the chatroom's icon: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Only images with a known format are accepted when uploading the icon
+    let mime_type = detect_image_mime_type(&icon_image).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(([(CONTENT_TYPE, mime_type)], icon_image))
+}
+
+/// Deletes the chatroom with everything that belongs to it, its online participants are notified and unsubscribed.
+pub async fn delete_chatroom(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(delete_request): Json<DeleteChatroomRequest>,
+) -> Result<Json<DeleteChatroomResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        delete_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::DeleteChatroom,
+    )?;
+
+    let chatroom_uid = delete_request.chatroom_uid;
+
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            delete(
+                edits.filter(
+                    message_edits::message_id.eq_any(
+                        chatroom_messages
+                            .filter(messages::parent_chatroom_id.eq(chatroom_uid))
+                            .select(messages::id),
+                    ),
+                ),
+            )
+            .execute(pg_connection)?;
+
+            delete(
+                reactions.filter(
+                    message_reactions::message_id.eq_any(
+                        chatroom_messages
+                            .filter(messages::parent_chatroom_id.eq(chatroom_uid))
+                            .select(messages::id),
+                    ),
+                ),
+            )
+            .execute(pg_connection)?;
+
+            delete(chatroom_messages.filter(messages::parent_chatroom_id.eq(chatroom_uid)))
+                .execute(pg_connection)?;
+
+            delete(memberships.filter(chatroom_members::chatroom_id.eq(chatroom_uid)))
+                .execute(pg_connection)?;
+
+            delete(bans.filter(chatroom_bans::chatroom_id.eq(chatroom_uid)))
+                .execute(pg_connection)?;
+
+            delete(invites.filter(chatroom_invites::chatroom_id.eq(chatroom_uid)))
+                .execute(pg_connection)?;
+
+            delete(icons.filter(chatroom_icons::chatroom_id.eq(chatroom_uid)))
+                .execute(pg_connection)?;
+
+            delete(chatroom_entries.filter(chatrooms::id.eq(chatroom_uid)))
+                .execute(pg_connection)?;
+
+            Ok(())
+        })
+        .map_err(|err| {
+            error!("An error occured while deleting a chatroom: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    close_chatroom(
+        &state,
+        chatroom_uid,
+        &WebSocketChatroomEventClient::ChatroomDeleted { chatroom_uid },
+    );
+
+    Ok(Json(DeleteChatroomResponse {}))
+}
+
+fn broadcast_settings_change(state: &ServerState, chatroom_response: &FetchChatroomResponse) {
+    broadcast_chatroom_event(
+        state,
+        chatroom_response.chatroom_uid,
+        &WebSocketChatroomEventClient::ChatroomSettingsChanged {
+            chatroom_uid: chatroom_response.chatroom_uid,
+            chatroom_name: chatroom_response.chatroom_name.clone(),
+            description: chatroom_response.description.clone(),
+            has_icon: chatroom_response.has_icon,
+            has_password: chatroom_response.has_password,
+        },
+    );
+}
@@ -1,34 +1,87 @@
+use crate::api::authentication::AuthenticatedUser;
 use crate::api::chatrooms::users::dsl::users;
-use crate::api::user_account_control::{update_chatroom_last_msg, verify_user_session};
+use crate::api::memberships::{
+    add_chatroom_member, chatroom_response, hand_over_ownership, is_chatroom_member,
+    lookup_member_role, remove_chatroom_member,
+};
+use crate::api::messages::lookup_sender_rejection;
+use crate::api::moderation::is_user_banned;
+use crate::api::profiles::{deleted_user_profile, lookup_user_profile};
+use crate::api::reactions::load_message_reactions;
+use crate::api::user_account_control::{
+    PasswordVerification, hash_password, update_chatroom_last_msg, verify_password,
+    verify_user_session,
+};
+use crate::api::websocket::{
+    broadcast_chatroom_event, send_chatroom_event_to_subscriber, subscribe_connected_user,
+    unsubscribe_user_from_chatroom,
+};
+use crate::rate_limit::{RateLimitKey, RateLimitedError};
 use crate::schema::messages::dsl::messages;
 
 use crate::models::{ChatroomEntry, MessageEntry, NewChatroom, NewMessage, UserAccountEntry};
 use crate::schema::chatrooms::dsl::chatrooms;
-use crate::schema::chatrooms::{chatroom_id, chatroom_password, participants};
+use crate::schema::chatrooms::{chatroom_id, chatroom_password};
 use crate::schema::messages::parent_chatroom_id;
-use crate::schema::users::{chatrooms_joined, id};
+use crate::schema::users::id;
 use crate::{
     ServerState,
     schema::{self, *},
 };
-use axum::{Json, extract::State, http::StatusCode};
-use chrono::Utc;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper, insert_into};
+use axum::{
+    Json,
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+};
+use chrono::{NaiveDateTime, Utc};
+use diesel::result::DatabaseErrorKind;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
+    insert_into,
+};
 use log::{error, warn};
 use rand::Rng;
-use rand::distr::Uniform;
-use whatssock_lib::client::{FetchMessages, WebSocketChatroomMessageClient};
+use std::net::SocketAddr;
+use whatssock_lib::client::{
+    FetchMessages, MessageRejectionReason, WebSocketChatroomEventClient,
+    WebSocketChatroomMessageClient,
+};
 use whatssock_lib::server::WebSocketChatroomMessageServer;
 use whatssock_lib::{
-    ChatroomMessageResponse, CreateChatroomRequest, FetchChatroomResponse,
-    FetchKnownChatroomResponse, FetchKnownChatrooms, FetchMessagesResponse, FetchUnknownChatroom,
-    UserLookup, vec_cast,
+    ChatroomMessageResponse, ChatroomRole, CreateChatroomRequest, DELETED_USER_ID,
+    FetchChatroomResponse, FetchKnownChatroomResponse, FetchKnownChatrooms, FetchMessagesResponse,
+    FetchUnknownChatroom, LeaveChatroomRequest, LeaveChatroomResponse, MessageReaction,
+    StartDirectMessageRequest, UserLookup, WebSocketChatroomMessages,
 };
 
+/// The characters chatroom ids are generated from, they are safe to use in URLs.
+const CHATROOM_ID_ALPHABET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// 16 characters of the 64 character alphabet give 96 bits of randomness, so that ids cannot be guessed.
+const CHATROOM_ID_LENGTH: usize = 16;
+
+/// How many times a new chatroom id is generated if the previous one was already taken.
+const CHATROOM_ID_GENERATION_ATTEMPTS: u32 = 5;
+
+/// Joins the chatroom with its id and password.
+/// Chatrooms without a password cannot be joined this way, otherwise their id would act as a permanent secret; they can only be joined through an invite.
 pub async fn fetch_unknown_chatroom(
     State(state): State<ServerState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    authenticated_user: AuthenticatedUser,
     Json(chatroom_request): Json<FetchUnknownChatroom>,
-) -> Result<Json<FetchChatroomResponse>, StatusCode> {
+) -> Result<Json<FetchChatroomResponse>, RateLimitedError> {
+    let rate_limit_keys = [
+        RateLimitKey::Ip(remote_addr.ip()),
+        RateLimitKey::UserId(authenticated_user.user_id),
+    ];
+
+    // Reject the request early if the address or the user is locked out, so that chatroom passwords cannot be brute forced
+    state
+        .chatroom_password_rate_limiter
+        .check(&rate_limit_keys)?;
+
     // Get a db connection from the pool
     let mut pg_connection = state.pg_pool.get().map_err(|err| {
         error!(
@@ -39,63 +92,113 @@ pub async fn fetch_unknown_chatroom(
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    let chatrooms_filter = chatrooms.filter(chatroom_id.eq(chatroom_request.chatroom_id));
-
-    let mut query_result: ChatroomEntry = if let Some(password) = chatroom_request.password {
-        let password_filter = chatrooms_filter
-            .clone()
-            .filter(chatroom_password.eq(password));
-
-        password_filter
-            .select(ChatroomEntry::as_select())
-            .first(&mut pg_connection)
-            .map_err(|err| {
-                error!("An error occured while fetching chatrooms from db: {}", err);
+    // Direct messages can only be joined by their two participants
+    let query_result: ChatroomEntry = chatrooms
+        .filter(chatroom_id.eq(chatroom_request.chatroom_id))
+        .filter(schema::chatrooms::is_direct_message.eq(false))
+        .select(ChatroomEntry::as_select())
+        .first(&mut pg_connection)
+        .map_err(|err| match err {
+            // An unknown chatroom counts as a failed attempt, but the db failing does not
+            diesel::result::Error::NotFound => {
+                state
+                    .chatroom_password_rate_limiter
+                    .record_attempt(&rate_limit_keys);
 
                 StatusCode::NOT_FOUND
-            })?
-    } else {
-        chatrooms_filter
-            .clone()
-            .select(ChatroomEntry::as_select())
-            .first(&mut pg_connection)
-            .map_err(|err| {
+            }
+            err => {
                 error!("An error occured while fetching chatrooms from db: {}", err);
 
-                StatusCode::NOT_FOUND
-            })?
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    // A wrong or missing password is reported the same way as a missing chatroom, as are chatrooms without a password
+    let (Some(stored_password), Some(password)) =
+        (&query_result.chatroom_password, chatroom_request.password)
+    else {
+        state
+            .chatroom_password_rate_limiter
+            .record_attempt(&rate_limit_keys);
+
+        return Err(StatusCode::NOT_FOUND.into());
     };
 
-    // Update the participants list
-    query_result
-        .participants
-        .push(Some(chatroom_request.user_session.user_id));
+    match verify_password(&password, stored_password) {
+        PasswordVerification::Valid => (),
+        PasswordVerification::NeedsRehash => {
+            // The chatroom was created before passwords were hashed, replace the plaintext password with a hash.
+            let rehashed_password = hash_password(&password)?;
 
-    // Add the user to the chatroom's participant list
-    diesel::update(chatrooms_filter)
-        .set(participants.eq(query_result.participants.clone()))
-        .execute(&mut pg_connection)
-        .map_err(|err| {
-            error!(
-                "An error occured while updating chatroom entry from db: {}",
-                err
-            );
+            diesel::update(chatrooms.filter(schema::chatrooms::id.eq(query_result.id)))
+                .set(chatroom_password.eq(rehashed_password))
+                .execute(&mut pg_connection)
+                .map_err(|err| {
+                    error!(
+                        "An error occured while updating the chatroom's password hash: {}",
+                        err
+                    );
 
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+        }
+        PasswordVerification::Invalid => {
+            state
+                .chatroom_password_rate_limiter
+                .record_attempt(&rate_limit_keys);
 
-    Ok(Json(FetchChatroomResponse {
-        chatroom_uid: query_result.id,
-        chatroom_id: query_result.chatroom_id,
-        chatroom_name: query_result.chatroom_name,
-        participants: query_result.participants,
-        is_direct_message: query_result.is_direct_message,
-        last_message_id: query_result.last_message_id,
-    }))
+            return Err(StatusCode::NOT_FOUND.into());
+        }
+    }
+
+    // Banned users cannot join the chatroom until their ban expires
+    let is_banned = is_user_banned(
+        &mut pg_connection,
+        query_result.id,
+        authenticated_user.user_id,
+    )
+    .map_err(|err| {
+        error!(
+            "An error occured while fetching chatroom bans from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if is_banned {
+        return Err(StatusCode::FORBIDDEN.into());
+    }
+
+    // Joining a chatroom which the user is already a member of does not change anything
+    add_chatroom_member(
+        &mut pg_connection,
+        query_result.id,
+        authenticated_user.user_id,
+        ChatroomRole::Member,
+    )
+    .map_err(|err| {
+        error!(
+            "An error occured while updating chatroom entry from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Messages can be received right away if the user is online
+    subscribe_connected_user(&state, query_result.id, authenticated_user.user_id);
+
+    Ok(Json(fetch_chatroom_response(
+        &mut pg_connection,
+        query_result,
+    )?))
 }
 
 pub async fn fetch_known_chatrooms(
     State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
     Json(bulk_chatrooms_request): Json<FetchKnownChatrooms>,
 ) -> Result<Json<FetchKnownChatroomResponse>, StatusCode> {
     // Get a db connection from the pool
@@ -108,13 +211,19 @@ pub async fn fetch_known_chatrooms(
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    // Verify user session validness
-    verify_user_session(&bulk_chatrooms_request.user_session, &mut pg_connection)?;
-
     let mut verified_chatrooms_reponses: Vec<FetchChatroomResponse> = Vec::new();
 
     // Verify that the user is indeed present in the chatroom
     for chatroom_request in bulk_chatrooms_request.chatroom_uids {
+        // If the user is not a member of the chatroom, return an error
+        if !check_chatroom_membership(
+            &mut pg_connection,
+            chatroom_request,
+            authenticated_user.user_id,
+        )? {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
         let chatroom_entry = chatrooms
             .filter(schema::chatrooms::id.eq(chatroom_request))
             .get_result::<ChatroomEntry>(&mut pg_connection)
@@ -124,23 +233,8 @@ pub async fn fetch_known_chatrooms(
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
-        let is_user_present = chatroom_entry
-            .participants
-            .contains(&Some(bulk_chatrooms_request.user_session.user_id));
-
-        // If the user is not present in the participants list, return an error
-        if !is_user_present {
-            return Err(StatusCode::FORBIDDEN);
-        }
-
-        verified_chatrooms_reponses.push(FetchChatroomResponse {
-            chatroom_uid: chatroom_entry.id,
-            chatroom_id: chatroom_entry.chatroom_id,
-            chatroom_name: chatroom_entry.chatroom_name,
-            participants: chatroom_entry.participants,
-            is_direct_message: chatroom_entry.is_direct_message,
-            last_message_id: chatroom_entry.last_message_id,
-        });
+        verified_chatrooms_reponses
+            .push(fetch_chatroom_response(&mut pg_connection, chatroom_entry)?);
     }
 
     Ok(Json(FetchKnownChatroomResponse {
@@ -150,6 +244,7 @@ pub async fn fetch_known_chatrooms(
 
 pub async fn create_chatroom(
     State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
     Json(chatroom_request): Json<CreateChatroomRequest>,
 ) -> Result<Json<FetchChatroomResponse>, StatusCode> {
     // Get a db connection from the pool
@@ -162,68 +257,295 @@ pub async fn create_chatroom(
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    let generated_chatroom_id: String = rand::rng()
-        .sample_iter(&Uniform::new(char::from(32), char::from(126)).unwrap())
-        .take(10)
-        .collect();
-
-    let chatroom_entry: ChatroomEntry = diesel::insert_into(chatrooms)
-        .values(&NewChatroom {
-            chatroom_id: generated_chatroom_id,
-            chatroom_name: chatroom_request.chatroom_name,
-            chatroom_password: chatroom_request.chatroom_passw,
-            // Insert the user_id into the participants list
-            participants: vec![chatroom_request.user_session.user_id],
-            is_direct_message: false,
-            last_message_id: None,
-        })
-        .get_result(&mut pg_connection)
-        .map_err(|err| {
-            error!("An error occured while creating a new chatroom: {}", err);
-
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    let mut user_account = users
-        .filter(id.eq(chatroom_request.user_session.user_id))
+    let user_account = users
+        .filter(id.eq(authenticated_user.user_id))
         .get_result::<UserAccountEntry>(&mut pg_connection)
         .map_err(|err| {
             error!(
                 "An error occured while fetching user account with id {}: {}",
-                chatroom_request.user_session.user_id, err
+                authenticated_user.user_id, err
             );
 
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
-    user_account.chatrooms_joined.push(Some(chatroom_entry.id));
+    // Only accounts with a verified email address can create chatrooms
+    if !user_account.email_verified {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Chatrooms without a password can only be joined through an invite
+    let hashed_password = chatroom_request
+        .chatroom_passw
+        .filter(|password| !password.is_empty())
+        .map(|password| hash_password(&password))
+        .transpose()?;
+
+    let mut generation_attempts = 1;
+
+    let chatroom_entry = loop {
+        let creation_result =
+            pg_connection.transaction::<_, diesel::result::Error, _>(|pg_connection| {
+                let chatroom_entry: ChatroomEntry = diesel::insert_into(chatrooms)
+                    .values(&NewChatroom {
+                        chatroom_id: generate_chatroom_id(),
+                        chatroom_name: chatroom_request.chatroom_name.clone(),
+                        chatroom_password: hashed_password.clone(),
+                        is_direct_message: false,
+                        last_message_id: None,
+                    })
+                    .get_result(pg_connection)?;
+
+                // The user who has created the chatroom is its owner
+                add_chatroom_member(
+                    pg_connection,
+                    chatroom_entry.id,
+                    authenticated_user.user_id,
+                    ChatroomRole::Owner,
+                )?;
+
+                Ok(chatroom_entry)
+            });
+
+        match creation_result {
+            // The generated id is already taken, try again with a new one
+            Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _))
+                if generation_attempts < CHATROOM_ID_GENERATION_ATTEMPTS =>
+            {
+                generation_attempts += 1;
+            }
+            creation_result => break creation_result,
+        }
+    }
+    .map_err(|err| {
+        error!("An error occured while creating a new chatroom: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Messages can be received right away if the user is online
+    subscribe_connected_user(&state, chatroom_entry.id, authenticated_user.user_id);
+
+    Ok(Json(fetch_chatroom_response(
+        &mut pg_connection,
+        chatroom_entry,
+    )?))
+}
+
+/// Finds the direct message between the user and the requested user, or creates it if they have not talked yet.
+pub async fn start_direct_message(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(direct_message_request): Json<StartDirectMessageRequest>,
+) -> Result<Json<FetchChatroomResponse>, StatusCode> {
+    let partner_id = direct_message_request.user_id;
+
+    if partner_id == authenticated_user.user_id || partner_id == DELETED_USER_ID {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
 
-    diesel::update(users.filter(id.eq(chatroom_request.user_session.user_id)))
-        .set(chatrooms_joined.eq(user_account.chatrooms_joined))
+    let user_account = users
+        .filter(id.eq(authenticated_user.user_id))
         .get_result::<UserAccountEntry>(&mut pg_connection)
         .map_err(|err| {
             error!(
                 "An error occured while fetching user account with id {}: {}",
-                chatroom_request.user_session.user_id, err
+                authenticated_user.user_id, err
             );
 
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
-    Ok(Json(FetchChatroomResponse {
-        chatroom_uid: chatroom_entry.id,
-        chatroom_id: chatroom_entry.chatroom_id,
-        chatroom_name: chatroom_entry.chatroom_name,
-        participants: chatroom_entry.participants,
-        is_direct_message: chatroom_entry.is_direct_message,
-        last_message_id: chatroom_entry.last_message_id,
-    }))
+    // Only accounts with a verified email address can start conversations
+    if !user_account.email_verified {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // The ids are ordered, so that both participants end up in the same chatroom
+    let direct_message_id = format!(
+        "dm:{}:{}",
+        authenticated_user.user_id.min(partner_id),
+        authenticated_user.user_id.max(partner_id)
+    );
+
+    let direct_message = pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            // Make sure that the other user exists
+            users
+                .filter(id.eq(partner_id))
+                .select(id)
+                .get_result::<i32>(pg_connection)?;
+
+            let existing_chatroom = chatrooms
+                .filter(chatroom_id.eq(&direct_message_id))
+                .filter(schema::chatrooms::is_direct_message.eq(true))
+                .get_result::<ChatroomEntry>(pg_connection)
+                .optional()?;
+
+            let chatroom_entry = match existing_chatroom {
+                Some(chatroom_entry) => chatroom_entry,
+                None => diesel::insert_into(chatrooms)
+                    .values(&NewChatroom {
+                        chatroom_id: direct_message_id.clone(),
+                        // Direct messages are displayed with the name of the other participant
+                        chatroom_name: String::new(),
+                        chatroom_password: None,
+                        is_direct_message: true,
+                        last_message_id: None,
+                    })
+                    .get_result::<ChatroomEntry>(pg_connection)?,
+            };
+
+            // Participants who have left the direct message before are added back
+            for participant in [authenticated_user.user_id, partner_id] {
+                add_chatroom_member(
+                    pg_connection,
+                    chatroom_entry.id,
+                    participant,
+                    ChatroomRole::Member,
+                )?;
+            }
+
+            chatroom_response(pg_connection, chatroom_entry)
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while starting a direct message: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    // Messages can be exchanged right away if the participants are online
+    for participant in [authenticated_user.user_id, partner_id] {
+        subscribe_connected_user(&state, direct_message.chatroom_uid, participant);
+    }
+
+    Ok(Json(direct_message))
+}
+
+pub async fn leave_chatroom(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(leave_request): Json<LeaveChatroomRequest>,
+) -> Result<Json<LeaveChatroomResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let new_owner = pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            // Users can only leave the chatrooms they are a member of
+            let role = lookup_member_role(
+                pg_connection,
+                leave_request.chatroom_uid,
+                authenticated_user.user_id,
+            )?
+            .ok_or(diesel::result::Error::NotFound)?;
+
+            remove_chatroom_member(
+                pg_connection,
+                leave_request.chatroom_uid,
+                authenticated_user.user_id,
+            )?;
+
+            // The chatroom is handed to another member, so that it is not left without an owner
+            if role == ChatroomRole::Owner {
+                hand_over_ownership(pg_connection, leave_request.chatroom_uid)
+            } else {
+                Ok(None)
+            }
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while leaving a chatroom: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    let participant_left = WebSocketChatroomEventClient::ParticipantLeft {
+        chatroom_uid: leave_request.chatroom_uid,
+        user_id: authenticated_user.user_id,
+    };
+
+    // The user's own connection is notified directly, so that it can remove the chatroom before it is unsubscribed
+    send_chatroom_event_to_subscriber(
+        &state,
+        leave_request.chatroom_uid,
+        authenticated_user.user_id,
+        &participant_left,
+    );
+
+    // The handler is closed if the user was its last subscriber, there is no one left to notify in that case
+    unsubscribe_user_from_chatroom(
+        &state,
+        leave_request.chatroom_uid,
+        authenticated_user.user_id,
+    );
+
+    // Notify the remaining participants
+    broadcast_chatroom_event(&state, leave_request.chatroom_uid, &participant_left);
+
+    if let Some(new_owner) = new_owner {
+        broadcast_chatroom_event(
+            &state,
+            leave_request.chatroom_uid,
+            &WebSocketChatroomEventClient::MemberRoleChanged {
+                chatroom_uid: leave_request.chatroom_uid,
+                user_id: new_owner,
+                role: ChatroomRole::Owner,
+            },
+        );
+    }
+
+    Ok(Json(LeaveChatroomResponse {}))
+}
+
+/// The reasons an incoming chatroom message may not be relayed.
+pub enum IncomingMessageError {
+    /// The message was refused, the sender should be notified but can stay connected.
+    Rejected(MessageRejectionReason),
+    /// The message could not be processed.
+    Failed(StatusCode),
+}
+
+impl From<StatusCode> for IncomingMessageError {
+    fn from(status_code: StatusCode) -> Self {
+        Self::Failed(status_code)
+    }
 }
 
 pub async fn handle_incoming_chatroom_message(
     State(state): &State<ServerState>,
     chatroom_request: WebSocketChatroomMessageServer,
-) -> Result<WebSocketChatroomMessageClient, StatusCode> {
+) -> Result<WebSocketChatroomMessageClient, IncomingMessageError> {
+    // Tombstones are only created by the server when a message is deleted
+    if matches!(
+        chatroom_request.message,
+        WebSocketChatroomMessages::Deleted { .. }
+    ) {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+
     // Get a db connection from the pool
     let mut pg_connection = state.pg_pool.get().map_err(|err| {
         error!(
@@ -237,35 +559,145 @@ pub async fn handle_incoming_chatroom_message(
     // Verify user session
     verify_user_session(&chatroom_request.message_owner_session, &mut pg_connection)?;
 
-    let inserted_message: MessageEntry = insert_into(messages)
-        .values(NewMessage {
-            parent_chatroom_id: chatroom_request.sent_to,
-            owner_user_id: chatroom_request.message_owner_session.user_id,
-            send_date: Utc::now().naive_utc(),
-            replying_to_msg: chatroom_request.replying_to_msg_id,
-            raw_message: rmp_serde::to_vec(&chatroom_request.message).unwrap(),
+    // Read-only members, muted members and users outside of the chatroom cannot send messages to it
+    if let Some(rejection_reason) = lookup_sender_rejection(
+        &mut pg_connection,
+        chatroom_request.sent_to,
+        chatroom_request.message_owner_session.user_id,
+    )? {
+        return Err(IncomingMessageError::Rejected(rejection_reason));
+    }
+
+    // Replies can only be sent to messages of the same chatroom
+    if let Some(replying_to_msg_id) = chatroom_request.replying_to_msg_id {
+        let replied_message = messages
+            .filter(schema::messages::id.eq(replying_to_msg_id))
+            .select((
+                parent_chatroom_id,
+                schema::messages::deleted_at.is_not_null(),
+            ))
+            .get_result::<(i32, bool)>(&mut pg_connection)
+            .optional()
+            .map_err(|err| {
+                error!("An error occured while fetching message from db: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        let is_valid_reply = replied_message.is_some_and(|(replied_chatroom_uid, is_deleted)| {
+            replied_chatroom_uid == chatroom_request.sent_to && !is_deleted
+        });
+
+        if !is_valid_reply {
+            return Err(IncomingMessageError::Rejected(
+                MessageRejectionReason::InvalidReply,
+            ));
+        }
+    }
+
+    // Threads can only be started from messages of the main timeline
+    if let Some(thread_root_id) = chatroom_request.thread_root_id {
+        let thread_root = messages
+            .filter(schema::messages::id.eq(thread_root_id))
+            .select((
+                parent_chatroom_id,
+                schema::messages::deleted_at.is_not_null(),
+                schema::messages::thread_root_id.is_not_null(),
+            ))
+            .get_result::<(i32, bool, bool)>(&mut pg_connection)
+            .optional()
+            .map_err(|err| {
+                error!("An error occured while fetching message from db: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        let is_valid_thread =
+            thread_root.is_some_and(|(root_chatroom_uid, is_deleted, is_thread_reply)| {
+                root_chatroom_uid == chatroom_request.sent_to && !is_deleted && !is_thread_reply
+            });
+
+        if !is_valid_thread {
+            return Err(IncomingMessageError::Rejected(
+                MessageRejectionReason::InvalidThread,
+            ));
+        }
+    }
+
+    let (inserted_message, thread_summary) = pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            let inserted_message: MessageEntry = insert_into(messages)
+                .values(NewMessage {
+                    parent_chatroom_id: chatroom_request.sent_to,
+                    owner_user_id: chatroom_request.message_owner_session.user_id,
+                    send_date: Utc::now().naive_utc(),
+                    replying_to_msg: chatroom_request.replying_to_msg_id,
+                    raw_message: rmp_serde::to_vec(&chatroom_request.message).unwrap(),
+                    thread_root_id: chatroom_request.thread_root_id,
+                })
+                .get_result(pg_connection)?;
+
+            // The summary of the thread is kept on its root
+            let thread_summary = match inserted_message.thread_root_id {
+                Some(thread_root_id) => {
+                    let (reply_count, last_reply_at) =
+                        diesel::update(messages.filter(schema::messages::id.eq(thread_root_id)))
+                            .set((
+                                schema::messages::thread_reply_count
+                                    .eq(schema::messages::thread_reply_count + 1),
+                                schema::messages::thread_last_reply_at
+                                    .eq(inserted_message.send_date),
+                            ))
+                            .returning((
+                                schema::messages::thread_reply_count,
+                                schema::messages::thread_last_reply_at,
+                            ))
+                            .get_result::<(i32, Option<NaiveDateTime>)>(pg_connection)?;
+
+                    Some((thread_root_id, reply_count, last_reply_at))
+                }
+                None => None,
+            };
+
+            Ok((inserted_message, thread_summary))
         })
-        .get_result(&mut pg_connection)
         .map_err(|err| {
             error!("An error occured while processing message: {}", err);
 
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
-    // Update chatroom last message
-    update_chatroom_last_msg(
-        inserted_message.id,
-        chatroom_request.sent_to,
-        &mut pg_connection,
-    )
-    .map_err(|err| {
-        error!(
-            "An error occured while updating chatroom information in db: {}",
-            err
-        );
+    // Thread replies do not show up as the last message of the chatroom
+    match thread_summary {
+        Some((thread_root_id, reply_count, last_reply_at)) => {
+            broadcast_chatroom_event(
+                state,
+                chatroom_request.sent_to,
+                &WebSocketChatroomEventClient::ThreadUpdated {
+                    chatroom_uid: chatroom_request.sent_to,
+                    thread_root_id,
+                    reply_count: reply_count as u32,
+                    last_reply_at,
+                },
+            );
+        }
+        None => {
+            // Update chatroom last message
+            update_chatroom_last_msg(
+                inserted_message.id,
+                chatroom_request.sent_to,
+                &mut pg_connection,
+            )
+            .map_err(|err| {
+                error!(
+                    "An error occured while updating chatroom information in db: {}",
+                    err
+                );
 
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        }
+    }
 
     Ok(WebSocketChatroomMessageClient {
         message_id: inserted_message.id,
@@ -274,6 +706,11 @@ pub async fn handle_incoming_chatroom_message(
         sent_to: chatroom_request.sent_to,
         message: chatroom_request.message,
         date_issued: chatroom_request.date_issued,
+        edited_at: None,
+        reactions: Vec::new(),
+        thread_root_id: inserted_message.thread_root_id,
+        thread_reply_count: 0,
+        thread_last_reply_at: None,
     })
 }
 
@@ -294,6 +731,11 @@ pub async fn fetch_user(
         .parse::<i32>()
         .map_err(|_| StatusCode::METHOD_NOT_ALLOWED)?;
 
+    // The owner of the messages which have been kept after their author has deleted their account
+    if uuid == DELETED_USER_ID {
+        return Ok(Json(deleted_user_profile()));
+    }
+
     let query = users
         .filter(id.eq(uuid))
         .first::<UserAccountEntry>(&mut pg_connection)
@@ -306,13 +748,12 @@ pub async fn fetch_user(
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
-    Ok(Json(UserLookup {
-        username: query.username,
-    }))
+    Ok(Json(lookup_user_profile(&mut pg_connection, query)?))
 }
 
 pub async fn fetch_messages(
     State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
     Json(fetch_messages_request): Json<FetchMessages>,
 ) -> Result<Json<FetchMessagesResponse>, StatusCode> {
     let mut pg_connection = state.pg_pool.get().map_err(|err| {
@@ -324,21 +765,6 @@ pub async fn fetch_messages(
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    verify_user_session(&fetch_messages_request.user_session, &mut pg_connection)?;
-
-    // Get the user's joined chatrooms to see that they have the permission to request the messages
-    let user_account = users
-        .filter(id.eq(fetch_messages_request.user_session.user_id))
-        .first::<UserAccountEntry>(&mut pg_connection)
-        .map_err(|err| {
-            error!(
-                "An error occured while fetching user information from db: {}",
-                err
-            );
-
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
     let requested_messages = match fetch_messages_request.message_request {
         whatssock_lib::MessageFetchType::NextFromId(bulk_chatroom_msg_request) => {
             // Check for user request size
@@ -351,15 +777,13 @@ pub async fn fetch_messages(
                 return Err(StatusCode::PAYLOAD_TOO_LARGE);
             }
 
-            // Check if the user's session exists
-            if !user_account
-                .chatrooms_joined
-                .contains(&Some(bulk_chatroom_msg_request.chatroom_uid))
-            {
-                error!(
-                    "User ID not found in db: {}",
-                    fetch_messages_request.user_session.user_id
-                );
+            // Check if the user is a member of the chatroom
+            if !check_chatroom_membership(
+                &mut pg_connection,
+                bulk_chatroom_msg_request.chatroom_uid,
+                authenticated_user.user_id,
+            )? {
+                error!("User ID not found in db: {}", authenticated_user.user_id);
 
                 return Err(StatusCode::UNAUTHORIZED);
             }
@@ -368,6 +792,7 @@ pub async fn fetch_messages(
             let bulk_msg_request = messages
                 .filter(schema::messages::id.lt(bulk_chatroom_msg_request.offset_id)) // only rows after the given id
                 .filter(parent_chatroom_id.eq(bulk_chatroom_msg_request.chatroom_uid)) // match attribute
+                .filter(schema::messages::thread_root_id.is_null()) // thread replies are fetched with their thread
                 .order(schema::messages::id.asc()) // make sure we get the "next" ones
                 .limit(bulk_chatroom_msg_request.count.into())
                 .load::<MessageEntry>(&mut pg_connection)
@@ -381,11 +806,7 @@ pub async fn fetch_messages(
                 dbg!(req.id);
             }
 
-            // Casting magic
-            // If this crashes please check function implmentation in the lib
-            // I love unsafe code bleeeeeeh
-            // Amen
-            unsafe { vec_cast(bulk_msg_request) }
+            bulk_msg_request
         }
         whatssock_lib::MessageFetchType::SingluarFromId(message_id) => {
             // Fetch the message
@@ -399,27 +820,18 @@ pub async fn fetch_messages(
                     StatusCode::INTERNAL_SERVER_ERROR
                 })?;
 
-            // Check if the user's session exists
-            if !user_account
-                .chatrooms_joined
-                .contains(&Some(message.parent_chatroom_id))
-            {
-                error!(
-                    "User ID not found in db: {}",
-                    fetch_messages_request.user_session.user_id
-                );
+            // Check if the user is a member of the chatroom
+            if !check_chatroom_membership(
+                &mut pg_connection,
+                message.parent_chatroom_id,
+                authenticated_user.user_id,
+            )? {
+                error!("User ID not found in db: {}", authenticated_user.user_id);
 
                 return Err(StatusCode::UNAUTHORIZED);
             }
 
-            vec![ChatroomMessageResponse {
-                message_id: message.id,
-                sent_to: message.parent_chatroom_id,
-                message_owner_id: message.owner_user_id,
-                replying_to_msg_id: message.replying_to_msg,
-                date_issued: message.send_date,
-                raw_message: message.raw_message,
-            }]
+            vec![message]
         }
         whatssock_lib::MessageFetchType::NextFromLatest(bulk_chatroom_msg_request) => {
             // Check for user request size
@@ -432,8 +844,20 @@ pub async fn fetch_messages(
                 return Err(StatusCode::PAYLOAD_TOO_LARGE);
             }
 
-            let bulk_msg_request = messages
+            // Check if the user is a member of the chatroom
+            if !check_chatroom_membership(
+                &mut pg_connection,
+                bulk_chatroom_msg_request.chatroom_uid,
+                authenticated_user.user_id,
+            )? {
+                error!("User ID not found in db: {}", authenticated_user.user_id);
+
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+
+            messages
                 .filter(parent_chatroom_id.eq(bulk_chatroom_msg_request.chatroom_uid)) // match attribute
+                .filter(schema::messages::thread_root_id.is_null()) // thread replies are fetched with their thread
                 .order(schema::messages::id.desc()) // make sure we get the "next" ones
                 .limit(bulk_chatroom_msg_request.count.into())
                 .load::<MessageEntry>(&mut pg_connection)
@@ -441,14 +865,153 @@ pub async fn fetch_messages(
                     error!("An error occured while fetching messages from db: {}", err);
 
                     StatusCode::INTERNAL_SERVER_ERROR
+                })?
+        }
+        whatssock_lib::MessageFetchType::ThreadReplies(thread_replies_request) => {
+            // Check for user request size
+            if thread_replies_request.count == 0 || thread_replies_request.count > 255 {
+                warn!(
+                    "The user has tried to request: `{}` amount of messages, which is invalid.",
+                    thread_replies_request.count
+                );
+
+                return Err(StatusCode::PAYLOAD_TOO_LARGE);
+            }
+
+            let thread_chatroom_uid = messages
+                .filter(schema::messages::id.eq(thread_replies_request.thread_root_id))
+                .select(parent_chatroom_id)
+                .get_result::<i32>(&mut pg_connection)
+                .map_err(|err| match err {
+                    diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+                    err => {
+                        error!("An error occured while fetching message from db: {}", err);
+
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    }
                 })?;
 
-            // Amen
-            unsafe { vec_cast(bulk_msg_request) }
+            // Check if the user is a member of the chatroom
+            if !check_chatroom_membership(
+                &mut pg_connection,
+                thread_chatroom_uid,
+                authenticated_user.user_id,
+            )? {
+                error!("User ID not found in db: {}", authenticated_user.user_id);
+
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+
+            let mut thread_replies_query = messages
+                .filter(schema::messages::thread_root_id.eq(thread_replies_request.thread_root_id))
+                .into_boxed();
+
+            if let Some(offset_id) = thread_replies_request.offset_id {
+                thread_replies_query =
+                    thread_replies_query.filter(schema::messages::id.lt(offset_id));
+            }
+
+            thread_replies_query
+                .order(schema::messages::id.desc()) // the latest replies come first
+                .limit(thread_replies_request.count.into())
+                .load::<MessageEntry>(&mut pg_connection)
+                .map_err(|err| {
+                    error!("An error occured while fetching messages from db: {}", err);
+
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?
         }
     };
 
+    let message_ids = requested_messages
+        .iter()
+        .map(|message| message.id)
+        .collect::<Vec<i32>>();
+
+    let mut reactions =
+        load_message_reactions(&mut pg_connection, &message_ids, authenticated_user.user_id)
+            .map_err(|err| {
+                error!(
+                    "An error occured while fetching message reactions from db: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
     Ok(Json(FetchMessagesResponse {
-        messages: requested_messages,
+        messages: requested_messages
+            .into_iter()
+            .map(|message| {
+                let message_reactions = reactions.remove(&message.id).unwrap_or_default();
+
+                chatroom_message_response(message, message_reactions)
+            })
+            .collect(),
     }))
 }
+
+/// Creates the response of the message, the reactions are loaded with [`load_message_reactions`].
+pub fn chatroom_message_response(
+    message: MessageEntry,
+    reactions: Vec<MessageReaction>,
+) -> ChatroomMessageResponse {
+    ChatroomMessageResponse {
+        message_id: message.id,
+        message_owner_id: message.owner_user_id,
+        replying_to_msg_id: message.replying_to_msg,
+        sent_to: message.parent_chatroom_id,
+        raw_message: message.raw_message,
+        date_issued: message.send_date,
+        edited_at: message.edited_at,
+        deleted_at: message.deleted_at,
+        reactions,
+        thread_root_id: message.thread_root_id,
+        thread_reply_count: message.thread_reply_count as u32,
+        thread_last_reply_at: message.thread_last_reply_at,
+    }
+}
+
+/// Checks whether the user is a member of the chatroom, database errors are turned into a [`StatusCode`].
+fn check_chatroom_membership(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+) -> Result<bool, StatusCode> {
+    is_chatroom_member(pg_connection, chatroom_uid, user_uid).map_err(|err| {
+        error!(
+            "An error occured while fetching chatroom members from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Generates a URL-safe chatroom id from [`CHATROOM_ID_ALPHABET`].
+fn generate_chatroom_id() -> String {
+    let mut rng = rand::rng();
+
+    (0..CHATROOM_ID_LENGTH)
+        .map(|_| CHATROOM_ID_ALPHABET[rng.random_range(0..CHATROOM_ID_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Creates the response of the chatroom, database errors are turned into a [`StatusCode`].
+pub fn fetch_chatroom_response(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_entry: ChatroomEntry,
+) -> Result<FetchChatroomResponse, StatusCode> {
+    chatroom_response(pg_connection, chatroom_entry).map_err(|err| {
+        error!(
+            "An error occured while fetching chatroom members from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{TimeDelta, Utc};
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, delete, update};
+use log::error;
+use whatssock_lib::{
+    client::VerifyEmailRequest,
+    decode_secure_key, encode_secure_key,
+    server::{ResendVerificationEmailResponse, VerifyEmailResponse},
+};
+
+use crate::{
+    ServerState,
+    api::{
+        authentication::AuthenticatedUser,
+        user_account_control::{generate_random_secure_key, hash_secure_key},
+    },
+    mail::{Mail, Mailer, send_in_background},
+    models::{EmailVerificationTokenEntry, NewEmailVerificationToken, UserAccountEntry},
+    schema::{
+        email_verification_tokens::{self, dsl::email_verification_tokens as verification_tokens},
+        users::{self, dsl::users as user_accounts},
+    },
+};
+
+/// How long a verification token can be used after it has been sent.
+pub const EMAIL_VERIFICATION_TOKEN_LIFETIME: TimeDelta = TimeDelta::hours(24);
+
+/// A new verification email can only be requested once this much time has passed since the last one.
+pub const EMAIL_VERIFICATION_RESEND_COOLDOWN: TimeDelta = TimeDelta::minutes(1);
+
+pub async fn verify_email(
+    State(state): State<ServerState>,
+    Json(verification_request): Json<VerifyEmailRequest>,
+) -> Result<Json<VerifyEmailResponse>, StatusCode> {
+    let token =
+        decode_secure_key(verification_request.token.trim()).ok_or(StatusCode::NOT_FOUND)?;
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let now = Utc::now().naive_utc();
+
+    // The token is deleted when it is used, so that it can only be used once
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            let token_entry = delete(
+                verification_tokens
+                    .filter(email_verification_tokens::token_hash.eq(hash_secure_key(&token)))
+                    .filter(email_verification_tokens::expires_at.gt(now)),
+            )
+            .get_result::<EmailVerificationTokenEntry>(pg_connection)?;
+
+            update(user_accounts.filter(users::id.eq(token_entry.user_id)))
+                .set(users::email_verified.eq(true))
+                .execute(pg_connection)?;
+
+            Ok(())
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while verifying an email address: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    Ok(Json(VerifyEmailResponse {}))
+}
+
+pub async fn resend_verification_email(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+) -> Result<Json<ResendVerificationEmailResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = user_accounts
+        .filter(users::id.eq(authenticated_user.user_id))
+        .get_result::<UserAccountEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching user account with id {}: {}",
+                authenticated_user.user_id, err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if user_account.email_verified {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    // Check when the last verification email was sent, so that the endpoint cannot be used to flood the user's inbox
+    let last_token_created_at = verification_tokens
+        .filter(email_verification_tokens::user_id.eq(user_account.id))
+        .select(diesel::dsl::max(email_verification_tokens::created_at))
+        .get_result::<Option<chrono::NaiveDateTime>>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the user's verification tokens: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if last_token_created_at.is_some_and(|created_at| {
+        created_at + EMAIL_VERIFICATION_RESEND_COOLDOWN > Utc::now().naive_utc()
+    }) {
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    send_verification_email(state.mailer.clone(), &mut pg_connection, &user_account)?;
+
+    Ok(Json(ResendVerificationEmailResponse {}))
+}
+
+/// Issues a new verification token for the user and emails it to the user's address.
+/// Every token issued to the user earlier is invalidated.
+pub fn send_verification_email(
+    mailer: Arc<dyn Mailer>,
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    user_account: &UserAccountEntry,
+) -> Result<(), StatusCode> {
+    let token = generate_random_secure_key();
+
+    // The creation time is compared against the server's UTC time for the resend cooldown, so it is not left to the db's default
+    let now = Utc::now().naive_utc();
+
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            delete(
+                verification_tokens.filter(email_verification_tokens::user_id.eq(user_account.id)),
+            )
+            .execute(pg_connection)?;
+
+            diesel::insert_into(verification_tokens)
+                .values(&NewEmailVerificationToken {
+                    user_id: user_account.id,
+                    token_hash: hash_secure_key(&token),
+                    created_at: now,
+                    expires_at: now + EMAIL_VERIFICATION_TOKEN_LIFETIME,
+                })
+                .execute(pg_connection)?;
+
+            Ok(())
+        })
+        .map_err(|err| {
+            error!(
+                "An error occured while storing the user's verification token: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let encoded_token = encode_secure_key(&token);
+
+    send_in_background(
+        mailer,
+        Mail {
+            to: user_account.email.clone(),
+            subject: String::from("Verify your Whatssock email address"),
+            body: format!(
+                "Hi {}!\n\nYour email verification code is:\n\n{encoded_token}\n\nEnter it in Whatssock to verify your email address. The code expires in {} hours.",
+                user_account.username,
+                EMAIL_VERIFICATION_TOKEN_LIFETIME.num_hours()
+            ),
+        },
+    );
+
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{TimeDelta, Utc};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl,
+    RunQueryDsl, SelectableHelper,
+};
+use log::error;
+use whatssock_lib::{
+    ChatroomInvite, ChatroomPermission, ChatroomRole, CreateChatroomInviteRequest,
+    CreateChatroomInviteResponse, FetchChatroomResponse, JoinByInviteRequest,
+    ListChatroomInvitesRequest, ListChatroomInvitesResponse, RevokeChatroomInviteRequest,
+    RevokeChatroomInviteResponse, decode_secure_key, encode_secure_key,
+};
+
+use crate::{
+    ServerState,
+    api::{
+        authentication::AuthenticatedUser,
+        memberships::{
+            add_chatroom_member, chatroom_response, is_chatroom_member, require_chatroom_permission,
+        },
+        moderation::is_user_banned,
+        user_account_control::{generate_random_secure_key, hash_secure_key},
+        websocket::subscribe_connected_user,
+    },
+    models::{ChatroomEntry, ChatroomInviteEntry, NewChatroomInvite},
+    schema::{
+        chatroom_invites::{self, dsl::chatroom_invites as invites},
+        chatrooms::{self, dsl::chatrooms as chatroom_entries},
+    },
+};
+
+pub async fn create_chatroom_invite(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(invite_request): Json<CreateChatroomInviteRequest>,
+) -> Result<Json<CreateChatroomInviteResponse>, StatusCode> {
+    // An invite which can never be redeemed is most likely a mistake
+    if invite_request.max_uses == Some(0) || invite_request.duration_secs == Some(0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let max_uses = invite_request
+        .max_uses
+        .map(i32::try_from)
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        invite_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::ManageInvites,
+    )?;
+
+    let invite_code = generate_random_secure_key();
+
+    let invite_entry = diesel::insert_into(invites)
+        .values(&NewChatroomInvite {
+            chatroom_id: invite_request.chatroom_uid,
+            created_by: authenticated_user.user_id,
+            code_hash: hash_secure_key(&invite_code),
+            max_uses,
+            expires_at: invite_request.duration_secs.map(|duration_secs| {
+                Utc::now().naive_utc() + TimeDelta::seconds(duration_secs.into())
+            }),
+        })
+        .returning(ChatroomInviteEntry::as_returning())
+        .get_result(&mut pg_connection)
+        .map_err(|err| {
+            error!("An error occured while creating a chatroom invite: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(CreateChatroomInviteResponse {
+        invite: chatroom_invite(invite_entry),
+        invite_code: encode_secure_key(&invite_code),
+    }))
+}
+
+pub async fn list_chatroom_invites(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(invites_request): Json<ListChatroomInvitesRequest>,
+) -> Result<Json<ListChatroomInvitesResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        invites_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::ManageInvites,
+    )?;
+
+    let now = Utc::now().naive_utc();
+
+    // Expired and used up invites are not listed, as they cannot be redeemed anymore
+    let invite_entries = invites
+        .filter(chatroom_invites::chatroom_id.eq(invites_request.chatroom_uid))
+        .filter(
+            chatroom_invites::expires_at
+                .is_null()
+                .or(chatroom_invites::expires_at.gt(now)),
+        )
+        .filter(
+            chatroom_invites::max_uses
+                .is_null()
+                .or(chatroom_invites::uses.lt(chatroom_invites::max_uses.assume_not_null())),
+        )
+        .order(chatroom_invites::created_at.desc())
+        .select(ChatroomInviteEntry::as_select())
+        .load::<ChatroomInviteEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!("An error occured while fetching chatroom invites: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(ListChatroomInvitesResponse {
+        invites: invite_entries.into_iter().map(chatroom_invite).collect(),
+    }))
+}
+
+pub async fn revoke_chatroom_invite(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(revoke_request): Json<RevokeChatroomInviteRequest>,
+) -> Result<Json<RevokeChatroomInviteResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        revoke_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::ManageInvites,
+    )?;
+
+    let revoked_invites = diesel::delete(
+        invites
+            .filter(chatroom_invites::id.eq(revoke_request.invite_id))
+            .filter(chatroom_invites::chatroom_id.eq(revoke_request.chatroom_uid)),
+    )
+    .execute(&mut pg_connection)
+    .map_err(|err| {
+        error!("An error occured while revoking a chatroom invite: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if revoked_invites == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Json(RevokeChatroomInviteResponse {}))
+}
+
+pub async fn join_by_invite(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(join_request): Json<JoinByInviteRequest>,
+) -> Result<Json<FetchChatroomResponse>, StatusCode> {
+    // A malformed code cannot belong to any invite
+    let invite_code =
+        decode_secure_key(join_request.invite_code.trim()).ok_or(StatusCode::NOT_FOUND)?;
+
+    let code_hash = hash_secure_key(&invite_code);
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let chatroom_entry = pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            let invite_entry = invites
+                .filter(chatroom_invites::code_hash.eq(&code_hash))
+                .select(ChatroomInviteEntry::as_select())
+                .first(pg_connection)?;
+
+            let chatroom_entry = chatroom_entries
+                .filter(chatrooms::id.eq(invite_entry.chatroom_id))
+                .select(ChatroomEntry::as_select())
+                .first(pg_connection)?;
+
+            // Banned users cannot join with an invite either, this is reported after the transaction
+            if is_user_banned(pg_connection, chatroom_entry.id, authenticated_user.user_id)? {
+                return Ok(None);
+            }
+
+            // Members redeeming an invite again do not use it up
+            if is_chatroom_member(pg_connection, chatroom_entry.id, authenticated_user.user_id)? {
+                return Ok(Some(chatroom_entry));
+            }
+
+            let now = Utc::now().naive_utc();
+
+            // The use is only counted if the invite is still redeemable, which also guards against concurrent redemptions
+            diesel::update(
+                invites
+                    .filter(chatroom_invites::id.eq(invite_entry.id))
+                    .filter(
+                        chatroom_invites::expires_at
+                            .is_null()
+                            .or(chatroom_invites::expires_at.gt(now)),
+                    )
+                    .filter(chatroom_invites::max_uses.is_null().or(
+                        chatroom_invites::uses.lt(chatroom_invites::max_uses.assume_not_null()),
+                    )),
+            )
+            .set(chatroom_invites::uses.eq(chatroom_invites::uses + 1))
+            .returning(chatroom_invites::id)
+            .get_result::<i32>(pg_connection)?;
+
+            add_chatroom_member(
+                pg_connection,
+                chatroom_entry.id,
+                authenticated_user.user_id,
+                ChatroomRole::Member,
+            )?;
+
+            Ok(Some(chatroom_entry))
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!(
+                    "An error occured while redeeming a chatroom invite: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?
+        .ok_or(StatusCode::FORBIDDEN)?;
+
+    // Messages can be received right away if the user is online
+    subscribe_connected_user(&state, chatroom_entry.id, authenticated_user.user_id);
+
+    let chatroom_response =
+        chatroom_response(&mut pg_connection, chatroom_entry).map_err(|err| {
+            error!(
+                "An error occured while fetching chatroom members from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(chatroom_response))
+}
+
+fn chatroom_invite(invite_entry: ChatroomInviteEntry) -> ChatroomInvite {
+    ChatroomInvite {
+        invite_id: invite_entry.id,
+        created_by: invite_entry.created_by,
+        uses: invite_entry.uses as u32,
+        max_uses: invite_entry.max_uses.map(|max_uses| max_uses as u32),
+        created_at: invite_entry.created_at,
+        expires_at: invite_entry.expires_at,
+    }
+}
@@ -0,0 +1,419 @@
+use std::cmp::Reverse;
+
+use axum::{Json, extract::State, http::StatusCode};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    SelectableHelper, dsl::exists, select,
+};
+use log::error;
+use whatssock_lib::{
+    ChatroomMember, ChatroomPermission, ChatroomRole, FetchChatroomResponse,
+    TransferOwnershipRequest, TransferOwnershipResponse, UpdateMemberRoleRequest,
+    UpdateMemberRoleResponse, client::WebSocketChatroomEventClient,
+};
+
+use crate::{
+    ServerState,
+    api::{authentication::AuthenticatedUser, websocket::broadcast_chatroom_event},
+    models::{ChatroomEntry, ChatroomMemberEntry, NewChatroomMember},
+    schema::{
+        chatroom_icons,
+        chatroom_members::{self, dsl::chatroom_members as memberships},
+    },
+};
+
+pub async fn update_member_role(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(role_request): Json<UpdateMemberRoleRequest>,
+) -> Result<Json<UpdateMemberRoleResponse>, StatusCode> {
+    // Users cannot change their own role, and ownership can only be transferred
+    if role_request.user_id == authenticated_user.user_id
+        || role_request.role == ChatroomRole::Owner
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let own_role = require_chatroom_permission(
+        &mut pg_connection,
+        role_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::ManageRoles,
+    )?;
+
+    let member_role = lookup_member_role(
+        &mut pg_connection,
+        role_request.chatroom_uid,
+        role_request.user_id,
+    )
+    .map_err(|err| {
+        error!(
+            "An error occured while fetching chatroom members from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Only the members ranked below the user can be managed, and they cannot be ranked as high as the user
+    if member_role >= own_role || role_request.role >= own_role {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // The role is only updated if it has not been changed since it was checked
+    let updated_rows = diesel::update(
+        memberships
+            .filter(chatroom_members::chatroom_id.eq(role_request.chatroom_uid))
+            .filter(chatroom_members::user_id.eq(role_request.user_id))
+            .filter(chatroom_members::role.eq(member_role.as_str())),
+    )
+    .set(chatroom_members::role.eq(role_request.role.as_str()))
+    .execute(&mut pg_connection)
+    .map_err(|err| {
+        error!("An error occured while updating a member's role: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if updated_rows == 0 {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    broadcast_chatroom_event(
+        &state,
+        role_request.chatroom_uid,
+        &WebSocketChatroomEventClient::MemberRoleChanged {
+            chatroom_uid: role_request.chatroom_uid,
+            user_id: role_request.user_id,
+            role: role_request.role,
+        },
+    );
+
+    Ok(Json(UpdateMemberRoleResponse {}))
+}
+
+pub async fn transfer_chatroom_ownership(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(transfer_request): Json<TransferOwnershipRequest>,
+) -> Result<Json<TransferOwnershipResponse>, StatusCode> {
+    if transfer_request.user_id == authenticated_user.user_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        transfer_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::TransferOwnership,
+    )?;
+
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            // Ownership can only be transferred to another member of the chatroom
+            lookup_member_role(
+                pg_connection,
+                transfer_request.chatroom_uid,
+                transfer_request.user_id,
+            )?
+            .ok_or(diesel::result::Error::NotFound)?;
+
+            // The previous owner is demoted first, as a chatroom can only have a single owner
+            // The demotion only succeeds if the user is still the owner, so that concurrent transfers cannot both go through
+            let demoted_rows = diesel::update(
+                memberships
+                    .filter(chatroom_members::chatroom_id.eq(transfer_request.chatroom_uid))
+                    .filter(chatroom_members::user_id.eq(authenticated_user.user_id))
+                    .filter(chatroom_members::role.eq(ChatroomRole::Owner.as_str())),
+            )
+            .set(chatroom_members::role.eq(ChatroomRole::Admin.as_str()))
+            .execute(pg_connection)?;
+
+            if demoted_rows != 1 {
+                return Err(diesel::result::Error::RollbackTransaction);
+            }
+
+            set_member_role(
+                pg_connection,
+                transfer_request.chatroom_uid,
+                transfer_request.user_id,
+                ChatroomRole::Owner,
+            )?;
+
+            Ok(())
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            // The user is no longer the owner of the chatroom
+            diesel::result::Error::RollbackTransaction => StatusCode::CONFLICT,
+            err => {
+                error!(
+                    "An error occured while transferring the ownership of a chatroom: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    for (user_id, role) in [
+        (authenticated_user.user_id, ChatroomRole::Admin),
+        (transfer_request.user_id, ChatroomRole::Owner),
+    ] {
+        broadcast_chatroom_event(
+            &state,
+            transfer_request.chatroom_uid,
+            &WebSocketChatroomEventClient::MemberRoleChanged {
+                chatroom_uid: transfer_request.chatroom_uid,
+                user_id,
+                role,
+            },
+        );
+    }
+
+    Ok(Json(TransferOwnershipResponse {}))
+}
+
+/// Checks that the user is a member of the chatroom and that their role has the permission, returns the user's role.
+pub fn require_chatroom_permission(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+    permission: ChatroomPermission,
+) -> Result<ChatroomRole, StatusCode> {
+    let role = lookup_member_role(pg_connection, chatroom_uid, user_uid).map_err(|err| {
+        error!(
+            "An error occured while fetching chatroom members from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match role {
+        Some(role) if role.has_permission(permission) => Ok(role),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// Adds the user to the chatroom, this is a no-op if they are already a member.
+pub fn add_chatroom_member(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+    role: ChatroomRole,
+) -> QueryResult<usize> {
+    diesel::insert_into(memberships)
+        .values(&NewChatroomMember {
+            chatroom_id: chatroom_uid,
+            user_id: user_uid,
+            role: role.as_str().to_string(),
+        })
+        .on_conflict_do_nothing()
+        .execute(pg_connection)
+}
+
+/// Removes the user from the chatroom, returns how many memberships have been removed.
+pub fn remove_chatroom_member(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+) -> QueryResult<usize> {
+    diesel::delete(
+        memberships
+            .filter(chatroom_members::chatroom_id.eq(chatroom_uid))
+            .filter(chatroom_members::user_id.eq(user_uid)),
+    )
+    .execute(pg_connection)
+}
+
+pub fn is_chatroom_member(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+) -> QueryResult<bool> {
+    select(exists(
+        memberships
+            .filter(chatroom_members::chatroom_id.eq(chatroom_uid))
+            .filter(chatroom_members::user_id.eq(user_uid)),
+    ))
+    .get_result(pg_connection)
+}
+
+/// Returns the role of the user in the chatroom, this is `None` if they are not a member.
+pub fn lookup_member_role(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+) -> QueryResult<Option<ChatroomRole>> {
+    memberships
+        .filter(chatroom_members::chatroom_id.eq(chatroom_uid))
+        .filter(chatroom_members::user_id.eq(user_uid))
+        .select(chatroom_members::role)
+        .first::<String>(pg_connection)
+        .optional()?
+        .map(parse_role)
+        .transpose()
+}
+
+/// Returns the membership of the user in the chatroom, this is `None` if they are not a member.
+pub fn lookup_membership(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+) -> QueryResult<Option<ChatroomMemberEntry>> {
+    memberships
+        .filter(chatroom_members::chatroom_id.eq(chatroom_uid))
+        .filter(chatroom_members::user_id.eq(user_uid))
+        .select(ChatroomMemberEntry::as_select())
+        .first(pg_connection)
+        .optional()
+}
+
+pub fn set_member_role(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+    role: ChatroomRole,
+) -> QueryResult<usize> {
+    diesel::update(
+        memberships
+            .filter(chatroom_members::chatroom_id.eq(chatroom_uid))
+            .filter(chatroom_members::user_id.eq(user_uid)),
+    )
+    .set(chatroom_members::role.eq(role.as_str()))
+    .execute(pg_connection)
+}
+
+/// Makes the highest ranked, longest standing member the owner of the chatroom.
+/// This is used when the owner leaves, returns the new owner or `None` if the chatroom has no members left.
+pub fn hand_over_ownership(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+) -> QueryResult<Option<i32>> {
+    let chatroom_memberships = memberships
+        .filter(chatroom_members::chatroom_id.eq(chatroom_uid))
+        .select(ChatroomMemberEntry::as_select())
+        .load::<ChatroomMemberEntry>(pg_connection)?;
+
+    // Admins are preferred over members, and members over read-only members
+    let successor = chatroom_memberships
+        .into_iter()
+        .map(|membership| {
+            Ok((
+                Reverse(parse_role(membership.role)?),
+                membership.joined_at,
+                membership.user_id,
+            ))
+        })
+        .collect::<QueryResult<Vec<_>>>()?
+        .into_iter()
+        .min();
+
+    let Some((_, _, successor_id)) = successor else {
+        return Ok(None);
+    };
+
+    set_member_role(
+        pg_connection,
+        chatroom_uid,
+        successor_id,
+        ChatroomRole::Owner,
+    )?;
+
+    Ok(Some(successor_id))
+}
+
+/// Returns the members of the chatroom in the order they have joined.
+pub fn lookup_chatroom_participants(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+) -> QueryResult<Vec<ChatroomMember>> {
+    memberships
+        .filter(chatroom_members::chatroom_id.eq(chatroom_uid))
+        .order((
+            chatroom_members::joined_at.asc(),
+            chatroom_members::user_id.asc(),
+        ))
+        .select(ChatroomMemberEntry::as_select())
+        .load::<ChatroomMemberEntry>(pg_connection)?
+        .into_iter()
+        .map(|membership| {
+            Ok(ChatroomMember {
+                user_id: membership.user_id,
+                role: parse_role(membership.role)?,
+                muted_until: membership.muted_until,
+            })
+        })
+        .collect()
+}
+
+/// Creates the response of the chatroom, the participants are looked up from its memberships.
+pub fn chatroom_response(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_entry: ChatroomEntry,
+) -> QueryResult<FetchChatroomResponse> {
+    let has_icon = select(exists(
+        chatroom_icons::table.filter(chatroom_icons::chatroom_id.eq(chatroom_entry.id)),
+    ))
+    .get_result(pg_connection)?;
+
+    Ok(FetchChatroomResponse {
+        participants: lookup_chatroom_participants(pg_connection, chatroom_entry.id)?,
+        chatroom_uid: chatroom_entry.id,
+        chatroom_id: chatroom_entry.chatroom_id,
+        chatroom_name: chatroom_entry.chatroom_name,
+        description: chatroom_entry.chatroom_description,
+        has_icon,
+        has_password: chatroom_entry.chatroom_password.is_some(),
+        is_direct_message: chatroom_entry.is_direct_message,
+        last_message_id: chatroom_entry.last_message_id,
+    })
+}
+
+/// The roles are stored as text, the database only allows the ones known by [`ChatroomRole`].
+pub fn parse_role(role: String) -> QueryResult<ChatroomRole> {
+    role.parse::<ChatroomRole>()
+        .map_err(|err| diesel::result::Error::DeserializationError(err.into()))
+}
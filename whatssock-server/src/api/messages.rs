@@ -0,0 +1,493 @@
+use std::env;
+
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use diesel::{
+    Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SelectableHelper, delete,
+    dsl::count_star, insert_into, update,
+};
+use log::{error, warn};
+use whatssock_lib::{
+    ChatroomMessageResponse, ChatroomPermission, DeleteMessageRequest, DeleteMessageResponse,
+    EditMessageRequest, FetchMessageEditsRequest, FetchMessageEditsResponse, MessageEdit,
+    WebSocketChatroomMessages,
+    client::{MessageRejectionReason, WebSocketChatroomEventClient},
+};
+
+use crate::{
+    ServerState,
+    api::{
+        authentication::AuthenticatedUser,
+        chatrooms::chatroom_message_response,
+        memberships::{is_chatroom_member, lookup_membership, require_chatroom_permission},
+        moderation::authorize_moderation,
+        reactions::load_message_reactions,
+        websocket::broadcast_chatroom_event,
+    },
+    models::{MessageEditEntry, MessageEntry, NewMessageEdit},
+    schema::{
+        chatrooms::{self, dsl::chatrooms as chatroom_entries},
+        message_edits::{self, dsl::message_edits as edits},
+        message_reactions::{self, dsl::message_reactions as reactions},
+        messages::{self, dsl::messages as message_entries},
+    },
+};
+
+/// Reads how long messages can be edited after they have been sent from `MESSAGE_EDIT_WINDOW_SECS`.
+/// Messages can be edited at any time if the variable is not set.
+pub fn message_edit_window_from_env() -> anyhow::Result<Option<TimeDelta>> {
+    match env::var("MESSAGE_EDIT_WINDOW_SECS") {
+        Ok(window_secs) => {
+            let window_secs = window_secs.parse::<i64>().map_err(|_| {
+                anyhow::anyhow!(
+                    "MESSAGE_EDIT_WINDOW_SECS must be a number of seconds, found: `{window_secs}`"
+                )
+            })?;
+
+            Ok(Some(TimeDelta::seconds(window_secs)))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+pub async fn edit_message(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(edit_request): Json<EditMessageRequest>,
+) -> Result<Json<ChatroomMessageResponse>, StatusCode> {
+    // Messages cannot be edited to be empty, and tombstones are only created by deleting the message
+    match &edit_request.message {
+        WebSocketChatroomMessages::StringMessage(message) if message.trim().is_empty() => {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        WebSocketChatroomMessages::Deleted { .. } => {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        _ => (),
+    }
+
+    let raw_message = rmp_serde::to_vec(&edit_request.message).unwrap();
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let message_entry = message_entries
+        .filter(messages::id.eq(edit_request.message_id))
+        .select(MessageEntry::as_select())
+        .get_result(&mut pg_connection)
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while fetching message from db: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    if message_entry.owner_user_id != authenticated_user.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if message_entry.deleted_at.is_some() {
+        return Err(StatusCode::GONE);
+    }
+
+    if state
+        .message_edit_window
+        .is_some_and(|edit_window| message_entry.send_date + edit_window < Utc::now().naive_utc())
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Members who cannot send messages to the chatroom anymore cannot edit their old ones either
+    require_unmuted_sender(
+        &mut pg_connection,
+        message_entry.parent_chatroom_id,
+        authenticated_user.user_id,
+    )?;
+
+    // There is nothing to edit
+    if message_entry.raw_message == raw_message {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let edited_at = Utc::now().naive_utc();
+
+    let edited_message = pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            // The content is only replaced if it has not changed since it was read, so that concurrent edits cannot drop a version from the history
+            let edited_message = update(
+                message_entries
+                    .filter(messages::id.eq(message_entry.id))
+                    .filter(messages::raw_message.eq(&message_entry.raw_message)),
+            )
+            .set((
+                messages::raw_message.eq(&raw_message),
+                messages::edited_at.eq(edited_at),
+            ))
+            .returning(MessageEntry::as_returning())
+            .get_result(pg_connection)?;
+
+            insert_into(edits)
+                .values(&NewMessageEdit {
+                    message_id: message_entry.id,
+                    raw_message: message_entry.raw_message,
+                    edited_at,
+                })
+                .execute(pg_connection)?;
+
+            Ok(edited_message)
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::CONFLICT,
+            err => {
+                error!("An error occured while editing a message: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    broadcast_chatroom_event(
+        &state,
+        edited_message.parent_chatroom_id,
+        &WebSocketChatroomEventClient::MessageEdited {
+            chatroom_uid: edited_message.parent_chatroom_id,
+            message_id: edited_message.id,
+            message: edit_request.message,
+            edited_at,
+        },
+    );
+
+    let message_reactions = load_message_reactions(
+        &mut pg_connection,
+        &[edited_message.id],
+        authenticated_user.user_id,
+    )
+    .map_err(|err| {
+        error!(
+            "An error occured while fetching message reactions from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .remove(&edited_message.id)
+    .unwrap_or_default();
+
+    Ok(Json(chatroom_message_response(
+        edited_message,
+        message_reactions,
+    )))
+}
+
+pub async fn delete_message(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(delete_request): Json<DeleteMessageRequest>,
+) -> Result<Json<DeleteMessageResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let message_entry = message_entries
+        .filter(messages::id.eq(delete_request.message_id))
+        .select(MessageEntry::as_select())
+        .get_result(&mut pg_connection)
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while fetching message from db: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    let chatroom_uid = message_entry.parent_chatroom_id;
+    let removed_by_moderator = message_entry.owner_user_id != authenticated_user.user_id;
+
+    if removed_by_moderator {
+        authorize_moderation(
+            &mut pg_connection,
+            chatroom_uid,
+            authenticated_user.user_id,
+            message_entry.owner_user_id,
+        )?;
+    } else {
+        let is_member =
+            is_chatroom_member(&mut pg_connection, chatroom_uid, authenticated_user.user_id)
+                .map_err(|err| {
+                    error!(
+                        "An error occured while fetching chatroom members from db: {}",
+                        err
+                    );
+
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+
+        // Authors who have left the chatroom cannot reach their messages anymore
+        if !is_member {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    }
+
+    if message_entry.deleted_at.is_some() {
+        return Err(StatusCode::GONE);
+    }
+
+    let tombstone = rmp_serde::to_vec(&WebSocketChatroomMessages::Deleted {
+        removed_by_moderator,
+    })
+    .unwrap();
+
+    let (last_message_id, thread_summary) = pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            // The row is kept so that the ids stay continuous, only its content is removed
+            update(
+                message_entries
+                    .filter(messages::id.eq(message_entry.id))
+                    .filter(messages::deleted_at.is_null()),
+            )
+            .set((
+                messages::raw_message.eq(&tombstone),
+                messages::edited_at.eq(None::<NaiveDateTime>),
+                messages::deleted_at.eq(Utc::now().naive_utc()),
+            ))
+            .returning(messages::id)
+            .get_result::<i32>(pg_connection)?;
+
+            // The previous versions would still contain the deleted content
+            delete(edits.filter(message_edits::message_id.eq(message_entry.id)))
+                .execute(pg_connection)?;
+
+            delete(reactions.filter(message_reactions::message_id.eq(message_entry.id)))
+                .execute(pg_connection)?;
+
+            // The deleted message might have been the latest one of the chatroom
+            let last_message_id = message_entries
+                .filter(messages::parent_chatroom_id.eq(chatroom_uid))
+                .filter(messages::thread_root_id.is_null())
+                .filter(messages::deleted_at.is_null())
+                .select(diesel::dsl::max(messages::id))
+                .get_result::<Option<i32>>(pg_connection)?;
+
+            update(chatroom_entries.filter(chatrooms::id.eq(chatroom_uid)))
+                .set(chatrooms::last_message_id.eq(last_message_id))
+                .execute(pg_connection)?;
+
+            let thread_summary = match message_entry.thread_root_id {
+                Some(thread_root_id) => Some((
+                    thread_root_id,
+                    refresh_thread_summary(pg_connection, thread_root_id)?,
+                )),
+                None => None,
+            };
+
+            Ok((last_message_id, thread_summary))
+        })
+        .map_err(|err| match err {
+            // The message has been deleted concurrently
+            diesel::result::Error::NotFound => StatusCode::GONE,
+            err => {
+                error!("An error occured while deleting a message: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    broadcast_chatroom_event(
+        &state,
+        chatroom_uid,
+        &WebSocketChatroomEventClient::MessageDeleted {
+            chatroom_uid,
+            message_id: message_entry.id,
+            removed_by_moderator,
+            last_message_id,
+        },
+    );
+
+    if let Some((thread_root_id, (reply_count, last_reply_at))) = thread_summary {
+        broadcast_chatroom_event(
+            &state,
+            chatroom_uid,
+            &WebSocketChatroomEventClient::ThreadUpdated {
+                chatroom_uid,
+                thread_root_id,
+                reply_count,
+                last_reply_at,
+            },
+        );
+    }
+
+    Ok(Json(DeleteMessageResponse {}))
+}
+
+/// Returns the previous versions of the message, every member of the chatroom can see them.
+pub async fn fetch_message_edits(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(edits_request): Json<FetchMessageEditsRequest>,
+) -> Result<Json<FetchMessageEditsResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let chatroom_uid = message_entries
+        .filter(messages::id.eq(edits_request.message_id))
+        .select(messages::parent_chatroom_id)
+        .get_result::<i32>(&mut pg_connection)
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while fetching message from db: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    let is_member =
+        is_chatroom_member(&mut pg_connection, chatroom_uid, authenticated_user.user_id).map_err(
+            |err| {
+                error!(
+                    "An error occured while fetching chatroom members from db: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            },
+        )?;
+
+    // Non-members are not told whether the message exists
+    if !is_member {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let edit_entries = edits
+        .filter(message_edits::message_id.eq(edits_request.message_id))
+        .order(message_edits::id.asc())
+        .select(MessageEditEntry::as_select())
+        .load::<MessageEditEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the message's edits: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(FetchMessageEditsResponse {
+        edits: edit_entries
+            .into_iter()
+            .filter_map(|edit_entry| {
+                // Versions which cannot be decoded are left out instead of failing the whole history
+                match rmp_serde::from_slice::<WebSocketChatroomMessages>(&edit_entry.raw_message) {
+                    Ok(message) => Some(MessageEdit {
+                        message,
+                        edited_at: edit_entry.edited_at,
+                    }),
+                    Err(err) => {
+                        warn!(
+                            "The edit with id {} could not be decoded: {}",
+                            edit_entry.id, err
+                        );
+
+                        None
+                    }
+                }
+            })
+            .collect(),
+    }))
+}
+
+/// Checks that the user can send messages to the chatroom and is not muted in it.
+pub fn require_unmuted_sender(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+) -> Result<(), StatusCode> {
+    if lookup_sender_rejection(pg_connection, chatroom_uid, user_uid)?.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(())
+}
+
+/// Returns why the user cannot send messages to the chatroom, or `None` if they can.
+/// Read-only members and users outside of the chatroom are missing the permission, muted members cannot send messages until their mute expires.
+pub fn lookup_sender_rejection(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+) -> Result<Option<MessageRejectionReason>, StatusCode> {
+    match require_chatroom_permission(
+        pg_connection,
+        chatroom_uid,
+        user_uid,
+        ChatroomPermission::SendMessages,
+    ) {
+        Ok(_) => (),
+        Err(StatusCode::FORBIDDEN) => return Ok(Some(MessageRejectionReason::MissingPermission)),
+        Err(err) => return Err(err),
+    }
+
+    let muted_until = lookup_membership(pg_connection, chatroom_uid, user_uid)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching chatroom members from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .and_then(|membership| membership.muted_until)
+        .filter(|muted_until| *muted_until > Utc::now().naive_utc());
+
+    Ok(muted_until.map(|until| MessageRejectionReason::Muted { until }))
+}
+
+/// Recounts the replies of the thread from the ones which have not been deleted.
+/// Returns the new reply count and the date of the latest reply.
+pub fn refresh_thread_summary(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    thread_root_id: i32,
+) -> QueryResult<(u32, Option<NaiveDateTime>)> {
+    let (reply_count, last_reply_at) = message_entries
+        .filter(messages::thread_root_id.eq(thread_root_id))
+        .filter(messages::deleted_at.is_null())
+        .select((count_star(), diesel::dsl::max(messages::send_date)))
+        .get_result::<(i64, Option<NaiveDateTime>)>(pg_connection)?;
+
+    update(message_entries.filter(messages::id.eq(thread_root_id)))
+        .set((
+            messages::thread_reply_count.eq(reply_count as i32),
+            messages::thread_last_reply_at.eq(last_reply_at),
+        ))
+        .execute(pg_connection)?;
+
+    Ok((reply_count as u32, last_reply_at))
+}
@@ -1,3 +1,15 @@
+pub mod account_management;
+pub mod authentication;
+pub mod chatroom_settings;
 pub mod chatrooms;
+pub mod email_verification;
+pub mod invites;
+pub mod memberships;
+pub mod messages;
+pub mod moderation;
+pub mod password_reset;
+pub mod profiles;
+pub mod reactions;
+pub mod two_factor;
 pub mod user_account_control;
 pub mod websocket;
@@ -0,0 +1,404 @@
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{TimeDelta, Utc};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+    SelectableHelper, dsl::exists, select,
+};
+use log::error;
+use whatssock_lib::{
+    BanMemberRequest, BanMemberResponse, ChatroomBan, ChatroomPermission, KickMemberRequest,
+    KickMemberResponse, ListChatroomBansRequest, ListChatroomBansResponse, MuteMemberRequest,
+    MuteMemberResponse, UnbanMemberRequest, UnbanMemberResponse,
+    client::{ParticipantRemovalReason, WebSocketChatroomEventClient},
+};
+
+use crate::{
+    ServerState,
+    api::{
+        authentication::AuthenticatedUser,
+        memberships::{
+            lookup_membership, parse_role, remove_chatroom_member, require_chatroom_permission,
+        },
+        websocket::{
+            broadcast_chatroom_event, send_chatroom_event_to_subscriber,
+            unsubscribe_user_from_chatroom,
+        },
+    },
+    models::{ChatroomBanEntry, ChatroomMemberEntry, NewChatroomBan},
+    schema::{
+        chatroom_bans::{self, dsl::chatroom_bans as bans},
+        chatroom_members::{self, dsl::chatroom_members as memberships},
+        users::{self, dsl::users as user_accounts},
+    },
+};
+
+/// The maximum length of the reason a ban can be given.
+const MAX_BAN_REASON_LENGTH: usize = 500;
+
+pub async fn kick_member(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(kick_request): Json<KickMemberRequest>,
+) -> Result<Json<KickMemberResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Only members can be kicked
+    authorize_moderation(
+        &mut pg_connection,
+        kick_request.chatroom_uid,
+        authenticated_user.user_id,
+        kick_request.user_id,
+    )?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    remove_chatroom_member(
+        &mut pg_connection,
+        kick_request.chatroom_uid,
+        kick_request.user_id,
+    )
+    .map_err(|err| {
+        error!("An error occured while kicking a member: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    evict_participant(
+        &state,
+        kick_request.chatroom_uid,
+        kick_request.user_id,
+        ParticipantRemovalReason::Kicked,
+    );
+
+    Ok(Json(KickMemberResponse {}))
+}
+
+pub async fn ban_member(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(ban_request): Json<BanMemberRequest>,
+) -> Result<Json<BanMemberResponse>, StatusCode> {
+    let reason = ban_request
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+
+    if reason
+        .as_ref()
+        .is_some_and(|reason| reason.chars().count() > MAX_BAN_REASON_LENGTH)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Users who are not members of the chatroom can be banned too, so that they cannot join it
+    let membership = authorize_moderation(
+        &mut pg_connection,
+        ban_request.chatroom_uid,
+        authenticated_user.user_id,
+        ban_request.user_id,
+    )?;
+
+    let now = Utc::now().naive_utc();
+    let expires_at = ban_request
+        .duration_secs
+        .map(|duration_secs| now + TimeDelta::seconds(duration_secs.into()));
+
+    let new_ban = NewChatroomBan {
+        chatroom_id: ban_request.chatroom_uid,
+        user_id: ban_request.user_id,
+        banned_by: authenticated_user.user_id,
+        reason,
+        expires_at,
+    };
+
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            // Make sure that the user exists
+            user_accounts
+                .filter(users::id.eq(ban_request.user_id))
+                .select(users::id)
+                .get_result::<i32>(pg_connection)?;
+
+            // Banning a user again replaces their previous ban
+            diesel::insert_into(bans)
+                .values(&new_ban)
+                .on_conflict((chatroom_bans::chatroom_id, chatroom_bans::user_id))
+                .do_update()
+                .set((&new_ban, chatroom_bans::created_at.eq(now)))
+                .execute(pg_connection)?;
+
+            remove_chatroom_member(pg_connection, ban_request.chatroom_uid, ban_request.user_id)?;
+
+            Ok(())
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while banning a user: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    if membership.is_some() {
+        evict_participant(
+            &state,
+            ban_request.chatroom_uid,
+            ban_request.user_id,
+            ParticipantRemovalReason::Banned { expires_at },
+        );
+    }
+
+    Ok(Json(BanMemberResponse {}))
+}
+
+pub async fn unban_member(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(unban_request): Json<UnbanMemberRequest>,
+) -> Result<Json<UnbanMemberResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        unban_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::ModerateMembers,
+    )?;
+
+    let removed_bans = diesel::delete(
+        bans.filter(chatroom_bans::chatroom_id.eq(unban_request.chatroom_uid))
+            .filter(chatroom_bans::user_id.eq(unban_request.user_id)),
+    )
+    .execute(&mut pg_connection)
+    .map_err(|err| {
+        error!("An error occured while unbanning a user: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if removed_bans == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Json(UnbanMemberResponse {}))
+}
+
+pub async fn list_chatroom_bans(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(bans_request): Json<ListChatroomBansRequest>,
+) -> Result<Json<ListChatroomBansResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    require_chatroom_permission(
+        &mut pg_connection,
+        bans_request.chatroom_uid,
+        authenticated_user.user_id,
+        ChatroomPermission::ModerateMembers,
+    )?;
+
+    let now = Utc::now().naive_utc();
+
+    let ban_entries = bans
+        .filter(chatroom_bans::chatroom_id.eq(bans_request.chatroom_uid))
+        .filter(
+            chatroom_bans::expires_at
+                .is_null()
+                .or(chatroom_bans::expires_at.gt(now)),
+        )
+        .order(chatroom_bans::created_at.desc())
+        .select(ChatroomBanEntry::as_select())
+        .load::<ChatroomBanEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!("An error occured while fetching chatroom bans: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(ListChatroomBansResponse {
+        bans: ban_entries
+            .into_iter()
+            .map(|ban_entry| ChatroomBan {
+                user_id: ban_entry.user_id,
+                banned_by: ban_entry.banned_by,
+                reason: ban_entry.reason,
+                created_at: ban_entry.created_at,
+                expires_at: ban_entry.expires_at,
+            })
+            .collect(),
+    }))
+}
+
+pub async fn mute_member(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(mute_request): Json<MuteMemberRequest>,
+) -> Result<Json<MuteMemberResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Only members can be muted
+    authorize_moderation(
+        &mut pg_connection,
+        mute_request.chatroom_uid,
+        authenticated_user.user_id,
+        mute_request.user_id,
+    )?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    let muted_until = mute_request
+        .duration_secs
+        .map(|duration_secs| Utc::now().naive_utc() + TimeDelta::seconds(duration_secs.into()));
+
+    diesel::update(
+        memberships
+            .filter(chatroom_members::chatroom_id.eq(mute_request.chatroom_uid))
+            .filter(chatroom_members::user_id.eq(mute_request.user_id)),
+    )
+    .set(chatroom_members::muted_until.eq(muted_until))
+    .execute(&mut pg_connection)
+    .map_err(|err| {
+        error!("An error occured while muting a member: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    broadcast_chatroom_event(
+        &state,
+        mute_request.chatroom_uid,
+        &WebSocketChatroomEventClient::MemberMuted {
+            chatroom_uid: mute_request.chatroom_uid,
+            user_id: mute_request.user_id,
+            muted_until,
+        },
+    );
+
+    Ok(Json(MuteMemberResponse {}))
+}
+
+/// Checks whether the user has a ban in the chatroom which has not expired yet.
+pub fn is_user_banned(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    user_uid: i32,
+) -> QueryResult<bool> {
+    select(exists(
+        bans.filter(chatroom_bans::chatroom_id.eq(chatroom_uid))
+            .filter(chatroom_bans::user_id.eq(user_uid))
+            .filter(
+                chatroom_bans::expires_at
+                    .is_null()
+                    .or(chatroom_bans::expires_at.gt(Utc::now().naive_utc())),
+            ),
+    ))
+    .get_result(pg_connection)
+}
+
+/// Checks that the moderator is allowed to act on the user, returns the user's membership if they are a member of the chatroom.
+pub fn authorize_moderation(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    chatroom_uid: i32,
+    moderator_uid: i32,
+    user_uid: i32,
+) -> Result<Option<ChatroomMemberEntry>, StatusCode> {
+    if moderator_uid == user_uid {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let moderator_role = require_chatroom_permission(
+        pg_connection,
+        chatroom_uid,
+        moderator_uid,
+        ChatroomPermission::ModerateMembers,
+    )?;
+
+    let membership = lookup_membership(pg_connection, chatroom_uid, user_uid).map_err(|err| {
+        error!(
+            "An error occured while fetching chatroom members from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if let Some(membership) = &membership {
+        let member_role = parse_role(membership.role.clone()).map_err(|err| {
+            error!("An error occured while reading a member's role: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        // Only the members ranked below the moderator can be moderated
+        if member_role >= moderator_role {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    Ok(membership)
+}
+
+/// Notifies the chatroom about the removal, then stops delivering the chatroom's messages to the removed user.
+fn evict_participant(
+    state: &ServerState,
+    chatroom_uid: i32,
+    user_id: i32,
+    reason: ParticipantRemovalReason,
+) {
+    let participant_removed = WebSocketChatroomEventClient::ParticipantRemoved {
+        chatroom_uid,
+        user_id,
+        reason,
+    };
+
+    // The removed user is notified directly, the handler would only deliver the broadcast after the user has been unsubscribed
+    send_chatroom_event_to_subscriber(state, chatroom_uid, user_id, &participant_removed);
+
+    unsubscribe_user_from_chatroom(state, chatroom_uid, user_id);
+
+    broadcast_chatroom_event(state, chatroom_uid, &participant_removed);
+}
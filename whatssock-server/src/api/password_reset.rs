@@ -0,0 +1,180 @@
+use std::net::SocketAddr;
+
+use axum::{
+    Json,
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+};
+use chrono::{TimeDelta, Utc};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, delete, update,
+};
+use log::error;
+use whatssock_lib::{
+    client::{ForgotPasswordRequest, ResetPasswordRequest},
+    decode_secure_key, encode_secure_key,
+    server::{ForgotPasswordResponse, ResetPasswordResponse},
+};
+
+use crate::{
+    ServerState,
+    api::user_account_control::{generate_random_secure_key, hash_password, hash_secure_key},
+    mail::{Mail, send_in_background},
+    models::{NewPasswordResetToken, PasswordResetTokenEntry, UserAccountEntry},
+    rate_limit::{RateLimitKey, RateLimitedError},
+    schema::{
+        password_reset_tokens::{self, dsl::password_reset_tokens as reset_tokens},
+        user_session_auth::{self, dsl::user_session_auth as user_sessions},
+        users::{self, dsl::users as user_accounts},
+    },
+};
+
+/// How long a password reset token can be used after it has been sent.
+pub const PASSWORD_RESET_TOKEN_LIFETIME: TimeDelta = TimeDelta::hours(1);
+
+/// Emails a password reset token to the address of the account.
+/// The response is the same whether the account exists or not, so that the endpoint cannot be used to probe usernames.
+pub async fn request_password_reset(
+    State(state): State<ServerState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    Json(reset_request): Json<ForgotPasswordRequest>,
+) -> Result<Json<ForgotPasswordResponse>, RateLimitedError> {
+    let rate_limit_keys = [
+        RateLimitKey::Ip(remote_addr.ip()),
+        RateLimitKey::Username(reset_request.username.clone()),
+    ];
+
+    // Every request counts, so that the endpoint cannot be used to flood the user's inbox
+    state.password_reset_rate_limiter.check(&rate_limit_keys)?;
+    state
+        .password_reset_rate_limiter
+        .record_attempt(&rate_limit_keys);
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = user_accounts
+        .filter(users::username.eq(reset_request.username))
+        .get_result::<UserAccountEntry>(&mut pg_connection)
+        .optional()
+        .map_err(|err| {
+            error!(
+                "An error occured while searching for the user's account: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let Some(user_account) = user_account else {
+        return Ok(Json(ForgotPasswordResponse {}));
+    };
+
+    let token = generate_random_secure_key();
+
+    // Only the latest token can be used
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            delete(reset_tokens.filter(password_reset_tokens::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            diesel::insert_into(reset_tokens)
+                .values(&NewPasswordResetToken {
+                    user_id: user_account.id,
+                    token_hash: hash_secure_key(&token),
+                    expires_at: Utc::now().naive_utc() + PASSWORD_RESET_TOKEN_LIFETIME,
+                })
+                .execute(pg_connection)?;
+
+            Ok(())
+        })
+        .map_err(|err| {
+            error!(
+                "An error occured while storing the user's password reset token: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    send_in_background(
+        state.mailer.clone(),
+        Mail {
+            to: user_account.email,
+            subject: String::from("Reset your Whatssock password"),
+            body: format!(
+                "Hi {}!\n\nSomeone has requested a password reset for your account. Your password reset code is:\n\n{}\n\nEnter it in Whatssock to set a new password. The code expires in {} minutes.\nIf you have not requested a password reset, you can ignore this email.",
+                user_account.username,
+                encode_secure_key(&token),
+                PASSWORD_RESET_TOKEN_LIFETIME.num_minutes()
+            ),
+        },
+    );
+
+    Ok(Json(ForgotPasswordResponse {}))
+}
+
+/// Sets a new password with a token sent by [`request_password_reset`].
+/// The token can only be used once, and every session of the user is logged out.
+pub async fn reset_password(
+    State(state): State<ServerState>,
+    Json(reset_request): Json<ResetPasswordRequest>,
+) -> Result<Json<ResetPasswordResponse>, StatusCode> {
+    if reset_request.new_password.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let token = decode_secure_key(reset_request.token.trim()).ok_or(StatusCode::NOT_FOUND)?;
+
+    let new_password_hash = hash_password(&reset_request.new_password)?;
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let now = Utc::now().naive_utc();
+
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            // The token is deleted when it is used, so that it can only be used once
+            let token_entry = delete(
+                reset_tokens
+                    .filter(password_reset_tokens::token_hash.eq(hash_secure_key(&token)))
+                    .filter(password_reset_tokens::expires_at.gt(now)),
+            )
+            .get_result::<PasswordResetTokenEntry>(pg_connection)?;
+
+            update(user_accounts.filter(users::id.eq(token_entry.user_id)))
+                .set(users::passw.eq(new_password_hash))
+                .execute(pg_connection)?;
+
+            // Whoever knew the old password should not stay logged in
+            delete(user_sessions.filter(user_session_auth::user_id.eq(token_entry.user_id)))
+                .execute(pg_connection)?;
+
+            Ok(())
+        })
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while resetting a password: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    Ok(Json(ResetPasswordResponse {}))
+}
@@ -0,0 +1,411 @@
+use axum::{
+    Json,
+    body::Bytes,
+    extract::State,
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgTextExpressionMethods, QueryDsl,
+    RunQueryDsl, define_sql_function, delete,
+    dsl::exists,
+    insert_into, select,
+    sql_types::{Float4, Nullable, Text},
+    update,
+};
+use log::error;
+use whatssock_lib::{
+    DELETED_USER_ID, UserLookup,
+    client::{SearchUsersRequest, UpdatePrivacyRequest, UpdateProfileRequest},
+    server::{SearchUsersResponse, UpdatePrivacyResponse},
+};
+
+use crate::{
+    ServerState,
+    api::authentication::AuthenticatedUser,
+    models::{UpdateUserProfile, UserAccountEntry, UserAvatarEntry},
+    schema::{
+        user_avatars::{self, dsl::user_avatars as avatars},
+        users::{self, dsl::users as user_accounts},
+    },
+};
+
+/// The maximum length of the display name in characters.
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 32;
+
+/// The maximum length of the bio in characters.
+pub const MAX_BIO_LENGTH: usize = 500;
+
+/// The maximum length of the status text in characters.
+pub const MAX_STATUS_LENGTH: usize = 100;
+
+/// The maximum size of an avatar image in bytes.
+pub const MAX_AVATAR_SIZE: usize = 256 * 1024;
+
+/// The number of users returned on a single page of the user search.
+pub const USER_SEARCH_PAGE_SIZE: i64 = 20;
+
+/// The minimum `pg_trgm` word similarity of a fuzzy match in the user search.
+const USER_SEARCH_SIMILARITY_THRESHOLD: f32 = 0.3;
+
+define_sql_function! {
+    /// The `pg_trgm` similarity of the query and the most similar part of the text.
+    fn word_similarity(query: Text, text: Text) -> Float4;
+}
+
+define_sql_function! {
+    fn coalesce(value: Nullable<Text>, fallback: Text) -> Text;
+}
+
+define_sql_function! {
+    fn greatest(first: Float4, second: Float4) -> Float4;
+}
+
+pub async fn update_profile(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(profile_request): Json<UpdateProfileRequest>,
+) -> Result<Json<UserLookup>, StatusCode> {
+    let updated_profile = UpdateUserProfile {
+        display_name: normalize_profile_field(
+            profile_request.display_name,
+            MAX_DISPLAY_NAME_LENGTH,
+        )?,
+        bio: normalize_profile_field(profile_request.bio, MAX_BIO_LENGTH)?,
+        status_text: normalize_profile_field(profile_request.status, MAX_STATUS_LENGTH)?,
+    };
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = update(user_accounts.filter(users::id.eq(authenticated_user.user_id)))
+        .set(&updated_profile)
+        .get_result::<UserAccountEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while updating the user's profile: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(lookup_user_profile(&mut pg_connection, user_account)?))
+}
+
+/// Replaces the user's avatar with the image in the request's body, an empty body removes the avatar.
+pub async fn update_avatar(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    avatar_image: Bytes,
+) -> Result<Json<UserLookup>, StatusCode> {
+    if avatar_image.len() > MAX_AVATAR_SIZE {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    if !avatar_image.is_empty() && detect_image_mime_type(&avatar_image).is_none() {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if avatar_image.is_empty() {
+        delete(avatars.filter(user_avatars::user_id.eq(authenticated_user.user_id)))
+            .execute(&mut pg_connection)
+            .map_err(|err| {
+                error!("An error occured while removing the user's avatar: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    } else {
+        let avatar_entry = UserAvatarEntry {
+            user_id: authenticated_user.user_id,
+            image: avatar_image.to_vec(),
+            updated_at: Utc::now().naive_utc(),
+        };
+
+        insert_into(avatars)
+            .values(&avatar_entry)
+            .on_conflict(user_avatars::user_id)
+            .do_update()
+            .set((
+                user_avatars::image.eq(&avatar_entry.image),
+                user_avatars::updated_at.eq(avatar_entry.updated_at),
+            ))
+            .execute(&mut pg_connection)
+            .map_err(|err| {
+                error!("An error occured while storing the user's avatar: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+
+    let user_account = user_accounts
+        .filter(users::id.eq(authenticated_user.user_id))
+        .get_result::<UserAccountEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching user account with id {}: {}",
+                authenticated_user.user_id, err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(lookup_user_profile(&mut pg_connection, user_account)?))
+}
+
+pub async fn fetch_avatar(
+    State(state): State<ServerState>,
+    uuid: String,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let uuid = uuid
+        .parse::<i32>()
+        .map_err(|_| StatusCode::METHOD_NOT_ALLOWED)?;
+
+    let avatar_image = avatars
+        .filter(user_avatars::user_id.eq(uuid))
+        .select(user_avatars::image)
+        .get_result::<Vec<u8>>(&mut pg_connection)
+        .optional()
+        .map_err(|err| {
+            error!("An error occured while fetching the user's avatar: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Only images with a known format are accepted when uploading the avatar
+    let mime_type =
+        detect_image_mime_type(&avatar_image).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(([(CONTENT_TYPE, mime_type)], avatar_image))
+}
+
+pub async fn update_privacy(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(privacy_request): Json<UpdatePrivacyRequest>,
+) -> Result<Json<UpdatePrivacyResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    update(user_accounts.filter(users::id.eq(authenticated_user.user_id)))
+        .set(users::discoverable.eq(privacy_request.discoverable))
+        .execute(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while updating the user's privacy settings: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(UpdatePrivacyResponse {}))
+}
+
+/// Searches the discoverable users by their usernames and display names.
+/// Users whose names start with the query are returned first, followed by the users whose names are similar to the query.
+pub async fn search_users(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(search_request): Json<SearchUsersRequest>,
+) -> Result<Json<SearchUsersResponse>, StatusCode> {
+    let search_query = search_request.query.trim().to_string();
+
+    if search_query.is_empty() || search_request.page < 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Pages this far out would overflow the offset
+    let page_offset = search_request
+        .page
+        .checked_mul(USER_SEARCH_PAGE_SIZE)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let prefix_pattern = format!("{}%", escape_like_pattern(&search_query));
+    let display_name = coalesce(users::display_name, "");
+
+    let is_prefix_match = users::username
+        .ilike(prefix_pattern.clone())
+        .or(display_name.ilike(prefix_pattern));
+    let similarity = greatest(
+        word_similarity(search_query.clone(), users::username),
+        word_similarity(search_query, display_name),
+    );
+
+    // Fetch one more user than the size of the page, so that we know if there is a next page
+    let mut matched_users = user_accounts
+        .filter(users::discoverable.eq(true))
+        .filter(users::id.ne(authenticated_user.user_id))
+        .filter(
+            is_prefix_match
+                .clone()
+                .or(similarity.clone().ge(USER_SEARCH_SIMILARITY_THRESHOLD)),
+        )
+        .order((
+            is_prefix_match.desc(),
+            similarity.desc(),
+            users::username.asc(),
+        ))
+        .offset(page_offset)
+        .limit(USER_SEARCH_PAGE_SIZE + 1)
+        .load::<UserAccountEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!("An error occured while searching for users: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let has_more = matched_users.len() as i64 > USER_SEARCH_PAGE_SIZE;
+
+    matched_users.truncate(USER_SEARCH_PAGE_SIZE as usize);
+
+    // Check the avatars of the whole page at once
+    let users_with_avatar = avatars
+        .filter(user_avatars::user_id.eq_any(matched_users.iter().map(|user| user.id)))
+        .select(user_avatars::user_id)
+        .load::<i32>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the users' avatars: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(SearchUsersResponse {
+        users: matched_users
+            .into_iter()
+            .map(|user_account| UserLookup {
+                has_avatar: users_with_avatar.contains(&user_account.id),
+                user_id: user_account.id,
+                username: user_account.username,
+                display_name: user_account.display_name,
+                bio: user_account.bio,
+                status: user_account.status_text,
+            })
+            .collect(),
+        has_more,
+    }))
+}
+
+/// Creates the public profile of the user, this does not contain any private information of the account.
+pub fn lookup_user_profile(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    user_account: UserAccountEntry,
+) -> Result<UserLookup, StatusCode> {
+    let has_avatar = select(exists(
+        avatars.filter(user_avatars::user_id.eq(user_account.id)),
+    ))
+    .get_result::<bool>(pg_connection)
+    .map_err(|err| {
+        error!("An error occured while fetching the user's avatar: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(UserLookup {
+        user_id: user_account.id,
+        username: user_account.username,
+        display_name: user_account.display_name,
+        bio: user_account.bio,
+        status: user_account.status_text,
+        has_avatar,
+    })
+}
+
+/// The profile displayed as the author of the messages which have been kept after their author has deleted their account.
+pub fn deleted_user_profile() -> UserLookup {
+    UserLookup {
+        user_id: DELETED_USER_ID,
+        username: String::from("Deleted user"),
+        ..Default::default()
+    }
+}
+
+/// Trims the field and treats empty fields as unset, returns `BAD_REQUEST` if the field is longer than `max_length` characters.
+pub fn normalize_profile_field(
+    field: Option<String>,
+    max_length: usize,
+) -> Result<Option<String>, StatusCode> {
+    let Some(field) = field else {
+        return Ok(None);
+    };
+
+    let field = field.trim();
+
+    if field.chars().count() > max_length {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok((!field.is_empty()).then(|| field.to_string()))
+}
+
+/// Escapes the characters which have a special meaning in `LIKE` patterns.
+fn escape_like_pattern(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Returns the mime type of the image based on its magic bytes, only PNG, JPEG, GIF and WebP images are recognized.
+pub fn detect_image_mime_type(image: &[u8]) -> Option<&'static str> {
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if image.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if image.starts_with(b"GIF87a") || image.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if image.len() >= 12 && image.starts_with(b"RIFF") && &image[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
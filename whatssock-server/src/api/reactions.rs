@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{Json, extract::State, http::StatusCode};
+use diesel::{
+    ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, delete, dsl::count_star, insert_into,
+};
+use log::error;
+use whatssock_lib::{
+    MessageReaction, MessageReactionRequest, MessageReactionResponse,
+    client::WebSocketChatroomEventClient,
+};
+
+use crate::{
+    ServerState,
+    api::{
+        authentication::AuthenticatedUser, memberships::is_chatroom_member,
+        messages::require_unmuted_sender, websocket::broadcast_chatroom_event,
+    },
+    models::NewMessageReaction,
+    schema::{
+        message_reactions::{self, dsl::message_reactions as reactions},
+        messages::{self, dsl::messages as message_entries},
+    },
+};
+
+/// The maximum amount of different emoji a message can be reacted with.
+pub const MAX_REACTIONS_PER_MESSAGE: i64 = 20;
+
+pub async fn add_reaction(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(reaction_request): Json<MessageReactionRequest>,
+) -> Result<Json<MessageReactionResponse>, StatusCode> {
+    // Reactions have to be a single emoji, the fully qualified form is stored so that the variations of an emoji are counted together
+    let emoji = emojis::get(reaction_request.emoji.trim())
+        .ok_or(StatusCode::BAD_REQUEST)?
+        .as_str()
+        .to_string();
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let chatroom_uid = lookup_reactable_message(&mut pg_connection, reaction_request.message_id)?;
+
+    // Read-only and muted members cannot react either
+    require_unmuted_sender(&mut pg_connection, chatroom_uid, authenticated_user.user_id)?;
+
+    let is_new_emoji = reactions
+        .filter(message_reactions::message_id.eq(reaction_request.message_id))
+        .filter(message_reactions::emoji.eq(&emoji))
+        .count()
+        .get_result::<i64>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching message reactions from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        == 0;
+
+    if is_new_emoji {
+        let emoji_count = reactions
+            .filter(message_reactions::message_id.eq(reaction_request.message_id))
+            .select(message_reactions::emoji)
+            .distinct()
+            .count()
+            .get_result::<i64>(&mut pg_connection)
+            .map_err(|err| {
+                error!(
+                    "An error occured while fetching message reactions from db: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        if emoji_count >= MAX_REACTIONS_PER_MESSAGE {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    let inserted_reactions = insert_into(reactions)
+        .values(&NewMessageReaction {
+            message_id: reaction_request.message_id,
+            user_id: authenticated_user.user_id,
+            emoji: emoji.clone(),
+        })
+        .on_conflict_do_nothing()
+        .execute(&mut pg_connection)
+        .map_err(|err| {
+            error!("An error occured while adding a reaction: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // The participants are only notified if the user has not reacted with this emoji already
+    if inserted_reactions > 0 {
+        broadcast_chatroom_event(
+            &state,
+            chatroom_uid,
+            &WebSocketChatroomEventClient::ReactionAdded {
+                chatroom_uid,
+                message_id: reaction_request.message_id,
+                user_id: authenticated_user.user_id,
+                emoji,
+            },
+        );
+    }
+
+    Ok(Json(MessageReactionResponse {}))
+}
+
+pub async fn remove_reaction(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(reaction_request): Json<MessageReactionRequest>,
+) -> Result<Json<MessageReactionResponse>, StatusCode> {
+    let emoji = reaction_request.emoji.trim();
+
+    // Reactions are stored in their fully qualified form
+    let emoji = emojis::get(emoji)
+        .map_or(emoji, |emoji| emoji.as_str())
+        .to_string();
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let chatroom_uid = lookup_reactable_message(&mut pg_connection, reaction_request.message_id)?;
+
+    let is_member =
+        is_chatroom_member(&mut pg_connection, chatroom_uid, authenticated_user.user_id).map_err(
+            |err| {
+                error!(
+                    "An error occured while fetching chatroom members from db: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            },
+        )?;
+
+    // Non-members are not told whether the message exists
+    if !is_member {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let removed_reactions = delete(
+        reactions
+            .filter(message_reactions::message_id.eq(reaction_request.message_id))
+            .filter(message_reactions::user_id.eq(authenticated_user.user_id))
+            .filter(message_reactions::emoji.eq(&emoji)),
+    )
+    .execute(&mut pg_connection)
+    .map_err(|err| {
+        error!("An error occured while removing a reaction: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if removed_reactions > 0 {
+        broadcast_chatroom_event(
+            &state,
+            chatroom_uid,
+            &WebSocketChatroomEventClient::ReactionRemoved {
+                chatroom_uid,
+                message_id: reaction_request.message_id,
+                user_id: authenticated_user.user_id,
+                emoji,
+            },
+        );
+    }
+
+    Ok(Json(MessageReactionResponse {}))
+}
+
+/// Aggregates the reactions of the messages, the reactions of the user are marked as reacted.
+pub fn load_message_reactions(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    message_ids: &[i32],
+    user_uid: i32,
+) -> QueryResult<HashMap<i32, Vec<MessageReaction>>> {
+    let reaction_counts = reactions
+        .filter(message_reactions::message_id.eq_any(message_ids))
+        .group_by((message_reactions::message_id, message_reactions::emoji))
+        .select((
+            message_reactions::message_id,
+            message_reactions::emoji,
+            count_star(),
+        ))
+        .order(diesel::dsl::min(message_reactions::created_at))
+        .load::<(i32, String, i64)>(pg_connection)?;
+
+    let own_reactions = reactions
+        .filter(message_reactions::message_id.eq_any(message_ids))
+        .filter(message_reactions::user_id.eq(user_uid))
+        .select((message_reactions::message_id, message_reactions::emoji))
+        .load::<(i32, String)>(pg_connection)?
+        .into_iter()
+        .collect::<HashSet<(i32, String)>>();
+
+    let mut message_reactions = HashMap::<i32, Vec<MessageReaction>>::new();
+
+    for (message_id, emoji, count) in reaction_counts {
+        let reacted = own_reactions.contains(&(message_id, emoji.clone()));
+
+        message_reactions
+            .entry(message_id)
+            .or_default()
+            .push(MessageReaction {
+                emoji,
+                count: count as u32,
+                reacted,
+            });
+    }
+
+    Ok(message_reactions)
+}
+
+/// Returns the chatroom of the message, deleted messages cannot be reacted to.
+fn lookup_reactable_message(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    message_id: i32,
+) -> Result<i32, StatusCode> {
+    let (chatroom_uid, is_deleted) = message_entries
+        .filter(messages::id.eq(message_id))
+        .select((
+            messages::parent_chatroom_id,
+            messages::deleted_at.is_not_null(),
+        ))
+        .get_result::<(i32, bool)>(pg_connection)
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => StatusCode::NOT_FOUND,
+            err => {
+                error!("An error occured while fetching message from db: {}", err);
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    if is_deleted {
+        return Err(StatusCode::GONE);
+    }
+
+    Ok(chatroom_uid)
+}
@@ -0,0 +1,601 @@
+use std::{net::SocketAddr, time::SystemTime};
+
+use axum::{
+    Json,
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+};
+use chrono::{TimeDelta, Utc};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, delete,
+    insert_into, update,
+};
+use log::error;
+use rand::{Rng, rng};
+use totp_rs::{Algorithm, TOTP};
+use whatssock_lib::{
+    client::{
+        ConfirmTotpRequest, DisableTotpRequest, TwoFactorLoginRequest, UserSessionInformation,
+    },
+    decode_secure_key, encode_secure_key,
+    server::{
+        DisableTotpResponse, LoginResponseSecure, TotpConfirmationResponse, TotpEnrollmentResponse,
+        TwoFactorChallenge,
+    },
+};
+
+use crate::{
+    ServerState,
+    api::{
+        authentication::AuthenticatedUser,
+        user_account_control::{
+            PasswordVerification, constant_time_eq, generate_random_secure_key, hash_secure_key,
+            issue_user_session, lookup_joined_chatrooms, verify_password,
+        },
+    },
+    models::{LoginChallengeEntry, NewLoginChallenge, NewTotpRecoveryCode, UserAccountEntry},
+    rate_limit::{RateLimitKey, RateLimitedError},
+    schema::{
+        login_challenges::{self, dsl::login_challenges as challenges},
+        totp_recovery_codes::{self, dsl::totp_recovery_codes as recovery_codes},
+        users::{self, dsl::users as user_accounts},
+    },
+};
+
+/// The issuer shown in the user's authenticator app.
+pub const TOTP_ISSUER: &str = "Whatssock";
+
+/// The length of a TOTP time step in seconds.
+pub const TOTP_STEP: u64 = 30;
+
+/// How long the user has to submit the code after the password has been accepted.
+pub const LOGIN_CHALLENGE_LIFETIME: TimeDelta = TimeDelta::minutes(5);
+
+/// The amount of recovery codes issued when two-factor authentication is enabled.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// The characters the recovery codes are made of, similar looking characters are left out.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+pub async fn enroll_totp(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+) -> Result<Json<TotpEnrollmentResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = fetch_user_account(&mut pg_connection, authenticated_user.user_id)?;
+
+    if user_account.totp_enabled {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    // 160 bits, as recommended by RFC 4226
+    let mut totp_secret = vec![0_u8; 20];
+    rng().fill(&mut totp_secret[..]);
+
+    // Restarting the enrolment replaces the secret which has not been confirmed yet
+    update(user_accounts.filter(users::id.eq(user_account.id)))
+        .set((
+            users::totp_secret.eq(Some(totp_secret.clone())),
+            users::totp_last_used_step.eq(None::<i64>),
+        ))
+        .execute(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while storing the user's TOTP secret: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let totp = create_totp(totp_secret, user_account.username);
+
+    Ok(Json(TotpEnrollmentResponse {
+        provisioning_uri: totp.get_url(),
+        secret: totp.get_secret_base32(),
+    }))
+}
+
+pub async fn confirm_totp(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(confirm_request): Json<ConfirmTotpRequest>,
+) -> Result<Json<TotpConfirmationResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = fetch_user_account(&mut pg_connection, authenticated_user.user_id)?;
+
+    if user_account.totp_enabled {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    // The enrolment has not been started
+    let Some(totp_secret) = user_account.totp_secret else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let totp = create_totp(totp_secret, user_account.username);
+
+    let Some(matched_step) = match_totp_code(&totp, &confirm_request.code) else {
+        return Err(StatusCode::FORBIDDEN);
+    };
+
+    let recovery_code_list: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            update(user_accounts.filter(users::id.eq(user_account.id)))
+                .set((
+                    users::totp_enabled.eq(true),
+                    users::totp_last_used_step.eq(Some(matched_step)),
+                ))
+                .execute(pg_connection)?;
+
+            delete(recovery_codes.filter(totp_recovery_codes::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            insert_into(recovery_codes)
+                .values(
+                    recovery_code_list
+                        .iter()
+                        .map(|recovery_code| NewTotpRecoveryCode {
+                            user_id: user_account.id,
+                            code_hash: hash_secure_key(
+                                normalize_recovery_code(recovery_code).as_bytes(),
+                            ),
+                        })
+                        .collect::<Vec<NewTotpRecoveryCode>>(),
+                )
+                .execute(pg_connection)?;
+
+            Ok(())
+        })
+        .map_err(|err| {
+            error!(
+                "An error occured while enabling two-factor authentication: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(TotpConfirmationResponse {
+        recovery_codes: recovery_code_list,
+    }))
+}
+
+pub async fn disable_totp(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(disable_request): Json<DisableTotpRequest>,
+) -> Result<Json<DisableTotpResponse>, RateLimitedError> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = fetch_user_account(&mut pg_connection, authenticated_user.user_id)?;
+
+    if !user_account.totp_enabled {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+
+    // Wrong passwords and codes count as failed logins, so that a stolen session cannot be used to guess them
+    let rate_limit_keys = [RateLimitKey::Username(user_account.username.clone())];
+
+    state.login_rate_limiter.check(&rate_limit_keys)?;
+
+    if verify_password(&disable_request.password, &user_account.passw)
+        == PasswordVerification::Invalid
+        || !verify_second_factor(&mut pg_connection, &user_account, &disable_request.code)?
+    {
+        state.login_rate_limiter.record_attempt(&rate_limit_keys);
+
+        return Err(StatusCode::FORBIDDEN.into());
+    }
+
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            update(user_accounts.filter(users::id.eq(user_account.id)))
+                .set((
+                    users::totp_enabled.eq(false),
+                    users::totp_secret.eq(None::<Vec<u8>>),
+                    users::totp_last_used_step.eq(None::<i64>),
+                ))
+                .execute(pg_connection)?;
+
+            delete(recovery_codes.filter(totp_recovery_codes::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            Ok(())
+        })
+        .map_err(|err| {
+            error!(
+                "An error occured while disabling two-factor authentication: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(DisableTotpResponse {}))
+}
+
+/// Finishes a login started by `fetch_login` with a TOTP or recovery code.
+pub async fn complete_two_factor_login(
+    State(state): State<ServerState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    Json(login_request): Json<TwoFactorLoginRequest>,
+) -> Result<Json<LoginResponseSecure>, RateLimitedError> {
+    let challenge_token =
+        decode_secure_key(&login_request.challenge_token).ok_or(StatusCode::NOT_FOUND)?;
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let challenge = challenges
+        .filter(login_challenges::challenge_hash.eq(hash_secure_key(&challenge_token)))
+        .filter(login_challenges::expires_at.gt(Utc::now().naive_utc()))
+        .get_result::<LoginChallengeEntry>(&mut pg_connection)
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let user_account = fetch_user_account(&mut pg_connection, challenge.user_id)?;
+
+    let rate_limit_keys = [
+        RateLimitKey::Ip(remote_addr.ip()),
+        RateLimitKey::Username(user_account.username.clone()),
+    ];
+
+    state.login_rate_limiter.check(&rate_limit_keys)?;
+
+    if !verify_second_factor(&mut pg_connection, &user_account, &login_request.code)? {
+        state.login_rate_limiter.record_attempt(&rate_limit_keys);
+
+        return Err(StatusCode::FORBIDDEN.into());
+    }
+
+    // The challenge can only be used once
+    delete(challenges.filter(login_challenges::id.eq(challenge.id)))
+        .execute(&mut pg_connection)
+        .map_err(|err| {
+            error!("An error occured while deleting a login challenge: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    state.login_rate_limiter.reset(&rate_limit_keys[1]);
+
+    let user_session_secure =
+        issue_user_session(&mut pg_connection, user_account.id, challenge.device_label)?;
+
+    let chatrooms_joined =
+        lookup_joined_chatrooms(&mut pg_connection, user_account.id).map_err(|err| {
+            error!(
+                "An error occured while fetching the user's chatrooms from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(LoginResponseSecure {
+        user_information: UserSessionInformation {
+            username: user_account.username,
+            chatrooms_joined,
+            user_id: user_account.id,
+            email_verified: user_account.email_verified,
+            totp_enabled: user_account.totp_enabled,
+            discoverable: user_account.discoverable,
+        },
+        user_session_secure,
+    }))
+}
+
+/// Stores a new login challenge for the user, which has to be answered with a code to finish logging in.
+/// The user's expired challenges are removed.
+pub fn issue_login_challenge(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    challenge_user_id: i32,
+    challenge_device_label: String,
+) -> Result<TwoFactorChallenge, StatusCode> {
+    let challenge_token = generate_random_secure_key();
+    let now = Utc::now().naive_utc();
+    let challenge_expires_at = now + LOGIN_CHALLENGE_LIFETIME;
+
+    pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            delete(
+                challenges
+                    .filter(login_challenges::user_id.eq(challenge_user_id))
+                    .filter(login_challenges::expires_at.le(now)),
+            )
+            .execute(pg_connection)?;
+
+            insert_into(challenges)
+                .values(&NewLoginChallenge {
+                    user_id: challenge_user_id,
+                    challenge_hash: hash_secure_key(&challenge_token),
+                    device_label: challenge_device_label,
+                    expires_at: challenge_expires_at,
+                })
+                .execute(pg_connection)?;
+
+            Ok(())
+        })
+        .map_err(|err| {
+            error!("An error occured while storing a login challenge: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(TwoFactorChallenge {
+        challenge_token: encode_secure_key(&challenge_token),
+        expires_at: challenge_expires_at,
+    })
+}
+
+/// Checks the code against the user's TOTP secret, or if it is not a TOTP code, against the user's unused recovery codes.
+/// Accepted TOTP codes cannot be used again, and accepted recovery codes are deleted.
+pub fn verify_second_factor(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    user_account: &UserAccountEntry,
+    code: &str,
+) -> Result<bool, StatusCode> {
+    let code = code.trim();
+
+    if code.len() == 6 && code.bytes().all(|byte| byte.is_ascii_digit()) {
+        let Some(totp_secret) = user_account.totp_secret.clone() else {
+            return Ok(false);
+        };
+
+        let totp = create_totp(totp_secret, user_account.username.clone());
+
+        let Some(matched_step) = match_totp_code(&totp, code) else {
+            return Ok(false);
+        };
+
+        // Only accept codes newer than the last accepted one, so that an observed code cannot be replayed
+        let updated_rows = update(
+            user_accounts.filter(users::id.eq(user_account.id)).filter(
+                users::totp_last_used_step
+                    .is_null()
+                    .or(users::totp_last_used_step.lt(matched_step)),
+            ),
+        )
+        .set(users::totp_last_used_step.eq(Some(matched_step)))
+        .execute(pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while storing the last used TOTP step: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        return Ok(updated_rows == 1);
+    }
+
+    let deleted_rows = delete(
+        recovery_codes
+            .filter(totp_recovery_codes::user_id.eq(user_account.id))
+            .filter(
+                totp_recovery_codes::code_hash
+                    .eq(hash_secure_key(normalize_recovery_code(code).as_bytes())),
+            ),
+    )
+    .execute(pg_connection)
+    .map_err(|err| {
+        error!("An error occured while redeeming a recovery code: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(deleted_rows == 1)
+}
+
+/// Returns the time step the code belongs to, if it is valid for the current, previous or next step.
+fn match_totp_code(totp: &TOTP, code: &str) -> Option<i64> {
+    let unix_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+
+    match_totp_code_at(totp, code, unix_time)
+}
+
+/// Returns the time step the code belongs to, if it is valid for the step of `unix_time` or the ones next to it.
+fn match_totp_code_at(totp: &TOTP, code: &str, unix_time: u64) -> Option<i64> {
+    let current_step = unix_time / TOTP_STEP;
+
+    // Allow one step of clock drift in both directions
+    (current_step.saturating_sub(1)..=current_step + 1)
+        .find(|step| {
+            constant_time_eq(
+                totp.generate(step * TOTP_STEP).as_bytes(),
+                code.trim().as_bytes(),
+            )
+        })
+        .map(|step| step as i64)
+}
+
+fn create_totp(totp_secret: Vec<u8>, account_name: String) -> TOTP {
+    TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        1,
+        TOTP_STEP,
+        totp_secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name,
+    )
+}
+
+/// Generates a recovery code in the `xxxxx-xxxxx` format.
+fn generate_recovery_code() -> String {
+    let mut rng = rng();
+
+    let characters: String = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.random_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+
+    format!("{}-{}", &characters[..5], &characters[5..])
+}
+
+/// Removes the formatting of a recovery code, so that it is accepted however the user has typed it.
+fn normalize_recovery_code(recovery_code: &str) -> String {
+    recovery_code
+        .chars()
+        .filter(|character| character.is_ascii_alphanumeric())
+        .map(|character| character.to_ascii_lowercase())
+        .collect()
+}
+
+fn fetch_user_account(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    account_id: i32,
+) -> Result<UserAccountEntry, StatusCode> {
+    user_accounts
+        .filter(users::id.eq(account_id))
+        .get_result::<UserAccountEntry>(pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching user account with id {}: {}",
+                account_id, err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The shared secret of the SHA1 test vectors in RFC 6238, appendix B.
+    const RFC_6238_SECRET: &[u8] = b"12345678901234567890";
+
+    fn rfc_6238_totp() -> TOTP {
+        create_totp(RFC_6238_SECRET.to_vec(), String::from("alice"))
+    }
+
+    #[test]
+    fn generates_rfc_6238_codes() {
+        // The RFC lists 8 digit codes, 6 digit codes are the same value truncated to the last 6 digits
+        let test_vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+
+        let totp = rfc_6238_totp();
+
+        for (unix_time, code) in test_vectors {
+            assert_eq!(totp.generate(unix_time), code);
+            assert_eq!(
+                match_totp_code_at(&totp, code, unix_time),
+                Some((unix_time / TOTP_STEP) as i64)
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_one_step_of_clock_drift() {
+        let totp = rfc_6238_totp();
+        let unix_time = 1111111111;
+        let current_step = unix_time / TOTP_STEP;
+
+        for step in [current_step - 1, current_step, current_step + 1] {
+            let code = totp.generate(step * TOTP_STEP);
+
+            assert_eq!(
+                match_totp_code_at(&totp, &code, unix_time),
+                Some(step as i64)
+            );
+        }
+
+        for step in [current_step - 2, current_step + 2] {
+            let code = totp.generate(step * TOTP_STEP);
+
+            assert_eq!(match_totp_code_at(&totp, &code, unix_time), None);
+        }
+    }
+
+    #[test]
+    fn reports_the_step_of_the_code_for_replay_protection() {
+        let totp = rfc_6238_totp();
+        let unix_time = 1111111111;
+        let current_step = unix_time / TOTP_STEP;
+        let code = totp.generate(unix_time);
+
+        // A code keeps matching the same step for as long as it is accepted, so storing the step rejects the code afterwards
+        assert_eq!(
+            match_totp_code_at(&totp, &code, unix_time),
+            Some(current_step as i64)
+        );
+        assert_eq!(
+            match_totp_code_at(&totp, &code, unix_time + TOTP_STEP),
+            Some(current_step as i64)
+        );
+        assert_eq!(
+            match_totp_code_at(&totp, &format!(" {code}\n"), unix_time),
+            Some(current_step as i64)
+        );
+        assert_eq!(match_totp_code_at(&totp, "000000", unix_time), None);
+    }
+
+    #[test]
+    fn normalizes_recovery_codes() {
+        assert_eq!(normalize_recovery_code("abcde-fghjk"), "abcdefghjk");
+        assert_eq!(normalize_recovery_code(" ABCDE FGHJK "), "abcdefghjk");
+        assert_eq!(normalize_recovery_code("abcdefghjk"), "abcdefghjk");
+
+        let recovery_code = generate_recovery_code();
+
+        assert_eq!(recovery_code.len(), 11);
+        assert_eq!(
+            normalize_recovery_code(&recovery_code.to_uppercase()),
+            recovery_code.replace('-', "")
+        );
+    }
+}
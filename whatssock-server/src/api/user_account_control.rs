@@ -1,31 +1,83 @@
+use crate::api::authentication::AuthenticatedUser;
+use crate::api::email_verification::send_verification_email;
+use crate::api::two_factor::issue_login_challenge;
 use crate::api::user_account_control::users::dsl::users;
 use crate::models::{
     NewUserAccount, NewUserSession, UpdateLastMessage, UserAccountEntry, UserSessionEntry,
 };
 use crate::schema::chatrooms::dsl::chatrooms;
+use crate::schema::password_reset_tokens::dsl::password_reset_tokens;
 use crate::schema::user_session_auth::dsl::user_session_auth;
-use crate::schema::user_session_auth::{session_token, user_id};
+use crate::schema::user_session_auth::{
+    expires_at, last_seen, session_token, token_id, user_id,
+};
+use crate::rate_limit::{RateLimitKey, RateLimitedError};
 use crate::schema::users::{id, passw, username};
 use crate::{
     ServerState,
     schema::{self, *},
 };
-use axum::{Json, extract::State, http::StatusCode};
-use diesel::dsl::{count_star, exists};
-use diesel::query_dsl::methods::{FilterDsl, SelectDsl};
+use argon2::password_hash::SaltString;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+};
+use axum::{
+    Json,
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+};
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use diesel::dsl::count_star;
 use diesel::{
-    ExpressionMethods, QueryResult, RunQueryDsl, SelectableHelper, delete, select, update,
+    BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+    SelectableHelper, delete, update,
 };
+use lettre::Address;
 use log::error;
 use rand::{Rng, rng};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::LazyLock;
 use whatssock_lib::{UserSession, UserSessionSecure};
-use whatssock_lib::client::{LoginRequest, RegisterRequest, UserSessionInformation};
-use whatssock_lib::server::{LoginResponse, LoginResponseSecure, LogoutResponse};
+use whatssock_lib::client::{
+    ChangePasswordRequest, LoginRequest, RegisterRequest, RevokeSessionRequest, UserSessionInformation,
+};
+use whatssock_lib::server::{
+    ActiveSession, ActiveSessionsResponse, ChangePasswordResponse, LoginOutcome, LoginResponseSecure, LogoutResponse,
+    RefreshSessionResponse, RevokeSessionResponse,
+};
+
+/// How long a session token stays valid after being issued or refreshed.
+pub const SESSION_TOKEN_LIFETIME: TimeDelta = TimeDelta::hours(24);
+
+/// Sessions which have not been used for this long are rejected, even if their token has not expired yet.
+pub const SESSION_IDLE_TIMEOUT: TimeDelta = TimeDelta::days(7);
+
+/// The absolute lifetime of a session, refreshing the token cannot keep a session alive for longer than this.
+pub const SESSION_MAX_LIFETIME: TimeDelta = TimeDelta::days(30);
+
+/// A hash which is verified against when the username does not exist, so that unknown usernames take as long to reject as wrong passwords.
+static DUMMY_PASSWORD_HASH: LazyLock<String> = LazyLock::new(|| {
+    Argon2::default()
+        .hash_password(b"whatssock-dummy-password", &SaltString::generate(&mut OsRng))
+        .expect("Hashing the dummy password must succeed")
+        .to_string()
+});
 
 pub async fn fetch_login(
     State(state): State<ServerState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     Json(information): Json<LoginRequest>,
-) -> Result<Json<LoginResponseSecure>, StatusCode> {
+) -> Result<Json<LoginOutcome>, RateLimitedError> {
+    let rate_limit_keys = [
+        RateLimitKey::Ip(remote_addr.ip()),
+        RateLimitKey::Username(information.username.clone()),
+    ];
+
+    // Reject the request early if the address or the account is locked out
+    state.login_rate_limiter.check(&rate_limit_keys)?;
+
     let mut pg_connection = state.pg_pool.get().map_err(|err| {
         error!(
             "An error occured while fetching login information from db: {}",
@@ -37,94 +89,112 @@ pub async fn fetch_login(
 
     let user_account = users
         .filter(username.eq(information.username.clone()))
-        .filter(passw.eq(information.password))
         .select(UserAccountEntry::as_select())
         .get_result(&mut pg_connection)
-        .map_err(|err| {
-            error!(
-                "An error occured while searching for the user's account: {}",
-                err
-            );
+        .map_err(|err| match err {
+            // An unknown username counts as a failed attempt, but the db failing does not
+            diesel::result::Error::NotFound => {
+                state.login_rate_limiter.record_attempt(&rate_limit_keys);
+
+                // Spend the same time as checking a real password, otherwise the response time would reveal which usernames exist
+                let _ = verify_password(&information.password, &DUMMY_PASSWORD_HASH);
 
-            StatusCode::NOT_FOUND
+                StatusCode::NOT_FOUND
+            }
+            err => {
+                error!(
+                    "An error occured while searching for the user's account: {}",
+                    err
+                );
+
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
         })?;
 
-    // Issue a new session token for future logins
-    let session_cookie_token = generate_random_secure_key();
-    
-    // Issue a new encryption key for communication between the client and the server
-    let encryption_key = generate_random_secure_key();
+    // Check the password, a mismatch is reported the same way as a missing user.
+    match verify_password(&information.password, &user_account.passw) {
+        PasswordVerification::Valid => (),
+        PasswordVerification::NeedsRehash => {
+            // The stored password is either plaintext or was hashed with outdated parameters, replace it with a fresh hash.
+            let rehashed_password = hash_password(&information.password)?;
+
+            diesel::update(users.filter(id.eq(user_account.id)))
+                .set(passw.eq(rehashed_password))
+                .execute(&mut pg_connection)
+                .map_err(|err| {
+                    error!(
+                        "An error occured while updating the user's password hash: {}",
+                        err
+                    );
+
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+        }
+        PasswordVerification::Invalid => {
+            state.login_rate_limiter.record_attempt(&rate_limit_keys);
 
-    let user_session_count = user_session_auth
-        .filter(user_id.eq(user_account.id))
-        .select(count_star())
-        .first::<i64>(&mut pg_connection)
-        .map_err(|err| {
+            return Err(StatusCode::NOT_FOUND.into());
+        }
+    }
+
+    // The session is only issued once the second factor has been submitted too.
+    // The account's failed attempts are not forgotten yet, otherwise logging in with the password would reset the attempts spent guessing codes.
+    if user_account.totp_enabled {
+        let challenge =
+            issue_login_challenge(&mut pg_connection, user_account.id, information.device_label)?;
+
+        return Ok(Json(LoginOutcome::TwoFactorRequired(challenge)));
+    }
+
+    // The account's failed attempts are forgotten after a successful login, the address' attempts still expire on their own
+    state.login_rate_limiter.reset(&rate_limit_keys[1]);
+
+    // Every login gets its own session, so that other devices stay logged in
+    let user_session_secure =
+        issue_user_session(&mut pg_connection, user_account.id, information.device_label)?;
+
+    let chatrooms_joined =
+        lookup_joined_chatrooms(&mut pg_connection, user_account.id).map_err(|err| {
             error!(
-                "An error occured while searching for the user's session token: {}",
+                "An error occured while fetching the user's chatrooms from db: {}",
                 err
             );
 
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
-    // Check if there are any existing user sessions
-    // If there arent this means some sort of issue has occured, thus the session has been invalidated or deleted.
-    if user_session_count != 0 {
-        // Search up a session token for the user, if it exists update it
-        diesel::update(user_session_auth)
-            .filter(user_id.eq(user_account.id))
-            .set(&NewUserSession {
-                user_id: user_account.id,
-                // Issue a new session token for future logins
-                session_token: session_cookie_token.clone().to_vec(),
-                encryption_key: encryption_key.clone().to_vec()
-            })
-            .get_result::<UserSessionEntry>(&mut pg_connection)
-            .map_err(|err| {
-                error!(
-                    "An error occured while searching for the user's session token: {}",
-                    err
-                );
-
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-    } else {
-        diesel::insert_into(user_session_auth)
-            .values(&NewUserSession {
-                user_id: user_account.id,
-                // Issue a new session token for future logins
-                session_token: session_cookie_token.clone().to_vec(),
-                encryption_key: encryption_key.clone().to_vec()
-            })
-            .get_result::<UserSessionEntry>(&mut pg_connection)
-            .map_err(|err| {
-                error!(
-                    "An error occured while fetching login information from db: {}",
-                    err
-                );
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-    }
-
-    Ok(Json(LoginResponseSecure {
+    Ok(Json(LoginOutcome::LoggedIn(LoginResponseSecure {
         user_information: UserSessionInformation {
             username: user_account.username,
-            chatrooms_joined: user_account.chatrooms_joined,
+            chatrooms_joined,
             user_id: user_account.id,
+            email_verified: user_account.email_verified,
+            totp_enabled: user_account.totp_enabled,
+            discoverable: user_account.discoverable,
         },
-        user_session_secure: UserSessionSecure {
-            user_id: user_account.id,
-            session_token: session_cookie_token,
-            encryption_key: encryption_key,
-        },
-    }))
+        user_session_secure,
+    })))
 }
 
 pub async fn register_user(
     State(state): State<ServerState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     Json(information): Json<RegisterRequest>,
-) -> Result<Json<LoginResponseSecure>, StatusCode> {
+) -> Result<Json<LoginResponseSecure>, RateLimitedError> {
+    let rate_limit_keys = [
+        RateLimitKey::Ip(remote_addr.ip()),
+        RateLimitKey::Username(information.username.clone()),
+    ];
+
+    // Every registration attempt counts, so that accounts cannot be mass created and usernames cannot be probed
+    state.register_rate_limiter.check(&rate_limit_keys)?;
+    state.register_rate_limiter.record_attempt(&rate_limit_keys);
+
+    // The verification code is sent to this address, so it has to be one the mailer can deliver to
+    if information.email.parse::<Address>().is_err() {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+
     let mut pg_connection = state.pg_pool.get().map_err(|err| {
         error!(
             "An error occured while fetching login information from db: {}",
@@ -148,15 +218,14 @@ pub async fn register_user(
         })?;
 
     if user_count != 0 {
-        return Err(StatusCode::FOUND);
+        return Err(StatusCode::FOUND.into());
     }
 
     // Insert the user's register information into the DB
     let user_account = diesel::insert_into(users)
         .values(&NewUserAccount {
             username: information.username.clone(),
-            passw: information.password,
-            chatrooms_joined: vec![],
+            passw: hash_password(&information.password)?,
             email: information.email,
         })
         .get_result::<UserAccountEntry>(&mut pg_connection)
@@ -168,47 +237,38 @@ pub async fn register_user(
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
-    // Issue a new session token for future logins
-    let session_cookie_token = generate_random_secure_key();
-    
-    // Issue a new encryption key for communication between the client and the server
-    let encryption_key = generate_random_secure_key();
+    // The account stays unverified until the code sent to its email address is submitted.
+    // The account has already been created at this point, if the code could not be issued the user can request a new one later.
+    if let Err(status_code) =
+        send_verification_email(state.mailer.clone(), &mut pg_connection, &user_account)
+    {
+        error!(
+            "An error occured while sending the verification email to user {}: {}",
+            user_account.id, status_code
+        );
+    }
 
-    // Store the session token in the db, there is no way of having another session token for this user as we have just created it.
-    diesel::insert_into(user_session_auth)
-        .values(&NewUserSession {
-            user_id: user_account.id,
-            // Issue a new session token for future logins
-            session_token: session_cookie_token.clone().to_vec(),
-            // Issue a new encryption key for communication between the client and the server
-            encryption_key: encryption_key.clone().to_vec(),
-        })
-        .get_result::<UserSessionEntry>(&mut pg_connection)
-        .map_err(|err| {
-            error!(
-                "An error occured while fetching login information from db: {}",
-                err
-            );
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    // Create the first session of the user
+    let user_session_secure =
+        issue_user_session(&mut pg_connection, user_account.id, information.device_label)?;
 
     Ok(Json(LoginResponseSecure {
-        user_session_secure: UserSessionSecure {
-            user_id: user_account.id,
-            session_token: session_cookie_token,
-            encryption_key: encryption_key,
-        },
+        user_session_secure,
         user_information: UserSessionInformation {
             username: user_account.username,
-            chatrooms_joined: user_account.chatrooms_joined,
+            // New accounts have not joined any chatrooms yet
+            chatrooms_joined: Vec::new(),
             user_id: user_account.id,
+            email_verified: user_account.email_verified,
+            totp_enabled: user_account.totp_enabled,
+            discoverable: user_account.discoverable,
         },
     }))
 }
 
 pub async fn fetch_user_information_from_session(
     State(state): State<ServerState>,
-    Json(user_session): Json<UserSession>,
+    authenticated_user: AuthenticatedUser,
 ) -> Result<Json<UserSessionInformation>, StatusCode> {
     // Get a db connection from the pool
     let mut pg_connection = state.pg_pool.get().map_err(|err| {
@@ -220,11 +280,8 @@ pub async fn fetch_user_information_from_session(
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    // Verify user session, this will return an error if the session is not found in the DB.
-    verify_user_session(&user_session, &mut pg_connection)?;
-
     let user_account = users
-        .filter(id.eq(user_session.user_id))
+        .filter(id.eq(authenticated_user.user_id))
         .select(UserAccountEntry::as_select())
         .first::<UserAccountEntry>(&mut pg_connection)
         .map_err(|err| {
@@ -235,16 +292,29 @@ pub async fn fetch_user_information_from_session(
             StatusCode::REQUEST_TIMEOUT
         })?;
 
+    let chatrooms_joined =
+        lookup_joined_chatrooms(&mut pg_connection, user_account.id).map_err(|err| {
+            error!(
+                "An error occured while fetching the user's chatrooms from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
     Ok(Json(UserSessionInformation {
         username: user_account.username,
-        chatrooms_joined: user_account.chatrooms_joined,
+        chatrooms_joined,
         user_id: user_account.id,
+        email_verified: user_account.email_verified,
+        totp_enabled: user_account.totp_enabled,
+        discoverable: user_account.discoverable,
     }))
 }
 
 pub async fn handle_logout_request(
     State(state): State<ServerState>,
-    Json(session_cookie): Json<UserSession>,
+    authenticated_user: AuthenticatedUser,
 ) -> Result<Json<LogoutResponse>, StatusCode> {
     // Get a db connection from the pool
     let mut pg_connection = state.pg_pool.get().map_err(|err| {
@@ -256,20 +326,298 @@ pub async fn handle_logout_request(
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    match delete(user_session_auth.filter(session_token.eq(session_cookie.session_token)))
+    // Only the session which has sent the request is logged out, the user's other devices stay logged in.
+    delete(user_session_auth.filter(token_id.eq(authenticated_user.session.token_id)))
         .execute(&mut pg_connection)
+        .map_err(|err| {
+            error!("An error occured while deleting the user's session: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(LogoutResponse {}))
+}
+
+pub async fn refresh_user_session(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+) -> Result<Json<RefreshSessionResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let current_session = authenticated_user.session;
+
+    // Rotate the session token, the old one stops working immediately
+    let new_session_token = generate_random_secure_key();
+    let new_expiry = session_expiry(current_session.created_at, Utc::now().naive_utc());
+
+    diesel::update(user_session_auth.filter(token_id.eq(current_session.token_id)))
+        .set((
+            session_token.eq(new_session_token.to_vec()),
+            expires_at.eq(new_expiry),
+        ))
+        .execute(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while refreshing the user's session: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(RefreshSessionResponse {
+        user_session: UserSession {
+            user_id: current_session.user_id,
+            session_token: new_session_token,
+        },
+        expires_at: new_expiry,
+    }))
+}
+
+pub async fn fetch_active_sessions(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+) -> Result<Json<ActiveSessionsResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let current_session = authenticated_user.session;
+
+    let session_entries = user_session_auth
+        .filter(user_id.eq(current_session.user_id))
+        .order(last_seen.desc())
+        .select(UserSessionEntry::as_select())
+        .load::<UserSessionEntry>(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching the user's sessions from db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(ActiveSessionsResponse {
+        sessions: session_entries
+            .into_iter()
+            .map(|session_entry| ActiveSession {
+                session_id: session_entry.token_id,
+                device_label: session_entry.device_label,
+                created_at: session_entry.created_at,
+                last_seen: session_entry.last_seen,
+                is_current: session_entry.token_id == current_session.token_id,
+            })
+            .collect(),
+    }))
+}
+
+pub async fn revoke_session(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(revoke_request): Json<RevokeSessionRequest>,
+) -> Result<Json<RevokeSessionResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Filter for the user id too, so that users can only revoke their own sessions
+    let revoked_count = delete(
+        user_session_auth
+            .filter(user_id.eq(authenticated_user.user_id))
+            .filter(token_id.eq(revoke_request.session_id)),
+    )
+    .execute(&mut pg_connection)
+    .map_err(|err| {
+        error!("An error occured while revoking the user's session: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if revoked_count == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Json(RevokeSessionResponse { revoked_count }))
+}
+
+pub async fn revoke_other_sessions(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+) -> Result<Json<RevokeSessionResponse>, StatusCode> {
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Delete every session of the user except for the one this request was sent with
+    let revoked_count = delete(
+        user_session_auth
+            .filter(user_id.eq(authenticated_user.user_id))
+            .filter(token_id.ne(authenticated_user.session.token_id)),
+    )
+    .execute(&mut pg_connection)
+    .map_err(|err| {
+        error!("An error occured while revoking the user's sessions: {}", err);
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(RevokeSessionResponse { revoked_count }))
+}
+
+pub async fn change_password(
+    State(state): State<ServerState>,
+    authenticated_user: AuthenticatedUser,
+    Json(change_request): Json<ChangePasswordRequest>,
+) -> Result<Json<ChangePasswordResponse>, RateLimitedError> {
+    if change_request.new_password.is_empty() {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+
+    // Get a db connection from the pool
+    let mut pg_connection = state.pg_pool.get().map_err(|err| {
+        error!(
+            "An error occured while fetching login information from db: {}",
+            err
+        );
+
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let user_account = users
+        .filter(id.eq(authenticated_user.user_id))
+        .select(UserAccountEntry::as_select())
+        .get_result(&mut pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while fetching user account with id {}: {}",
+                authenticated_user.user_id, err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // Wrong current passwords count as failed logins, so that a stolen session cannot be used to guess the password
+    let rate_limit_keys = [RateLimitKey::Username(user_account.username.clone())];
+
+    state.login_rate_limiter.check(&rate_limit_keys)?;
+
+    if verify_password(&change_request.current_password, &user_account.passw)
+        == PasswordVerification::Invalid
     {
-        Ok(r_affected) => {
-            dbg!(r_affected);
-        }
-        Err(err) => {
-            error!("{err}");
+        state.login_rate_limiter.record_attempt(&rate_limit_keys);
 
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        return Err(StatusCode::FORBIDDEN.into());
     }
 
-    Ok(Json(LogoutResponse {}))
+    let new_password_hash = hash_password(&change_request.new_password)?;
+
+    // Every other session is logged out, the one which has changed the password stays logged in
+    let revoked_count = pg_connection
+        .transaction::<_, diesel::result::Error, _>(|pg_connection| {
+            diesel::update(users.filter(id.eq(user_account.id)))
+                .set(passw.eq(new_password_hash))
+                .execute(pg_connection)?;
+
+            delete(password_reset_tokens.filter(schema::password_reset_tokens::user_id.eq(user_account.id)))
+                .execute(pg_connection)?;
+
+            delete(
+                user_session_auth
+                    .filter(user_id.eq(user_account.id))
+                    .filter(token_id.ne(authenticated_user.session.token_id)),
+            )
+            .execute(pg_connection)
+        })
+        .map_err(|err| {
+            error!(
+                "An error occured while changing the user's password: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(ChangePasswordResponse { revoked_count }))
+}
+
+/// Creates a new session for the user on the device given, and stores it in the db.
+/// The returned [`UserSessionSecure`] contains the freshly issued session token and encryption key.
+pub fn issue_user_session(
+    pg_connection: &mut r2d2::PooledConnection<
+        diesel::r2d2::ConnectionManager<diesel::PgConnection>,
+    >,
+    session_user_id: i32,
+    session_device_label: String,
+) -> Result<UserSessionSecure, StatusCode> {
+    // Issue a new session token for future logins
+    let session_cookie_token = generate_random_secure_key();
+
+    // Issue a new encryption key for communication between the client and the server
+    let encryption_key = generate_random_secure_key();
+
+    // The timestamps are set here instead of relying on the db's defaults, the session checks compare them against the server's UTC time
+    let now = Utc::now().naive_utc();
+    let session_expires_at = session_expiry(now, now);
+
+    diesel::insert_into(user_session_auth)
+        .values(&NewUserSession {
+            user_id: session_user_id,
+            session_token: session_cookie_token.to_vec(),
+            encryption_key: encryption_key.to_vec(),
+            device_label: session_device_label,
+            created_at: now,
+            last_seen: now,
+            expires_at: session_expires_at,
+        })
+        .get_result::<UserSessionEntry>(pg_connection)
+        .map_err(|err| {
+            error!(
+                "An error occured while storing the user's session in db: {}",
+                err
+            );
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(UserSessionSecure {
+        user_id: session_user_id,
+        session_token: session_cookie_token,
+        encryption_key,
+        expires_at: session_expires_at,
+    })
+}
+
+/// Calculates when a session token issued at `issued_at` should expire.
+/// The token is valid for [`SESSION_TOKEN_LIFETIME`], but never past [`SESSION_MAX_LIFETIME`] counted from the session's creation.
+pub fn session_expiry(session_created_at: NaiveDateTime, issued_at: NaiveDateTime) -> NaiveDateTime {
+    (issued_at + SESSION_TOKEN_LIFETIME).min(session_created_at + SESSION_MAX_LIFETIME)
 }
 
 pub fn generate_random_secure_key() -> [u8; 32] {
@@ -282,8 +630,85 @@ pub fn generate_random_secure_key() -> [u8; 32] {
     custom_identifier
 }
 
+/// Hashes a single-use key before it is stored, so that a leaked db cannot be used to redeem the keys sent out in emails.
+pub fn hash_secure_key(key: &[u8]) -> Vec<u8> {
+    Sha256::digest(key).to_vec()
+}
+
+/// The outcome of checking a password against the value stored in `users.passw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordVerification {
+    /// The password matches the stored Argon2id hash.
+    Valid,
+    /// The password matches, but the stored value is plaintext or uses outdated parameters and should be replaced.
+    NeedsRehash,
+    /// The password does not match.
+    Invalid,
+}
+
+/// Hashes the password with Argon2id and a random salt, returning the PHC string which is stored in the db.
+pub fn hash_password(password: &str) -> Result<String, StatusCode> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|password_hash| password_hash.to_string())
+        .map_err(|err| {
+            error!("An error occured while hashing a password: {}", err);
+
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Verifies the password against the value stored in the db.
+/// Rows which were created before passwords were hashed still contain the plaintext password, these are compared in constant time and flagged for rehashing.
+pub fn verify_password(password: &str, stored_password: &str) -> PasswordVerification {
+    let Ok(password_hash) = PasswordHash::new(stored_password) else {
+        // The stored value is not a PHC string, so it must be a legacy plaintext password.
+        return if constant_time_eq(password.as_bytes(), stored_password.as_bytes()) {
+            PasswordVerification::NeedsRehash
+        } else {
+            PasswordVerification::Invalid
+        };
+    };
+
+    let argon2 = Argon2::default();
+
+    if argon2
+        .verify_password(password.as_bytes(), &password_hash)
+        .is_err()
+    {
+        return PasswordVerification::Invalid;
+    }
+
+    // Check if the hash was created with the parameters we are currently using
+    let is_up_to_date = password_hash.algorithm == Algorithm::Argon2id.ident()
+        && password_hash.version == Some(Version::default().into())
+        && Params::try_from(&password_hash).is_ok_and(|params| &params == argon2.params());
+
+    if is_up_to_date {
+        PasswordVerification::Valid
+    } else {
+        PasswordVerification::NeedsRehash
+    }
+}
+
+/// Compares two byte slices without returning early on the first mismatching byte.
+pub fn constant_time_eq(lhs: &[u8], rhs: &[u8]) -> bool {
+    if lhs.len() != rhs.len() {
+        return false;
+    }
+
+    lhs.iter()
+        .zip(rhs.iter())
+        .fold(0_u8, |acc, (l, r)| acc | (l ^ r))
+        == 0
+}
+
 /// Checks the [`UserSession`] passed in.
 /// The function checks for the session token and the id. If either one of them dont match, it will return [`StatusCode::FORBIDDEN`].
+/// Sessions whose token has expired or which have been idle for longer than [`SESSION_IDLE_TIMEOUT`] are rejected the same way.
+/// Every successful verification also refreshes the session's `last_seen` timestamp.
 pub fn verify_user_session(
     // The UserSession which is checked
     user_session: &UserSession,
@@ -292,17 +717,39 @@ pub fn verify_user_session(
         diesel::r2d2::ConnectionManager<diesel::PgConnection>,
     >,
 ) -> Result<UserSessionEntry, StatusCode> {
-    let user_session: UserSessionEntry = 
+    let now = Utc::now().naive_utc();
+
+    let verified_session = diesel::update(
         user_session_auth
             .filter(schema::user_session_auth::user_id.eq(user_session.user_id))
             .filter(schema::user_session_auth::session_token.eq(user_session.session_token))
-            .select(UserSessionEntry::as_select())
-            .get_result::<UserSessionEntry>(pg_connection)
-            .map_err(|_err| {
-                StatusCode::FORBIDDEN
-            })?;
-
-    Ok(user_session)
+            .filter(expires_at.gt(now))
+            .filter(last_seen.gt(now - SESSION_IDLE_TIMEOUT)),
+    )
+    .set(last_seen.eq(now))
+    .get_result::<UserSessionEntry>(pg_connection);
+
+    match verified_session {
+        Ok(session_entry) => Ok(session_entry),
+        Err(_err) => {
+            // Clean up the user's sessions which can never be used again
+            if let Err(err) = delete(
+                user_session_auth
+                    .filter(schema::user_session_auth::user_id.eq(user_session.user_id))
+                    .filter(
+                        expires_at
+                            .le(now)
+                            .or(last_seen.le(now - SESSION_IDLE_TIMEOUT)),
+                    ),
+            )
+            .execute(pg_connection)
+            {
+                error!("An error occured while removing expired sessions: {}", err);
+            }
+
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
 }
 
 pub fn lookup_joined_chatrooms(
@@ -311,12 +758,15 @@ pub fn lookup_joined_chatrooms(
         diesel::r2d2::ConnectionManager<diesel::PgConnection>,
     >,
     user_uid: i32,
-) -> QueryResult<Vec<Option<i32>>> {
-    users
-        .filter(id.eq(user_uid))
-        .select(UserAccountEntry::as_select())
-        .get_result(pg_connection)
-        .map(|entry| entry.chatrooms_joined)
+) -> QueryResult<Vec<i32>> {
+    chatroom_members::table
+        .filter(chatroom_members::user_id.eq(user_uid))
+        .order((
+            chatroom_members::joined_at.asc(),
+            chatroom_members::chatroom_id.asc(),
+        ))
+        .select(chatroom_members::chatroom_id)
+        .load(pg_connection)
 }
 
 pub fn update_chatroom_last_msg(
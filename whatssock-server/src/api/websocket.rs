@@ -18,12 +18,14 @@ use tokio::{
     },
 };
 use tokio_util::sync::CancellationToken;
-use whatssock_lib::{UserSession, server::WebSocketChatroomMessageServer};
+use whatssock_lib::{
+    UserSession, client::WebSocketChatroomEventClient, server::WebSocketChatroomMessageServer,
+};
 
 use crate::{
     ServerState,
     api::{
-        chatrooms::handle_incoming_chatroom_message,
+        chatrooms::{IncomingMessageError, handle_incoming_chatroom_message},
         user_account_control::{lookup_joined_chatrooms, verify_user_session},
     },
 };
@@ -64,19 +66,11 @@ pub async fn handle_socket(state: State<ServerState>, socket: WebSocket, remote_
                 DashMap<i32, DashMap<i32, mpsc::Sender<Message>>>,
             > = state.chatroom_subscriptions.clone();
 
-            let mut joined_chatroom_ids = Vec::new();
-
             // Get which chatrooms the user is present in
             match lookup_joined_chatrooms(&mut pg_connection, user_session.user_id) {
                 Ok(joined_chatrooms) => {
                     // Automaticly subscribe to the chatrooms which the user has joined
-                    for joined_chatroom in joined_chatrooms {
-                        // We can safely unwrap here, the option is just a weird trait of diesel
-                        let chatroom_id = joined_chatroom.unwrap();
-
-                        // Store the user's joined classrooms'
-                        joined_chatroom_ids.push(chatroom_id);
-
+                    for chatroom_id in joined_chatrooms {
                         if currently_available_chatroom_handlers
                             .get(&chatroom_id)
                             .is_none()
@@ -110,12 +104,12 @@ pub async fn handle_socket(state: State<ServerState>, socket: WebSocket, remote_
             let curr_open_conn: Arc<dashmap::DashSet<SocketAddr>> =
                 state.currently_open_connections.clone();
             let curr_open_conn_clone = curr_open_conn.clone();
+            let connected_users_handle = state.connected_users.clone();
 
             // Dont allow multiple ws connections from the same address
             if !curr_open_conn.insert(remote_addr) {
                 error!("Remote: {remote_addr} tried to open two or more connections.");
                 disconnect_user_from_server(
-                    joined_chatroom_ids,
                     user_session.user_id,
                     remote_addr,
                     chatroom_subscriptions_handle.clone(),
@@ -125,6 +119,16 @@ pub async fn handle_socket(state: State<ServerState>, socket: WebSocket, remote_
                 return;
             };
 
+            // Store the user's connection, so that the chatrooms joined from now on can be subscribed to aswell
+            connected_users_handle
+                .insert(user_session.user_id, client_thread_sender_handle.clone());
+
+            // Rejected messages are reported back to the sender only
+            let client_rejection_handle = client_thread_sender_handle.clone();
+
+            // Used to tell apart this connection's entry in the connected users from the ones of newer connections
+            let client_connection_handle = client_thread_sender_handle.clone();
+
             // Spawn client receiver thread
             spawn(async move {
                 while let Some(msg) = reader.next().await {
@@ -147,14 +151,28 @@ pub async fn handle_socket(state: State<ServerState>, socket: WebSocket, remote_
                         .await
                         {
                             Ok(relayed_msg) => relayed_msg,
-                            Err(err) => {
+                            // The message was refused, but the connection is kept open
+                            Err(IncomingMessageError::Rejected(reason)) => {
+                                let rejection = WebSocketChatroomEventClient::MessageRejected {
+                                    chatroom_uid: ws_msg.sent_to,
+                                    reason,
+                                };
+
+                                let _ = client_rejection_handle
+                                    .send(Message::Binary(
+                                        rmp_serde::to_vec(&rejection).unwrap().into(),
+                                    ))
+                                    .await;
+
+                                continue;
+                            }
+                            Err(IncomingMessageError::Failed(err)) => {
                                 error!(
                                     "Error: `{err}` occured when trying to process incoming message from: `{}`. Quitting handler thread...",
                                     ws_msg.message_owner_session.user_id
                                 );
 
                                 disconnect_user_from_server(
-                                    joined_chatroom_ids,
                                     user_session.user_id,
                                     remote_addr,
                                     chatroom_subscriptions_handle.clone(),
@@ -179,7 +197,6 @@ pub async fn handle_socket(state: State<ServerState>, socket: WebSocket, remote_
                             );
 
                             disconnect_user_from_server(
-                                joined_chatroom_ids,
                                 user_session.user_id,
                                 remote_addr,
                                 chatroom_subscriptions_handle.clone(),
@@ -192,14 +209,17 @@ pub async fn handle_socket(state: State<ServerState>, socket: WebSocket, remote_
 
                         chatroom_handler_sender
                             .send(Message::Binary(
-                                rmp_serde::to_vec(&relayed_message).unwrap().into(),
+                                rmp_serde::to_vec(&WebSocketChatroomEventClient::Message(
+                                    relayed_message,
+                                ))
+                                .unwrap()
+                                .into(),
                             ))
                             .unwrap();
                     }
                     // client disconnected
                     else {
                         disconnect_user_from_server(
-                            joined_chatroom_ids,
                             user_session.user_id,
                             remote_addr,
                             chatroom_subscriptions_handle.clone(),
@@ -210,6 +230,12 @@ pub async fn handle_socket(state: State<ServerState>, socket: WebSocket, remote_
                         break;
                     };
                 }
+
+                // The user cannot be subscribed to new chatrooms through this connection anymore
+                // The entry is only removed if it still belongs to this connection, a newer connection of the user may have replaced it already
+                connected_users_handle.remove_if(&user_session.user_id, |_, client_handle| {
+                    client_handle.same_channel(&client_connection_handle)
+                });
             });
 
             // Spawn client writer
@@ -251,8 +277,130 @@ pub fn subscribe_to_channel_handler(
     }
 }
 
+/// Subscribes the user's open WebSocket connection to the chatroom, this is a no-op if the user is not online.
+pub fn subscribe_connected_user(state: &ServerState, chatroom_id: i32, user_id: i32) {
+    let Some(client_handle) = state
+        .connected_users
+        .get(&user_id)
+        .map(|client_handle| client_handle.clone())
+    else {
+        return;
+    };
+
+    if state.currently_online_chatrooms.get(&chatroom_id).is_none() {
+        // Create chatroom handler if it doesnt exist yet
+        create_chatroom_handler(
+            state.chatroom_subscriptions.clone(),
+            state.currently_online_chatrooms.clone(),
+            chatroom_id,
+        );
+    }
+
+    subscribe_to_channel_handler(
+        chatroom_id,
+        user_id,
+        client_handle,
+        state.chatroom_subscriptions.clone(),
+    );
+}
+
+/// Removes the user's WebSocket connection from the chatroom's subscribers, the chatroom's handler is closed if no subscribers are left.
+pub fn unsubscribe_user_from_chatroom(state: &ServerState, chatroom_id: i32, user_id: i32) {
+    let Some(chatroom_subscribers) = state.chatroom_subscriptions.get(&chatroom_id) else {
+        return;
+    };
+
+    chatroom_subscribers.remove(&user_id);
+
+    let is_chatroom_empty = chatroom_subscribers.is_empty();
+
+    // Release the lock before modifying the map
+    drop(chatroom_subscribers);
+
+    if is_chatroom_empty {
+        state.chatroom_subscriptions.remove(&chatroom_id);
+
+        if let Some((_, (handler_cancel_token, _))) =
+            state.currently_online_chatrooms.remove(&chatroom_id)
+        {
+            // Cancel handler
+            handler_cancel_token.cancel();
+
+            // Log in console
+            info!("Removing chatroom: {chatroom_id} as there are no participants left.");
+        }
+    }
+}
+
+/// Sends the event to every online participant of the chatroom, this is a no-op if none of them are online.
+pub fn broadcast_chatroom_event(
+    state: &ServerState,
+    chatroom_id: i32,
+    event: &WebSocketChatroomEventClient,
+) {
+    let Some(chatroom_handler) = state.currently_online_chatrooms.get(&chatroom_id) else {
+        return;
+    };
+
+    // The broadcast only fails if the handler has no receivers left, in which case there is no one to notify
+    let _ = chatroom_handler
+        .1
+        .send(Message::Binary(rmp_serde::to_vec(event).unwrap().into()));
+}
+
+/// Sends the event to the user's connection directly instead of through the chatroom's handler, this is a no-op if the user is not subscribed to the chatroom.
+/// The handler delivers its events later, so users who are unsubscribed right after would never receive a broadcast event.
+pub fn send_chatroom_event_to_subscriber(
+    state: &ServerState,
+    chatroom_id: i32,
+    user_id: i32,
+    event: &WebSocketChatroomEventClient,
+) {
+    let Some(chatroom_subscribers) = state.chatroom_subscriptions.get(&chatroom_id) else {
+        return;
+    };
+
+    let Some(client_handle) = chatroom_subscribers.get(&user_id) else {
+        return;
+    };
+
+    if let Err(err) =
+        client_handle.try_send(Message::Binary(rmp_serde::to_vec(event).unwrap().into()))
+    {
+        error!("Error occured when sending to client `{user_id}` handler: {err}");
+    }
+}
+
+/// Sends the event to every online participant of the chatroom, then closes the chatroom's handler and drops every subscription to it.
+pub fn close_chatroom(state: &ServerState, chatroom_id: i32, event: &WebSocketChatroomEventClient) {
+    if let Some((_, (handler_cancel_token, _))) =
+        state.currently_online_chatrooms.remove(&chatroom_id)
+    {
+        // Cancel handler
+        handler_cancel_token.cancel();
+
+        // Log in console
+        info!("Removing chatroom: {chatroom_id} as it has been deleted.");
+    }
+
+    let Some((_, chatroom_subscribers)) = state.chatroom_subscriptions.remove(&chatroom_id) else {
+        return;
+    };
+
+    // The event is sent to the subscribers directly, as the handler has been closed already
+    let encoded_event = Message::Binary(rmp_serde::to_vec(event).unwrap().into());
+
+    for chatroom_subscriber in chatroom_subscribers.iter() {
+        if let Err(err) = chatroom_subscriber.value().try_send(encoded_event.clone()) {
+            error!(
+                "Error occured when sending to client `{}` handler: {err}",
+                chatroom_subscriber.key()
+            );
+        }
+    }
+}
+
 pub fn disconnect_user_from_server(
-    chatroom_ids: Vec<i32>,
     user_id: i32,
     remote_addr: SocketAddr,
     chatroom_subscriptions: Arc<
@@ -264,6 +412,14 @@ pub fn disconnect_user_from_server(
     // Remove the user's remote address from the open addresses.
     curr_open_conn.remove(&remote_addr);
 
+    // The chatrooms are looked up from the subscriptions, as the user may have joined new chatrooms since connecting
+    // The ids are collected first, so that the subscriptions are not modified while being iterated over
+    let chatroom_ids: Vec<i32> = chatroom_subscriptions
+        .iter()
+        .filter(|subscribers| subscribers.contains_key(&user_id))
+        .map(|subscribers| *subscribers.key())
+        .collect();
+
     // Disconnect the user from every one of the chatrooms they're present in.
     for chatroom_id in chatroom_ids {
         match chatroom_subscriptions.get_mut(&chatroom_id) {
@@ -274,15 +430,18 @@ pub fn disconnect_user_from_server(
                 websocket_list.remove(&user_id);
 
                 if websocket_list.is_empty() {
-                    // We can unwrap here because if it didnt exist it wouldve been caught already
-                    let (_, (handler_cancel_token, _)) =
-                        available_chatrooms_handle.remove(&chatroom_id).unwrap();
-
-                    // Cancel handler
-                    handler_cancel_token.cancel();
-
-                    // Log in console
-                    info!("Removing chatroom: {chatroom_id} as there are no participants left.");
+                    // The handler may have been closed already, for example if the chatroom was deleted in the meantime
+                    if let Some((_, (handler_cancel_token, _))) =
+                        available_chatrooms_handle.remove(&chatroom_id)
+                    {
+                        // Cancel handler
+                        handler_cancel_token.cancel();
+
+                        // Log in console
+                        info!(
+                            "Removing chatroom: {chatroom_id} as there are no participants left."
+                        );
+                    }
                 }
             }
             None => {
@@ -321,8 +480,10 @@ pub fn create_chatroom_handler(
                 // If this returns an error it means there are no more clients left.
                 // We can close the chatroom handler if thats the case
                 Ok(recv_msg) = receiver.recv() => {
-                    // We can safely unwrap here
-                    let chatroom_subs = chatroom_subscriptions.get(&this_chatroom_id).unwrap();
+                    // The subscriptions are only missing if the chatroom is being closed
+                    let Some(chatroom_subs) = chatroom_subscriptions.get(&this_chatroom_id) else {
+                        break;
+                    };
 
                     let subs = chatroom_subs.value();
 
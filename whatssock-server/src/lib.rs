@@ -1,13 +1,20 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use axum::extract::ws::Message;
+use chrono::TimeDelta;
 use dashmap::{DashMap, DashSet};
 use diesel::{PgConnection, r2d2::ConnectionManager};
 use tokio::sync::broadcast::Sender;
 use tokio_util::sync::CancellationToken;
 
+use crate::{
+    api::account_management::DeletedAccountMessagePolicy, mail::Mailer, rate_limit::RateLimiter,
+};
+
 pub mod api;
+pub mod mail;
 pub mod models;
+pub mod rate_limit;
 pub mod schema;
 
 pub type PgPool = r2d2::Pool<ConnectionManager<PgConnection>>;
@@ -26,4 +33,20 @@ pub struct ServerState {
     pub chatroom_subscriptions:
         Arc<DashMap<i32, DashMap<i32, tokio::sync::mpsc::Sender<axum::extract::ws::Message>>>>,
     pub currently_open_connections: Arc<DashSet<SocketAddr>>,
+    /// The WebSocket connection of every online user, so that they can be subscribed to the chatrooms they join while being online.
+    pub connected_users: Arc<DashMap<i32, tokio::sync::mpsc::Sender<axum::extract::ws::Message>>>,
+    /// Limits the failed login attempts per IP address and username.
+    pub login_rate_limiter: Arc<RateLimiter>,
+    /// Limits the registration attempts per IP address and username.
+    pub register_rate_limiter: Arc<RateLimiter>,
+    /// Limits the password reset requests per IP address and username.
+    pub password_reset_rate_limiter: Arc<RateLimiter>,
+    /// Limits the failed attempts at joining chatrooms with their password per IP address and user.
+    pub chatroom_password_rate_limiter: Arc<RateLimiter>,
+    /// The transport used to send emails to the users.
+    pub mailer: Arc<dyn Mailer>,
+    /// Decides whether the messages of deleted accounts are deleted or anonymised.
+    pub deleted_account_message_policy: DeletedAccountMessagePolicy,
+    /// How long messages can be edited after they have been sent, there is no limit if this is `None`.
+    pub message_edit_window: Option<TimeDelta>,
 }
@@ -0,0 +1,173 @@
+use std::{
+    env,
+    fmt::Debug,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use lettre::{
+    Message, SmtpTransport, Transport, message::Mailbox,
+    transport::smtp::authentication::Credentials,
+};
+use log::{error, info};
+
+/// An email sent by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mail {
+    /// The address the email is sent to.
+    pub to: String,
+    pub subject: String,
+    /// The plain text body of the email.
+    pub body: String,
+}
+
+/// The transport the server delivers its emails through.
+/// Implementations may block, use [`send_in_background`] to send emails from a request handler.
+pub trait Mailer: Debug + Send + Sync {
+    fn send(&self, mail: &Mail) -> anyhow::Result<()>;
+}
+
+/// Delivers the emails through an SMTP relay.
+#[derive(Debug)]
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Creates a mailer which connects to the relay at `host` with TLS.
+    pub fn new(
+        host: &str,
+        credentials: Option<Credentials>,
+        from: Mailbox,
+    ) -> anyhow::Result<Self> {
+        let mut transport_builder = SmtpTransport::relay(host)?;
+
+        if let Some(credentials) = credentials {
+            transport_builder = transport_builder.credentials(credentials);
+        }
+
+        Ok(Self {
+            transport: transport_builder.build(),
+            from,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(mail.to.parse()?)
+            .subject(mail.subject.clone())
+            .body(mail.body.clone())?;
+
+        self.transport.send(&message)?;
+
+        Ok(())
+    }
+}
+
+/// Writes every email into its own file in a directory instead of sending it.
+/// This is meant for local development, where there is no SMTP relay to send the emails through.
+#[derive(Debug)]
+pub struct FileMailer {
+    directory: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(directory: PathBuf) -> anyhow::Result<Self> {
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self { directory })
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        let file_name = format!(
+            "{}_{:016x}.txt",
+            Utc::now().format("%Y%m%d%H%M%S"),
+            rand::random::<u64>()
+        );
+
+        fs::write(
+            self.directory.join(file_name),
+            format!(
+                "To: {}\nSubject: {}\n\n{}\n",
+                mail.to, mail.subject, mail.body
+            ),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Keeps every email in memory, so that tests can inspect what would have been sent.
+#[derive(Debug, Default)]
+pub struct InMemoryMailer {
+    sent_mails: Mutex<Vec<Mail>>,
+}
+
+impl InMemoryMailer {
+    /// Returns every email sent through this mailer so far.
+    pub fn sent_mails(&self) -> Vec<Mail> {
+        self.sent_mails.lock().unwrap().clone()
+    }
+}
+
+impl Mailer for InMemoryMailer {
+    fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        self.sent_mails.lock().unwrap().push(mail.clone());
+
+        Ok(())
+    }
+}
+
+/// Creates the mailer configured in the environment.
+/// If `SMTP_HOST` is set the emails are sent through that relay from `MAIL_FROM`, authenticating with `SMTP_USERNAME` and `SMTP_PASSWORD` if they are set.
+/// Otherwise the emails are written into `MAIL_OUTBOX_DIR`, or `./mail_outbox` if that is not set either.
+pub fn mailer_from_env() -> anyhow::Result<Arc<dyn Mailer>> {
+    if let Ok(smtp_host) = env::var("SMTP_HOST") {
+        let from = env::var("MAIL_FROM")
+            .context("MAIL_FROM must be set if SMTP_HOST is set")?
+            .parse::<Mailbox>()?;
+
+        let credentials = match (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            (Ok(smtp_username), Ok(smtp_password)) => {
+                Some(Credentials::new(smtp_username, smtp_password))
+            }
+            _ => None,
+        };
+
+        info!("Sending emails through the SMTP relay at {smtp_host}...");
+
+        return Ok(Arc::new(SmtpMailer::new(&smtp_host, credentials, from)?));
+    }
+
+    let outbox_directory =
+        PathBuf::from(env::var("MAIL_OUTBOX_DIR").unwrap_or_else(|_| String::from("mail_outbox")));
+
+    info!(
+        "SMTP_HOST is not set, writing emails into {}...",
+        outbox_directory.display()
+    );
+
+    Ok(Arc::new(FileMailer::new(outbox_directory)?))
+}
+
+/// Sends the email on a blocking thread, so that the request which triggered it does not have to wait for the delivery.
+/// Failed deliveries are only logged.
+pub fn send_in_background(mailer: Arc<dyn Mailer>, mail: Mail) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = mailer.send(&mail) {
+            error!(
+                "An error occured while sending an email to {}: {}",
+                mail.to, err
+            );
+        }
+    });
+}
@@ -1,10 +1,13 @@
-use std::{env, net::SocketAddr, sync::Arc};
+use std::{env, net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
     Router,
     body::Body,
     extract::Request,
-    http::{Response, StatusCode},
+    http::{
+        HeaderValue, Response, StatusCode,
+        header::{AUTHORIZATION, COOKIE},
+    },
     middleware::{self, Next},
     routing::{any, get, post},
     serve,
@@ -18,16 +21,37 @@ use dotenvy::dotenv;
 use env_logger::Env;
 use log::info;
 use tokio::net::TcpListener;
-use whatssock_lib::domain_paths::{GET_FETCH_MESSAGES, GET_FETCH_USER, POST_LOGIN, POST_LOGOUT, POST_NEW_CHATROOM, POST_REGISTER, POST_REQUEST_K_CHATROOM, POST_REQUEST_UK_CHATROOM, POST_SESSION_VERIFICATION, WS_ESTABLISH_CHATROOM_CONNECTION};
+use whatssock_lib::domain_paths::{GET_FETCH_AVATAR, GET_FETCH_CHATROOM_ICON, GET_FETCH_MESSAGES, GET_FETCH_USER, POST_ADD_REACTION, POST_BAN_MEMBER, POST_CHANGE_PASSWORD, POST_CREATE_INVITE, POST_DELETE_ACCOUNT, POST_DELETE_CHATROOM, POST_DELETE_MESSAGE, POST_EDIT_MESSAGE, POST_EXPORT_ACCOUNT, POST_FETCH_MESSAGE_EDITS, POST_FORGOT_PASSWORD, POST_JOIN_BY_INVITE, POST_KICK_MEMBER, POST_LEAVE_CHATROOM, POST_LIST_BANS, POST_LIST_INVITES, POST_LIST_SESSIONS, POST_LOGIN, POST_LOGIN_TOTP, POST_LOGOUT, POST_MUTE_MEMBER, POST_NEW_CHATROOM, POST_REFRESH_SESSION, POST_REGISTER, POST_REMOVE_REACTION, POST_REQUEST_K_CHATROOM, POST_REQUEST_UK_CHATROOM, POST_RESEND_VERIFICATION_EMAIL, POST_RESET_PASSWORD, POST_REVOKE_INVITE, POST_REVOKE_OTHER_SESSIONS, POST_REVOKE_SESSION, POST_SEARCH_USERS, POST_SESSION_VERIFICATION, POST_START_DIRECT_MESSAGE, POST_TOTP_CONFIRM, POST_TOTP_DISABLE, POST_TOTP_ENROLL, POST_TRANSFER_OWNERSHIP, POST_UNBAN_MEMBER, POST_UPDATE_AVATAR, POST_UPDATE_CHATROOM_ICON, POST_UPDATE_CHATROOM_SETTINGS, POST_UPDATE_MEMBER_ROLE, POST_UPDATE_PRIVACY, POST_UPDATE_PROFILE, POST_VERIFY_EMAIL, WS_ESTABLISH_CHATROOM_CONNECTION};
 use whatssock_server::{
     ServerState,
+    mail::mailer_from_env,
+    rate_limit::{RateLimitConfig, RateLimiter},
     api::{
+        account_management::{DeletedAccountMessagePolicy, delete_account, export_account_data},
+        chatroom_settings::{
+            delete_chatroom, fetch_chatroom_icon, update_chatroom_icon, update_chatroom_settings,
+        },
         chatrooms::{
             create_chatroom, fetch_known_chatrooms, fetch_messages, fetch_unknown_chatroom,
-            fetch_user,
+            fetch_user, leave_chatroom, start_direct_message,
+        },
+        email_verification::{resend_verification_email, verify_email},
+        invites::{
+            create_chatroom_invite, join_by_invite, list_chatroom_invites, revoke_chatroom_invite,
         },
+        memberships::{transfer_chatroom_ownership, update_member_role},
+        messages::{
+            delete_message, edit_message, fetch_message_edits, message_edit_window_from_env,
+        },
+        moderation::{ban_member, kick_member, list_chatroom_bans, mute_member, unban_member},
+        password_reset::{request_password_reset, reset_password},
+        profiles::{fetch_avatar, search_users, update_avatar, update_privacy, update_profile},
+        reactions::{add_reaction, remove_reaction},
+        two_factor::{complete_two_factor_login, confirm_totp, disable_totp, enroll_totp},
         user_account_control::{
-            fetch_login, fetch_user_information_from_session, handle_logout_request, register_user,
+            change_password, fetch_active_sessions, fetch_login, fetch_user_information_from_session,
+            handle_logout_request, refresh_user_session, register_user, revoke_other_sessions,
+            revoke_session,
         },
         websocket::handler,
     },
@@ -38,7 +62,17 @@ async fn log_request(request: Request<Body>, next: Next) -> Result<Response<Body
     let uri = request.uri().clone();
 
     println!("> Incoming: {} {}", method, uri);
-    println!("> Headers: {:?}", request.headers());
+    // The session tokens are sent in these headers, they must never end up in the logs
+    let mut headers = request.headers().clone();
+
+    for header_name in [AUTHORIZATION, COOKIE] {
+        // Inserting replaces every value of the header, in case it was sent more than once
+        if headers.contains_key(&header_name) {
+            headers.insert(header_name, HeaderValue::from_static("<redacted>"));
+        }
+    }
+
+    println!("> Headers: {:?}", headers);
 
     let response = next.run(request).await;
 
@@ -57,19 +91,64 @@ async fn main() -> anyhow::Result<()> {
     // Establish connection with the database
     let servere_state = establish_state()?;
 
+    // Periodically clean up the rate limiters' expired entries
+    spawn_rate_limiter_cleanup(&servere_state);
+
     // Start up the webserver
     let router = Router::new()
         .route(POST_REGISTER, post(register_user))
         .route(POST_LOGIN, post(fetch_login))
+        .route(POST_LOGIN_TOTP, post(complete_two_factor_login))
         .route(POST_SESSION_VERIFICATION, post(fetch_user_information_from_session))
+        .route(POST_REFRESH_SESSION, post(refresh_user_session))
         .route(POST_LOGOUT, post(handle_logout_request))
+        .route(POST_VERIFY_EMAIL, post(verify_email))
+        .route(POST_RESEND_VERIFICATION_EMAIL, post(resend_verification_email))
+        .route(POST_CHANGE_PASSWORD, post(change_password))
+        .route(POST_FORGOT_PASSWORD, post(request_password_reset))
+        .route(POST_RESET_PASSWORD, post(reset_password))
+        .route(POST_TOTP_ENROLL, post(enroll_totp))
+        .route(POST_TOTP_CONFIRM, post(confirm_totp))
+        .route(POST_TOTP_DISABLE, post(disable_totp))
+        .route(POST_EXPORT_ACCOUNT, post(export_account_data))
+        .route(POST_DELETE_ACCOUNT, post(delete_account))
+        .route(POST_UPDATE_PROFILE, post(update_profile))
+        .route(POST_UPDATE_AVATAR, post(update_avatar))
+        .route(POST_UPDATE_PRIVACY, post(update_privacy))
+        .route(POST_SEARCH_USERS, post(search_users))
+        .route(POST_LIST_SESSIONS, post(fetch_active_sessions))
+        .route(POST_REVOKE_SESSION, post(revoke_session))
+        .route(POST_REVOKE_OTHER_SESSIONS, post(revoke_other_sessions))
         .route(
             POST_REQUEST_UK_CHATROOM,
             post(fetch_unknown_chatroom),
         )
         .route(POST_REQUEST_K_CHATROOM, post(fetch_known_chatrooms))
         .route(POST_NEW_CHATROOM, post(create_chatroom))
+        .route(POST_START_DIRECT_MESSAGE, post(start_direct_message))
+        .route(POST_LEAVE_CHATROOM, post(leave_chatroom))
+        .route(POST_UPDATE_MEMBER_ROLE, post(update_member_role))
+        .route(POST_TRANSFER_OWNERSHIP, post(transfer_chatroom_ownership))
+        .route(POST_KICK_MEMBER, post(kick_member))
+        .route(POST_BAN_MEMBER, post(ban_member))
+        .route(POST_UNBAN_MEMBER, post(unban_member))
+        .route(POST_LIST_BANS, post(list_chatroom_bans))
+        .route(POST_MUTE_MEMBER, post(mute_member))
+        .route(POST_CREATE_INVITE, post(create_chatroom_invite))
+        .route(POST_LIST_INVITES, post(list_chatroom_invites))
+        .route(POST_REVOKE_INVITE, post(revoke_chatroom_invite))
+        .route(POST_JOIN_BY_INVITE, post(join_by_invite))
+        .route(POST_UPDATE_CHATROOM_SETTINGS, post(update_chatroom_settings))
+        .route(POST_UPDATE_CHATROOM_ICON, post(update_chatroom_icon))
+        .route(POST_DELETE_CHATROOM, post(delete_chatroom))
+        .route(POST_EDIT_MESSAGE, post(edit_message))
+        .route(POST_DELETE_MESSAGE, post(delete_message))
+        .route(POST_FETCH_MESSAGE_EDITS, post(fetch_message_edits))
+        .route(POST_ADD_REACTION, post(add_reaction))
+        .route(POST_REMOVE_REACTION, post(remove_reaction))
         .route(GET_FETCH_USER, get(fetch_user))
+        .route(GET_FETCH_AVATAR, get(fetch_avatar))
+        .route(GET_FETCH_CHATROOM_ICON, get(fetch_chatroom_icon))
         .route(GET_FETCH_MESSAGES, get(fetch_messages))
         .route(WS_ESTABLISH_CHATROOM_CONNECTION, any(handler))
         .layer(middleware::from_fn(log_request))
@@ -90,6 +169,27 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Spawns a task which removes the stale entries of the rate limiters every minute.
+fn spawn_rate_limiter_cleanup(state: &ServerState) {
+    let login_rate_limiter = state.login_rate_limiter.clone();
+    let register_rate_limiter = state.register_rate_limiter.clone();
+    let password_reset_rate_limiter = state.password_reset_rate_limiter.clone();
+    let chatroom_password_rate_limiter = state.chatroom_password_rate_limiter.clone();
+
+    tokio::spawn(async move {
+        let mut cleanup_interval = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            cleanup_interval.tick().await;
+
+            login_rate_limiter.remove_stale_entries();
+            register_rate_limiter.remove_stale_entries();
+            password_reset_rate_limiter.remove_stale_entries();
+            chatroom_password_rate_limiter.remove_stale_entries();
+        }
+    });
+}
+
 /// Establishes connection with the PostgreSQL database.
 pub fn establish_state() -> anyhow::Result<ServerState> {
     // Read the database url from the .env
@@ -109,5 +209,25 @@ pub fn establish_state() -> anyhow::Result<ServerState> {
         chatroom_subscriptions: Arc::new(DashMap::new()),
         currently_online_chatrooms: Arc::new(DashMap::new()),
         currently_open_connections: Arc::new(DashSet::new()),
+        connected_users: Arc::new(DashMap::new()),
+        login_rate_limiter: Arc::new(RateLimiter::new(RateLimitConfig::from_env(
+            "LOGIN_RATE_LIMIT",
+            RateLimitConfig::login_defaults(),
+        ))),
+        register_rate_limiter: Arc::new(RateLimiter::new(RateLimitConfig::from_env(
+            "REGISTER_RATE_LIMIT",
+            RateLimitConfig::register_defaults(),
+        ))),
+        password_reset_rate_limiter: Arc::new(RateLimiter::new(RateLimitConfig::from_env(
+            "PASSWORD_RESET_RATE_LIMIT",
+            RateLimitConfig::password_reset_defaults(),
+        ))),
+        chatroom_password_rate_limiter: Arc::new(RateLimiter::new(RateLimitConfig::from_env(
+            "CHATROOM_PASSWORD_RATE_LIMIT",
+            RateLimitConfig::chatroom_password_defaults(),
+        ))),
+        mailer: mailer_from_env()?,
+        deleted_account_message_policy: DeletedAccountMessagePolicy::from_env()?,
+        message_edit_window: message_edit_window_from_env()?,
     })
 }
@@ -14,8 +14,15 @@ pub struct UserAccountEntry {
     pub username: String,
     pub passw: String,
     pub email: String,
-    pub chatrooms_joined: Vec<Option<i32>>,
     pub created_at: chrono::NaiveDate,
+    pub email_verified: bool,
+    pub totp_secret: Option<Vec<u8>>,
+    pub totp_enabled: bool,
+    pub totp_last_used_step: Option<i64>,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub status_text: Option<String>,
+    pub discoverable: bool,
 }
 
 #[derive(Debug, Clone, Insertable)]
@@ -24,10 +31,28 @@ pub struct UserAccountEntry {
 pub struct NewUserAccount {
     pub username: String,
     pub passw: String,
-    pub chatrooms_joined: Vec<Option<i32>>,
     pub email: String,
 }
 
+#[derive(Debug, Clone, AsChangeset)]
+#[diesel(table_name = crate::schema::users)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(treat_none_as_null = true)]
+pub struct UpdateUserProfile {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub status_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable, Insertable)]
+#[diesel(table_name = crate::schema::user_avatars)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UserAvatarEntry {
+    pub user_id: i32,
+    pub image: Vec<u8>,
+    pub updated_at: NaiveDateTime,
+}
+
 #[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
 #[diesel(table_name = crate::schema::user_session_auth)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -36,6 +61,10 @@ pub struct UserSessionEntry {
     pub user_id: i32,
     pub session_token: Vec<u8>,
     pub encryption_key: Vec<u8>,
+    pub device_label: String,
+    pub created_at: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
 }
 
 #[derive(Debug, Clone, Insertable, AsChangeset)]
@@ -44,7 +73,82 @@ pub struct UserSessionEntry {
 pub struct NewUserSession {
     pub user_id: i32,
     pub session_token: Vec<u8>,
-    pub encryption_key: Vec<u8>
+    pub encryption_key: Vec<u8>,
+    pub device_label: String,
+    pub created_at: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
+#[diesel(table_name = crate::schema::email_verification_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EmailVerificationTokenEntry {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: Vec<u8>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = crate::schema::email_verification_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewEmailVerificationToken {
+    pub user_id: i32,
+    pub token_hash: Vec<u8>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
+#[diesel(table_name = crate::schema::password_reset_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PasswordResetTokenEntry {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: Vec<u8>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = crate::schema::password_reset_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewPasswordResetToken {
+    pub user_id: i32,
+    pub token_hash: Vec<u8>,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = crate::schema::totp_recovery_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewTotpRecoveryCode {
+    pub user_id: i32,
+    pub code_hash: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
+#[diesel(table_name = crate::schema::login_challenges)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LoginChallengeEntry {
+    pub id: i32,
+    pub user_id: i32,
+    pub challenge_hash: Vec<u8>,
+    pub device_label: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = crate::schema::login_challenges)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewLoginChallenge {
+    pub user_id: i32,
+    pub challenge_hash: Vec<u8>,
+    pub device_label: String,
+    pub expires_at: NaiveDateTime,
 }
 
 #[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
@@ -55,9 +159,19 @@ pub struct ChatroomEntry {
     pub chatroom_id: String,
     pub chatroom_name: String,
     pub chatroom_password: Option<String>,
-    pub participants: Vec<Option<i32>>,
     pub is_direct_message: bool,
     pub last_message_id: Option<i32>,
+    pub chatroom_description: Option<String>,
+}
+
+/// The fields which are `None` are left unchanged.
+#[derive(Debug, Clone, AsChangeset)]
+#[diesel(table_name = crate::schema::chatrooms)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UpdateChatroomSettings {
+    pub chatroom_name: Option<String>,
+    pub chatroom_description: Option<Option<String>>,
+    pub chatroom_password: Option<Option<String>>,
 }
 
 #[derive(Debug, Clone, AsChangeset)]
@@ -74,11 +188,88 @@ pub struct NewChatroom {
     pub chatroom_id: String,
     pub chatroom_name: String,
     pub chatroom_password: Option<String>,
-    pub participants: Vec<i32>,
     pub is_direct_message: bool,
     pub last_message_id: Option<i32>,
 }
 
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable, Insertable)]
+#[diesel(table_name = crate::schema::chatroom_icons)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ChatroomIconEntry {
+    pub chatroom_id: i32,
+    pub image: Vec<u8>,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
+#[diesel(table_name = crate::schema::chatroom_members)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ChatroomMemberEntry {
+    pub chatroom_id: i32,
+    pub user_id: i32,
+    pub role: String,
+    pub joined_at: NaiveDateTime,
+    pub muted_until: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = crate::schema::chatroom_members)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewChatroomMember {
+    pub chatroom_id: i32,
+    pub user_id: i32,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
+#[diesel(table_name = crate::schema::chatroom_bans)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ChatroomBanEntry {
+    pub chatroom_id: i32,
+    pub user_id: i32,
+    pub banned_by: i32,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::chatroom_bans)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(treat_none_as_null = true)]
+pub struct NewChatroomBan {
+    pub chatroom_id: i32,
+    pub user_id: i32,
+    pub banned_by: i32,
+    pub reason: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
+#[diesel(table_name = crate::schema::chatroom_invites)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ChatroomInviteEntry {
+    pub id: i32,
+    pub chatroom_id: i32,
+    pub created_by: i32,
+    pub code_hash: Vec<u8>,
+    pub uses: i32,
+    pub max_uses: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = crate::schema::chatroom_invites)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewChatroomInvite {
+    pub chatroom_id: i32,
+    pub created_by: i32,
+    pub code_hash: Vec<u8>,
+    pub max_uses: Option<i32>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
 #[derive(Debug, Clone, Insertable)]
 #[diesel(table_name = crate::schema::messages)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -88,6 +279,7 @@ pub struct NewMessage {
     pub send_date: NaiveDateTime,
     pub replying_to_msg: Option<i32>,
     pub raw_message: Vec<u8>,
+    pub thread_root_id: Option<i32>,
 }
 
 #[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
@@ -101,4 +293,37 @@ pub struct MessageEntry {
     pub parent_chatroom_id: i32,
     pub raw_message: Vec<u8>,
     pub send_date: NaiveDateTime,
+    pub edited_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub thread_root_id: Option<i32>,
+    pub thread_reply_count: i32,
+    pub thread_last_reply_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Selectable, QueryableByName, Queryable)]
+#[diesel(table_name = crate::schema::message_edits)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct MessageEditEntry {
+    pub id: i32,
+    pub message_id: i32,
+    pub raw_message: Vec<u8>,
+    pub edited_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = crate::schema::message_edits)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewMessageEdit {
+    pub message_id: i32,
+    pub raw_message: Vec<u8>,
+    pub edited_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = crate::schema::message_reactions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewMessageReaction {
+    pub message_id: i32,
+    pub user_id: i32,
+    pub emoji: String,
 }
@@ -0,0 +1,360 @@
+use std::{
+    env,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    http::{StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+
+/// Configuration of a [`RateLimiter`].
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    /// The amount of attempts allowed within `attempt_window` before the key gets locked out.
+    pub max_attempts: u32,
+    /// Attempts older than this are forgotten.
+    pub attempt_window: Duration,
+    /// The duration of the first lockout, every consecutive lockout doubles it.
+    pub base_lockout: Duration,
+    /// The lockout duration never grows past this.
+    pub max_lockout: Duration,
+}
+
+impl RateLimitConfig {
+    /// The default limits for failed login attempts.
+    pub fn login_defaults() -> Self {
+        Self {
+            max_attempts: 5,
+            attempt_window: Duration::from_secs(15 * 60),
+            base_lockout: Duration::from_secs(30),
+            max_lockout: Duration::from_secs(60 * 60),
+        }
+    }
+
+    /// The default limits for registration attempts.
+    pub fn register_defaults() -> Self {
+        Self {
+            max_attempts: 5,
+            attempt_window: Duration::from_secs(60 * 60),
+            base_lockout: Duration::from_secs(5 * 60),
+            max_lockout: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    /// The default limits for failed attempts at joining chatrooms with their password.
+    pub fn chatroom_password_defaults() -> Self {
+        Self {
+            max_attempts: 5,
+            attempt_window: Duration::from_secs(15 * 60),
+            base_lockout: Duration::from_secs(60),
+            max_lockout: Duration::from_secs(60 * 60),
+        }
+    }
+
+    /// The default limits for password reset requests.
+    pub fn password_reset_defaults() -> Self {
+        Self {
+            max_attempts: 3,
+            attempt_window: Duration::from_secs(60 * 60),
+            base_lockout: Duration::from_secs(15 * 60),
+            max_lockout: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    /// Overrides the values of `defaults` with the ones set in the environment.
+    /// The variables read are `{prefix}_MAX_ATTEMPTS`, `{prefix}_ATTEMPT_WINDOW_SECS`, `{prefix}_BASE_LOCKOUT_SECS` and `{prefix}_MAX_LOCKOUT_SECS`.
+    pub fn from_env(prefix: &str, defaults: Self) -> Self {
+        let read_var = |name: &str| {
+            env::var(format!("{prefix}_{name}"))
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+
+        Self {
+            max_attempts: read_var("MAX_ATTEMPTS")
+                .map(|value| value as u32)
+                .unwrap_or(defaults.max_attempts),
+            attempt_window: read_var("ATTEMPT_WINDOW_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.attempt_window),
+            base_lockout: read_var("BASE_LOCKOUT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.base_lockout),
+            max_lockout: read_var("MAX_LOCKOUT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.max_lockout),
+        }
+    }
+}
+
+/// The identity an attempt is counted against.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Ip(IpAddr),
+    Username(String),
+    /// Used by the endpoints which can only be called by logged in users.
+    UserId(i32),
+}
+
+#[derive(Debug, Clone)]
+struct RateLimitEntry {
+    /// The amount of attempts in the current window.
+    attempts: u32,
+    window_start: Instant,
+    /// The amount of consecutive lockouts, used for the exponential backoff.
+    lockout_count: u32,
+    locked_until: Option<Instant>,
+}
+
+/// Counts attempts per [`RateLimitKey`] in memory, and locks keys out with an exponential backoff once they exceed the configured limit.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    entries: DashMap<RateLimitKey, RateLimitEntry>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            entries: DashMap::new(),
+        }
+    }
+
+    /// Returns [`RateLimitedError::TooManyRequests`] if any of the keys are currently locked out.
+    pub fn check(&self, keys: &[RateLimitKey]) -> Result<(), RateLimitedError> {
+        let now = Instant::now();
+
+        let retry_after = keys
+            .iter()
+            .filter_map(|key| {
+                self.entries
+                    .get(key)
+                    .and_then(|entry| entry.locked_until)
+                    .filter(|locked_until| *locked_until > now)
+                    .map(|locked_until| locked_until - now)
+            })
+            .max();
+
+        match retry_after {
+            Some(retry_after) => Err(RateLimitedError::TooManyRequests { retry_after }),
+            None => Ok(()),
+        }
+    }
+
+    /// Records an attempt for every key, locking out the ones which have exceeded the limit.
+    pub fn record_attempt(&self, keys: &[RateLimitKey]) {
+        let now = Instant::now();
+
+        for key in keys {
+            let mut entry = self.entries.entry(key.clone()).or_insert(RateLimitEntry {
+                attempts: 0,
+                window_start: now,
+                lockout_count: 0,
+                locked_until: None,
+            });
+
+            // Forget the consecutive lockouts if the key has behaved since the last one
+            if entry
+                .locked_until
+                .is_some_and(|locked_until| locked_until + self.config.max_lockout < now)
+            {
+                entry.lockout_count = 0;
+                entry.locked_until = None;
+            }
+
+            // Start a new window if the current one has passed
+            if entry.window_start + self.config.attempt_window < now {
+                entry.attempts = 0;
+                entry.window_start = now;
+            }
+
+            entry.attempts += 1;
+
+            if entry.attempts >= self.config.max_attempts {
+                let lockout = self
+                    .config
+                    .base_lockout
+                    .saturating_mul(2_u32.saturating_pow(entry.lockout_count))
+                    .min(self.config.max_lockout);
+
+                entry.locked_until = Some(now + lockout);
+                entry.lockout_count += 1;
+                entry.attempts = 0;
+                entry.window_start = now;
+            }
+        }
+    }
+
+    /// Forgets every attempt of the key, this should be called after a successful attempt.
+    pub fn reset(&self, key: &RateLimitKey) {
+        self.entries.remove(key);
+    }
+
+    /// Removes the entries which no longer affect the key's limits, so that the map does not grow indefinitely.
+    pub fn remove_stale_entries(&self) {
+        let now = Instant::now();
+
+        self.entries.retain(|_, entry| {
+            let is_window_active = entry.window_start + self.config.attempt_window >= now;
+            let is_lockout_relevant = entry
+                .locked_until
+                .is_some_and(|locked_until| locked_until + self.config.max_lockout >= now);
+
+            is_window_active || is_lockout_relevant
+        });
+    }
+}
+
+/// The error returned by rate limited endpoints.
+/// Every other error is still reported with a plain [`StatusCode`].
+#[derive(Debug, Clone, Copy)]
+pub enum RateLimitedError {
+    Status(StatusCode),
+    TooManyRequests { retry_after: Duration },
+}
+
+impl From<StatusCode> for RateLimitedError {
+    fn from(status_code: StatusCode) -> Self {
+        Self::Status(status_code)
+    }
+}
+
+impl IntoResponse for RateLimitedError {
+    fn into_response(self) -> Response {
+        match self {
+            RateLimitedError::Status(status_code) => status_code.into_response(),
+            RateLimitedError::TooManyRequests { retry_after } => {
+                // Round up, so that the client never retries too early
+                let retry_after_secs =
+                    retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(RETRY_AFTER, retry_after_secs.to_string())],
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, thread};
+
+    use super::*;
+
+    fn test_config() -> RateLimitConfig {
+        RateLimitConfig {
+            max_attempts: 3,
+            attempt_window: Duration::from_secs(60),
+            base_lockout: Duration::from_secs(10),
+            max_lockout: Duration::from_secs(60),
+        }
+    }
+
+    fn retry_after(rate_limiter: &RateLimiter, key: &RateLimitKey) -> Option<Duration> {
+        match rate_limiter.check(std::slice::from_ref(key)) {
+            Ok(()) => None,
+            Err(RateLimitedError::TooManyRequests { retry_after }) => Some(retry_after),
+            Err(RateLimitedError::Status(status_code)) => {
+                panic!("Unexpected status code: {status_code}")
+            }
+        }
+    }
+
+    #[test]
+    fn locks_out_after_max_attempts() {
+        let rate_limiter = RateLimiter::new(test_config());
+        let key = RateLimitKey::Username(String::from("alice"));
+        let other_key = RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        for _ in 0..2 {
+            rate_limiter.record_attempt(std::slice::from_ref(&key));
+        }
+
+        assert!(retry_after(&rate_limiter, &key).is_none());
+
+        rate_limiter.record_attempt(std::slice::from_ref(&key));
+
+        assert!(retry_after(&rate_limiter, &key).is_some());
+        assert!(retry_after(&rate_limiter, &other_key).is_none());
+
+        // A request is rejected if any of its keys are locked out
+        assert!(rate_limiter.check(&[other_key, key.clone()]).is_err());
+
+        rate_limiter.reset(&key);
+
+        assert!(retry_after(&rate_limiter, &key).is_none());
+    }
+
+    #[test]
+    fn lockout_doubles_until_max_lockout() {
+        let rate_limiter = RateLimiter::new(test_config());
+        let key = RateLimitKey::Username(String::from("alice"));
+
+        let mut lockouts = Vec::new();
+
+        for _ in 0..4 {
+            for _ in 0..3 {
+                rate_limiter.record_attempt(std::slice::from_ref(&key));
+            }
+
+            lockouts.push(retry_after(&rate_limiter, &key).unwrap());
+        }
+
+        // The lockouts are measured slightly after they were set, so allow for some elapsed time
+        let expected_secs = [10, 20, 40, 60];
+
+        for (lockout, expected_secs) in lockouts.iter().zip(expected_secs) {
+            assert!(*lockout <= Duration::from_secs(expected_secs));
+            assert!(*lockout > Duration::from_secs(expected_secs - 1));
+        }
+    }
+
+    #[test]
+    fn attempts_are_forgotten_after_window() {
+        let rate_limiter = RateLimiter::new(RateLimitConfig {
+            attempt_window: Duration::from_millis(50),
+            ..test_config()
+        });
+        let key = RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        for _ in 0..2 {
+            rate_limiter.record_attempt(std::slice::from_ref(&key));
+        }
+
+        thread::sleep(Duration::from_millis(100));
+
+        for _ in 0..2 {
+            rate_limiter.record_attempt(std::slice::from_ref(&key));
+        }
+
+        assert!(retry_after(&rate_limiter, &key).is_none());
+
+        rate_limiter.record_attempt(std::slice::from_ref(&key));
+
+        assert!(retry_after(&rate_limiter, &key).is_some());
+    }
+
+    #[test]
+    fn stale_entries_are_removed() {
+        let rate_limiter = RateLimiter::new(RateLimitConfig {
+            attempt_window: Duration::from_millis(50),
+            ..test_config()
+        });
+        let key = RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        rate_limiter.record_attempt(std::slice::from_ref(&key));
+
+        thread::sleep(Duration::from_millis(100));
+
+        rate_limiter.remove_stale_entries();
+
+        assert!(rate_limiter.entries.is_empty());
+    }
+}
@@ -1,14 +1,95 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    chatroom_bans (chatroom_id, user_id) {
+        chatroom_id -> Int4,
+        user_id -> Int4,
+        banned_by -> Int4,
+        reason -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    chatroom_icons (chatroom_id) {
+        chatroom_id -> Int4,
+        image -> Bytea,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    chatroom_invites (id) {
+        id -> Int4,
+        chatroom_id -> Int4,
+        created_by -> Int4,
+        code_hash -> Bytea,
+        uses -> Int4,
+        max_uses -> Nullable<Int4>,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    chatroom_members (chatroom_id, user_id) {
+        chatroom_id -> Int4,
+        user_id -> Int4,
+        role -> Varchar,
+        joined_at -> Timestamp,
+        muted_until -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     chatrooms (id) {
         id -> Int4,
         chatroom_id -> Varchar,
         chatroom_name -> Varchar,
         chatroom_password -> Nullable<Varchar>,
-        participants -> Array<Nullable<Int4>>,
         is_direct_message -> Bool,
         last_message_id -> Nullable<Int4>,
+        chatroom_description -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    email_verification_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Bytea,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    login_challenges (id) {
+        id -> Int4,
+        user_id -> Int4,
+        challenge_hash -> Bytea,
+        device_label -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    message_edits (id) {
+        id -> Int4,
+        message_id -> Int4,
+        raw_message -> Bytea,
+        edited_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    message_reactions (message_id, user_id, emoji) {
+        message_id -> Int4,
+        user_id -> Int4,
+        emoji -> Varchar,
+        created_at -> Timestamp,
     }
 }
 
@@ -20,6 +101,21 @@ diesel::table! {
         parent_chatroom_id -> Int4,
         raw_message -> Bytea,
         send_date -> Timestamp,
+        edited_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        thread_root_id -> Nullable<Int4>,
+        thread_reply_count -> Int4,
+        thread_last_reply_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    password_reset_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Bytea,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
     }
 }
 
@@ -32,12 +128,32 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    totp_recovery_codes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        code_hash -> Bytea,
+    }
+}
+
+diesel::table! {
+    user_avatars (user_id) {
+        user_id -> Int4,
+        image -> Bytea,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     user_session_auth (token_id) {
         token_id -> Int4,
         user_id -> Int4,
         session_token -> Bytea,
         encryption_key -> Bytea,
+        device_label -> Varchar,
+        created_at -> Timestamp,
+        last_seen -> Timestamp,
+        expires_at -> Timestamp,
     }
 }
 
@@ -47,15 +163,33 @@ diesel::table! {
         username -> Varchar,
         passw -> Varchar,
         email -> Varchar,
-        chatrooms_joined -> Array<Nullable<Int4>>,
         created_at -> Date,
+        email_verified -> Bool,
+        totp_secret -> Nullable<Bytea>,
+        totp_enabled -> Bool,
+        totp_last_used_step -> Nullable<Int8>,
+        display_name -> Nullable<Varchar>,
+        bio -> Nullable<Varchar>,
+        status_text -> Nullable<Varchar>,
+        discoverable -> Bool,
     }
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
+    chatroom_bans,
+    chatroom_icons,
+    chatroom_invites,
+    chatroom_members,
     chatrooms,
+    email_verification_tokens,
+    login_challenges,
+    message_edits,
+    message_reactions,
     messages,
+    password_reset_tokens,
     posts,
+    totp_recovery_codes,
+    user_avatars,
     user_session_auth,
     users,
 );